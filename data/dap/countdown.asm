@@ -0,0 +1,8 @@
+# Count down from the input to 1.
+start:   IN [counter]
+loop:    OUT [counter]
+         ADD [counter], -1, [counter]
+         JNZ [counter], loop
+         HALT
+
+counter: DATA 0
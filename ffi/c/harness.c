@@ -0,0 +1,69 @@
+/* Exercises the C API of the Intcode computer. Prints "ok" on success. */
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "intcode.h"
+
+#define CHECK(condition)                                                       \
+  do {                                                                         \
+    if (!(condition)) {                                                        \
+      printf("%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition);     \
+      exit(1);                                                                 \
+    }                                                                          \
+  } while (0)
+
+int main(void) {
+  /* Output the sum of two inputs, then halt. */
+  const int64_t program[] = {3, 11, 3, 12, 1, 11, 12, 13, 4, 13, 99};
+  Intcode *pc = intcode_new(program, sizeof program / sizeof program[0]);
+  int64_t value = 0;
+
+  CHECK(intcode_run(pc) == INTCODE_STATUS_WAITING);
+  intcode_push_input(pc, 40);
+  CHECK(intcode_run(pc) == INTCODE_STATUS_WAITING);
+  CHECK(!intcode_pop_output(pc, &value));
+
+  /* The snapshot continues independently. */
+  Intcode *copy = intcode_snapshot(pc);
+  intcode_push_input(pc, 2);
+  CHECK(intcode_run(pc) == INTCODE_STATUS_HALTED);
+  CHECK(intcode_pop_output(pc, &value) && value == 42);
+  CHECK(intcode_read(pc, 13) == 42);
+  CHECK(intcode_memory_size(pc) == 14);
+
+  /* Patch the addition into a multiplication. */
+  CHECK(intcode_write(copy, 4, 2));
+  intcode_push_input(copy, 3);
+  CHECK(intcode_run(copy) == INTCODE_STATUS_HALTED);
+  CHECK(intcode_pop_output(copy, &value) && value == 120);
+  CHECK(intcode_read(copy, 1000) == 0);
+  CHECK(intcode_write(copy, 1000, 7));
+  CHECK(intcode_memory_size(copy) == 1001);
+
+  /* Memory can't grow past the limit. */
+  CHECK(!intcode_write(copy, INTCODE_MAX_MEMORY, 7));
+  CHECK(!intcode_write(copy, SIZE_MAX, 7));
+  CHECK(intcode_memory_size(copy) == 1001);
+
+  intcode_free(pc);
+  intcode_free(copy);
+  intcode_free(NULL);
+
+  /* An unknown opcode stops the computer. */
+  const int64_t invalid[] = {42};
+  pc = intcode_new(invalid, 1);
+  CHECK(intcode_run(pc) == INTCODE_STATUS_INVALID);
+  CHECK(intcode_run(pc) == INTCODE_STATUS_INVALID);
+  intcode_free(pc);
+
+  /* So can the program's. Store 1 at address 2^40. */
+  const int64_t far[] = {1101, 1, 0, 1099511627776, 99};
+  pc = intcode_new(far, sizeof far / sizeof far[0]);
+  CHECK(intcode_run(pc) == INTCODE_STATUS_FAULTED);
+  CHECK(intcode_memory_size(pc) == 5);
+  intcode_free(pc);
+
+  printf("ok\n");
+  return 0;
+}
@@ -0,0 +1,137 @@
+#ifndef INTCODE_H
+#define INTCODE_H
+
+/* Generated from src/lib.rs by cbindgen. Don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Maximum number of words of memory a computer may use. Programs accessing
+ * memory past it fault.
+ */
+#define INTCODE_MAX_MEMORY (1 << 28)
+
+/**
+ * Why `intcode_run` returned.
+ */
+typedef enum IntcodeStatus {
+  INTCODE_STATUS_HALTED,
+  /**
+   * Waiting on input.
+   */
+  INTCODE_STATUS_WAITING,
+  /**
+   * An instruction failed; see `IntcodeComputer::fault`.
+   */
+  INTCODE_STATUS_FAULTED,
+  /**
+   * The program reached an invalid instruction. The computer can't run
+   * any further.
+   */
+  INTCODE_STATUS_INVALID,
+} IntcodeStatus;
+
+/**
+ * An Intcode computer.
+ */
+typedef struct Intcode Intcode;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create a computer with a copy of the `len` words at `memory`. Returns
+ * null if `len` is more than `INTCODE_MAX_MEMORY`.
+ *
+ * # Safety
+ *
+ * `memory` must point to `len` words, or `len` must be zero.
+ */
+struct Intcode *intcode_new(const int64_t *memory, size_t len);
+
+/**
+ * Create a copy of `pc`, which runs independently of it. Returns null if
+ * the copy can't be made.
+ *
+ * # Safety
+ *
+ * `pc` must be a valid computer.
+ */
+struct Intcode *intcode_snapshot(const struct Intcode *pc);
+
+/**
+ * Release a computer. Does nothing if `pc` is null.
+ *
+ * # Safety
+ *
+ * `pc` must be null or a valid computer, which can't be used afterwards.
+ */
+void intcode_free(struct Intcode *pc);
+
+/**
+ * Queue an input value.
+ *
+ * # Safety
+ *
+ * `pc` must be a valid computer.
+ */
+void intcode_push_input(struct Intcode *pc, int64_t value);
+
+/**
+ * Run until the computer halts, faults or waits on input.
+ *
+ * # Safety
+ *
+ * `pc` must be a valid computer.
+ */
+enum IntcodeStatus intcode_run(struct Intcode *pc);
+
+/**
+ * Take the oldest output value and store it in `value`. Returns false if
+ * there is no output.
+ *
+ * # Safety
+ *
+ * `pc` must be a valid computer and `value` must be valid for writes.
+ */
+bool intcode_pop_output(struct Intcode *pc, int64_t *value);
+
+/**
+ * Number of words of memory in use. Words past the end read as zero.
+ *
+ * # Safety
+ *
+ * `pc` must be a valid computer.
+ */
+size_t intcode_memory_size(const struct Intcode *pc);
+
+/**
+ * The word at `addr`.
+ *
+ * # Safety
+ *
+ * `pc` must be a valid computer.
+ */
+int64_t intcode_read(const struct Intcode *pc, size_t addr);
+
+/**
+ * Set the word at `addr`, growing memory if needed. Returns false, leaving
+ * memory unchanged, if `addr` isn't below `INTCODE_MAX_MEMORY` or memory
+ * can't grow.
+ *
+ * # Safety
+ *
+ * `pc` must be a valid computer.
+ */
+bool intcode_write(struct Intcode *pc, size_t addr, int64_t value);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* INTCODE_H */
@@ -0,0 +1,261 @@
+//! A C API for embedding an IntcodeComputer.
+//!
+//! The declarations are in `include/intcode.h`, generated from this crate
+//! with cbindgen. A computer is created with `intcode_new` or
+//! `intcode_snapshot` and must be released with `intcode_free`. Pointers to
+//! computers must be valid and not used from two threads at once. Panics
+//! are caught at every entry point rather than unwinding into C.
+
+use aoc2019::{Fault, IntcodeComputer};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+/// Maximum number of words of memory a computer may use. Programs accessing
+/// memory past it fault.
+pub const INTCODE_MAX_MEMORY: usize = 1 << 28;
+
+/// An Intcode computer.
+pub struct Intcode {
+    pc: IntcodeComputer,
+    /// Whether the program reached an invalid instruction.
+    invalid: bool,
+}
+
+/// Why `intcode_run` returned.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntcodeStatus {
+    Halted,
+    /// Waiting on input.
+    Waiting,
+    /// An instruction failed; see `IntcodeComputer::fault`.
+    Faulted,
+    /// The program reached an invalid instruction. The computer can't run
+    /// any further.
+    Invalid,
+}
+
+/// Call `f`, returning `on_panic` if it panics, so no panic unwinds into C.
+fn guard<T>(on_panic: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(on_panic)
+}
+
+/// Create a computer with a copy of the `len` words at `memory`. Returns
+/// null if `len` is more than `INTCODE_MAX_MEMORY`.
+///
+/// # Safety
+///
+/// `memory` must point to `len` words, or `len` must be zero.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_new(memory: *const i64, len: usize) -> *mut Intcode {
+    if len > INTCODE_MAX_MEMORY {
+        return ptr::null_mut();
+    }
+    guard(ptr::null_mut(), || {
+        let memory = if len == 0 {
+            vec![]
+        } else {
+            slice::from_raw_parts(memory, len).to_vec()
+        };
+        let mut pc = IntcodeComputer::new(memory);
+        pc.limits.max_addr = Some(INTCODE_MAX_MEMORY - 1);
+        Box::into_raw(Box::new(Intcode { pc, invalid: false }))
+    })
+}
+
+/// Create a copy of `pc`, which runs independently of it. Returns null if
+/// the copy can't be made.
+///
+/// # Safety
+///
+/// `pc` must be a valid computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_snapshot(pc: *const Intcode) -> *mut Intcode {
+    let intcode = &*pc;
+    guard(ptr::null_mut(), || {
+        Box::into_raw(Box::new(Intcode {
+            pc: intcode.pc.clone(),
+            invalid: intcode.invalid,
+        }))
+    })
+}
+
+/// Release a computer. Does nothing if `pc` is null.
+///
+/// # Safety
+///
+/// `pc` must be null or a valid computer, which can't be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_free(pc: *mut Intcode) {
+    if !pc.is_null() {
+        guard((), || drop(Box::from_raw(pc)));
+    }
+}
+
+/// Queue an input value.
+///
+/// # Safety
+///
+/// `pc` must be a valid computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_push_input(pc: *mut Intcode, value: i64) {
+    let pc = &mut (*pc).pc;
+    guard((), || pc.input.push_back(value));
+}
+
+/// Run until the computer halts, faults or waits on input.
+///
+/// # Safety
+///
+/// `pc` must be a valid computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_run(pc: *mut Intcode) -> IntcodeStatus {
+    let intcode = &mut *pc;
+    let pc = &mut intcode.pc;
+    if intcode.invalid || panic::catch_unwind(AssertUnwindSafe(|| pc.run())).is_err() {
+        intcode.invalid = true;
+        return IntcodeStatus::Invalid;
+    }
+    match pc.fault {
+        _ if pc.halted => IntcodeStatus::Halted,
+        Some(
+            Fault::InvalidOpcode { .. } | Fault::InvalidMode { .. } | Fault::ImmediateWrite { .. },
+        ) => IntcodeStatus::Invalid,
+        Some(_) => IntcodeStatus::Faulted,
+        None => IntcodeStatus::Waiting,
+    }
+}
+
+/// Take the oldest output value and store it in `value`. Returns false if
+/// there is no output.
+///
+/// # Safety
+///
+/// `pc` must be a valid computer and `value` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_pop_output(pc: *mut Intcode, value: *mut i64) -> bool {
+    let pc = &mut (*pc).pc;
+    match guard(None, || pc.output.pop_front()) {
+        Some(output) => {
+            *value = output;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Number of words of memory in use. Words past the end read as zero.
+///
+/// # Safety
+///
+/// `pc` must be a valid computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_memory_size(pc: *const Intcode) -> usize {
+    let pc = &(*pc).pc;
+    guard(0, || pc.mem.len())
+}
+
+/// The word at `addr`.
+///
+/// # Safety
+///
+/// `pc` must be a valid computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_read(pc: *const Intcode, addr: usize) -> i64 {
+    let pc = &(*pc).pc;
+    guard(0, || pc.mem.get(addr).copied().unwrap_or(0))
+}
+
+/// Set the word at `addr`, growing memory if needed. Returns false, leaving
+/// memory unchanged, if `addr` isn't below `INTCODE_MAX_MEMORY` or memory
+/// can't grow.
+///
+/// # Safety
+///
+/// `pc` must be a valid computer.
+#[no_mangle]
+pub unsafe extern "C" fn intcode_write(pc: *mut Intcode, addr: usize, value: i64) -> bool {
+    let mem = &mut (*pc).pc.mem;
+    if addr >= INTCODE_MAX_MEMORY {
+        return false;
+    }
+    guard(false, || {
+        if mem.len() <= addr {
+            if mem.try_reserve(addr + 1 - mem.len()).is_err() {
+                return false;
+            }
+            mem.resize(addr + 1, 0);
+        }
+        mem[addr] = value;
+        true
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, path::Path, process::Command};
+
+    #[test]
+    fn header() {
+        let crate_dir = env!("CARGO_MANIFEST_DIR");
+        let config = cbindgen::Config::from_file(Path::new(crate_dir).join("cbindgen.toml"));
+        let mut header = vec![];
+        cbindgen::Builder::new()
+            .with_src(Path::new(crate_dir).join("src/lib.rs"))
+            .with_config(config.unwrap())
+            .generate()
+            .unwrap()
+            .write(&mut header);
+        let path = Path::new(crate_dir).join("include/intcode.h");
+        if env::var_os("INTCODE_WRITE_HEADER").is_some() {
+            std::fs::write(&path, &header).unwrap();
+        }
+        let current = std::fs::read(&path).unwrap_or_default();
+        assert!(
+            current == header,
+            "include/intcode.h is out of date; regenerate it with INTCODE_WRITE_HEADER=1"
+        );
+    }
+
+    #[test]
+    fn c_harness() {
+        let crate_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        // `cargo test` doesn't build the cdylib, so build it separately.
+        let target = crate_dir.join("../target/ffi");
+        let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let status = Command::new(cargo)
+            .args(["build", "--lib", "--target-dir"])
+            .arg(&target)
+            .current_dir(crate_dir)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let lib_dir = target.join("debug");
+        let harness = target.join("harness");
+        let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+            .arg("-Wall")
+            .arg("-Werror")
+            .arg("-I")
+            .arg(crate_dir.join("include"))
+            .arg(crate_dir.join("c/harness.c"))
+            .arg("-L")
+            .arg(&lib_dir)
+            .args(["-lintcode", "-o"])
+            .arg(&harness)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let output = Command::new(&harness)
+            .env("LD_LIBRARY_PATH", &lib_dir)
+            .env("DYLD_LIBRARY_PATH", &lib_dir)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{stdout}");
+        assert_eq!(stdout, "ok\n");
+    }
+}
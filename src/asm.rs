@@ -0,0 +1,485 @@
+//! Decoding, disassembling and assembling Intcode instructions.
+
+use crate::ParameterMode;
+use std::{collections::BTreeMap, error, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+    Add,
+    Mul,
+    Input,
+    Output,
+    Jnz,
+    Jz,
+    Lt,
+    Eq,
+    Rb,
+    Halt,
+}
+
+impl Opcode {
+    pub const ALL: [Opcode; 10] = [
+        Self::Add,
+        Self::Mul,
+        Self::Input,
+        Self::Output,
+        Self::Jnz,
+        Self::Jz,
+        Self::Lt,
+        Self::Eq,
+        Self::Rb,
+        Self::Halt,
+    ];
+
+    pub fn from_code(code: i64) -> Option<Self> {
+        Self::ALL.into_iter().find(|opcode| opcode.code() == code)
+    }
+
+    pub fn code(self) -> i64 {
+        match self {
+            Self::Add => 1,
+            Self::Mul => 2,
+            Self::Input => 3,
+            Self::Output => 4,
+            Self::Jnz => 5,
+            Self::Jz => 6,
+            Self::Lt => 7,
+            Self::Eq => 8,
+            Self::Rb => 9,
+            Self::Halt => 99,
+        }
+    }
+
+    /// Number of parameters.
+    pub fn arity(self) -> usize {
+        match self {
+            Self::Add | Self::Mul | Self::Lt | Self::Eq => 3,
+            Self::Jnz | Self::Jz => 2,
+            Self::Input | Self::Output | Self::Rb => 1,
+            Self::Halt => 0,
+        }
+    }
+
+    /// Index of the parameter that is a write address, if any.
+    pub fn write_param(self) -> Option<usize> {
+        match self {
+            Self::Add | Self::Mul | Self::Lt | Self::Eq => Some(2),
+            Self::Input => Some(0),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Self::Add => "ADD",
+            Self::Mul => "MUL",
+            Self::Input => "IN",
+            Self::Output => "OUT",
+            Self::Jnz => "JNZ",
+            Self::Jz => "JZ",
+            Self::Lt => "LT",
+            Self::Eq => "EQ",
+            Self::Rb => "RB",
+            Self::Halt => "HALT",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Param {
+    pub mode: ParameterMode,
+    pub value: i64,
+}
+
+impl Param {
+    pub fn position(addr: i64) -> Self {
+        Self {
+            mode: ParameterMode::Position,
+            value: addr,
+        }
+    }
+
+    pub fn immediate(value: i64) -> Self {
+        Self {
+            mode: ParameterMode::Immediate,
+            value,
+        }
+    }
+
+    pub fn relative(offset: i64) -> Self {
+        Self {
+            mode: ParameterMode::Relative,
+            value: offset,
+        }
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            ParameterMode::Position => write!(f, "[{}]", self.value),
+            ParameterMode::Immediate => write!(f, "{}", self.value),
+            ParameterMode::Relative if self.value < 0 => write!(f, "[rb-{}]", -self.value),
+            ParameterMode::Relative => write!(f, "[rb+{}]", self.value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub params: Vec<Param>,
+}
+
+impl Instruction {
+    pub fn new(opcode: Opcode, params: Vec<Param>) -> Self {
+        assert_eq!(
+            params.len(),
+            opcode.arity(),
+            "Wrong number of parameters for {opcode:?}"
+        );
+        Self { opcode, params }
+    }
+
+    /// Decode the instruction at `addr`. Words past the end of `mem` read as
+    /// zero. Returns None for an unknown opcode or parameter mode, or a write
+    /// address in immediate mode.
+    pub fn decode(mem: &[i64], addr: usize) -> Option<Self> {
+        let word = |addr: usize| mem.get(addr).copied().unwrap_or(0);
+        let instruction = word(addr);
+        if instruction < 0 {
+            return None;
+        }
+        let opcode = Opcode::from_code(instruction % 100)?;
+        let modes = instruction / 100;
+
+        let mut params = vec![];
+        for n in 1..=opcode.arity() {
+            let mode = ParameterMode::try_new(modes, n)?;
+            if mode == ParameterMode::Immediate && opcode.write_param() == Some(n - 1) {
+                return None;
+            }
+            params.push(Param {
+                mode,
+                value: word(addr + n),
+            });
+        }
+        Some(Self { opcode, params })
+    }
+
+    /// Number of words the instruction occupies.
+    pub fn size(&self) -> usize {
+        1 + self.params.len()
+    }
+
+    pub fn encode(&self) -> Vec<i64> {
+        let mut instruction = self.opcode.code();
+        let mut scale = 100;
+        for param in &self.params {
+            instruction += param.mode.digit() * scale;
+            scale *= 10;
+        }
+
+        let mut result = vec![instruction];
+        result.extend(self.params.iter().map(|param| param.value));
+        result
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.opcode.mnemonic())?;
+        for (i, param) in self.params.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(f, "{separator}{param}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Disassemble `program` by a linear sweep from address 0. Words that don't
+/// decode are listed as data.
+pub fn disassemble(program: &[i64]) -> String {
+    let mut result = String::new();
+    let mut addr = 0;
+    while addr < program.len() {
+        match Instruction::decode(program, addr) {
+            Some(instruction) if addr + instruction.size() <= program.len() => {
+                result += &format!("{addr:>5}: {instruction}\n");
+                addr += instruction.size();
+            }
+            _ => {
+                result += &format!("{addr:>5}: DATA {}\n", program[addr]);
+                addr += 1;
+            }
+        }
+    }
+    result
+}
+
+/// An error in assembly source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for AsmError {}
+
+/// An assembled program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Assembly {
+    pub program: Vec<i64>,
+    /// Address of each label.
+    pub labels: BTreeMap<String, usize>,
+    /// Address of the first word emitted by each source line, by line number.
+    pub lines: BTreeMap<usize, usize>,
+}
+
+impl Assembly {
+    /// A label at `addr`, if there is one.
+    pub fn label_at(&self, addr: usize) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|&(_, &a)| a == addr)
+            .map(|(label, _)| label.as_str())
+    }
+}
+
+/// A word that may refer to a label defined later, plus an offset.
+enum Operand {
+    Number(i64),
+    Label(String, i64),
+}
+
+fn is_label(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_value(text: &str) -> Option<Operand> {
+    if let Ok(value) = text.parse() {
+        return Some(Operand::Number(value));
+    }
+    let (label, offset) = match text.find(['+', '-']) {
+        Some(i) => {
+            let (label, offset) = text.split_at(i);
+            let offset = offset.trim_start_matches('+').replace(' ', "");
+            (label.trim(), offset.parse().ok()?)
+        }
+        None => (text, 0),
+    };
+    is_label(label).then(|| Operand::Label(label.to_string(), offset))
+}
+
+/// Parse a parameter in the syntax of `Param`'s Display.
+fn parse_param(text: &str) -> Option<(ParameterMode, Operand)> {
+    let Some(inner) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) else {
+        return Some((ParameterMode::Immediate, parse_value(text)?));
+    };
+    let inner = inner.trim();
+    let Some(offset) = inner
+        .strip_prefix("rb")
+        .map(str::trim_start)
+        .filter(|offset| offset.is_empty() || offset.starts_with(['+', '-']))
+    else {
+        return Some((ParameterMode::Position, parse_value(inner)?));
+    };
+    let offset = match offset.split_at(offset.len().min(1)) {
+        ("", _) => 0,
+        ("+", value) => value.trim().parse().ok()?,
+        (_, value) => -value.trim().parse::<i64>().ok()?,
+    };
+    Some((ParameterMode::Relative, Operand::Number(offset)))
+}
+
+/// Assemble `source`, which has one instruction per line in the syntax of
+/// `disassemble`. An instruction may be preceded by labels (`loop:`) and by
+/// its address (`12:`), which is checked. Parameters may refer to labels,
+/// optionally with an offset (`[patch+1]`), `DATA` takes a list of words, and
+/// `#` starts a comment.
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    let mut assembly = Assembly::default();
+    let mut words = vec![];
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: String| AsmError {
+            line: line_number,
+            message,
+        };
+        let mut rest = line.split('#').next().unwrap().trim();
+        while let Some((head, tail)) = rest.split_once(':') {
+            let head = head.trim();
+            if let Ok(addr) = head.parse::<usize>() {
+                if addr != words.len() {
+                    return Err(error(format!("address {addr} should be {}", words.len())));
+                }
+            } else if !is_label(head) {
+                return Err(error(format!("invalid label {head:?}")));
+            } else if assembly
+                .labels
+                .insert(head.to_string(), words.len())
+                .is_some()
+            {
+                return Err(error(format!("duplicate label {head}")));
+            }
+            rest = tail.trim();
+        }
+        if rest.is_empty() {
+            continue;
+        }
+
+        assembly.lines.insert(line_number, words.len());
+        let (mnemonic, params) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let params: Vec<&str> = params
+            .split(',')
+            .map(str::trim)
+            .filter(|param| !param.is_empty())
+            .collect();
+        if mnemonic == "DATA" {
+            for param in params {
+                let value =
+                    parse_value(param).ok_or_else(|| error(format!("invalid word {param:?}")))?;
+                words.push((line_number, value));
+            }
+            continue;
+        }
+
+        let opcode = Opcode::ALL
+            .into_iter()
+            .find(|opcode| opcode.mnemonic() == mnemonic)
+            .ok_or_else(|| error(format!("unknown instruction {mnemonic}")))?;
+        if params.len() != opcode.arity() {
+            return Err(error(format!(
+                "{mnemonic} takes {} parameters",
+                opcode.arity()
+            )));
+        }
+        let mut instruction = opcode.code();
+        let mut operands = vec![];
+        let mut scale = 100;
+        for (n, param) in params.into_iter().enumerate() {
+            let (mode, operand) =
+                parse_param(param).ok_or_else(|| error(format!("invalid parameter {param:?}")))?;
+            if mode == ParameterMode::Immediate && opcode.write_param() == Some(n) {
+                return Err(error(format!("{param} can't be written")));
+            }
+            instruction += mode.digit() * scale;
+            scale *= 10;
+            operands.push((line_number, operand));
+        }
+        words.push((line_number, Operand::Number(instruction)));
+        words.extend(operands);
+    }
+
+    for (line, word) in words {
+        let value = match word {
+            Operand::Number(value) => value,
+            Operand::Label(label, offset) => match assembly.labels.get(&label) {
+                Some(&addr) => addr as i64 + offset,
+                None => {
+                    return Err(AsmError {
+                        line,
+                        message: format!("unknown label {label}"),
+                    })
+                }
+            },
+        };
+        assembly.program.push(value);
+    }
+    Ok(assembly)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{assemble, disassemble, AsmError, Instruction, Opcode, Param};
+
+    #[test]
+    fn decode_modes() {
+        let instruction = Instruction::decode(&[21101, 4, -5, 6], 0).unwrap();
+        assert_eq!(instruction.opcode, Opcode::Add);
+        assert_eq!(
+            instruction.params,
+            [
+                Param::immediate(4),
+                Param::immediate(-5),
+                Param::relative(6)
+            ]
+        );
+        assert_eq!(instruction.to_string(), "ADD 4, -5, [rb+6]");
+        assert_eq!(instruction.encode(), [21101, 4, -5, 6]);
+    }
+
+    #[test]
+    fn decode_invalid() {
+        assert_eq!(Instruction::decode(&[0], 0), None);
+        assert_eq!(Instruction::decode(&[305], 0), None);
+        assert_eq!(Instruction::decode(&[10001, 0, 0, 0], 0), None);
+        assert_eq!(Instruction::decode(&[103, 0], 0), None);
+    }
+
+    #[test]
+    fn disassemble_program() {
+        let text = disassemble(&[1002, 4, 3, 4, 33, 99, 7]);
+        assert_eq!(
+            text,
+            "    0: MUL [4], 3, [4]\n    4: DATA 33\n    5: HALT\n    6: DATA 7\n"
+        );
+    }
+
+    #[test]
+    fn assemble_program() {
+        let source = "\
+# Count down from 3.
+start: ADD 3, 0, [counter]
+loop:  OUT [counter]
+       ADD [counter], -1, [counter]
+       JNZ [counter], loop
+       RB 5
+       IN [rb-2]
+       HALT
+counter: DATA 0, loop+2, counter - 1
+";
+        let assembly = assemble(source).unwrap();
+        assert_eq!(
+            assembly.program,
+            [1101, 3, 0, 18, 4, 18, 1001, 18, -1, 18, 1005, 18, 4, 109, 5, 203, -2, 99, 0, 6, 17]
+        );
+        assert_eq!(assembly.labels["loop"], 4);
+        assert_eq!(assembly.label_at(18), Some("counter"));
+        assert_eq!(assembly.lines[&3], 4);
+
+        let program = [1002, 4, 3, 4, 33, 99, 7, 21101, 1, 2, -3];
+        let program = [&program[..], &[204, 0]].concat();
+        assert_eq!(assemble(&disassemble(&program)).unwrap().program, program);
+    }
+
+    #[test]
+    fn assemble_errors() {
+        let error = |line, message: &str| {
+            Err(AsmError {
+                line,
+                message: message.to_string(),
+            })
+        };
+        assert_eq!(assemble("HALT\nJZ 0"), error(2, "JZ takes 2 parameters"));
+        assert_eq!(assemble("ADD 1, 2, 3"), error(1, "3 can't be written"));
+        assert_eq!(
+            assemble("\nOUT [nowhere]"),
+            error(2, "unknown label nowhere")
+        );
+        assert_eq!(assemble("HALT\n0: HALT"), error(2, "address 0 should be 1"));
+        assert_eq!(assemble("NOP"), error(1, "unknown instruction NOP"));
+        assert_eq!(
+            assemble("OUT [x+y]"),
+            error(1, "invalid parameter \"[x+y]\"")
+        );
+    }
+}
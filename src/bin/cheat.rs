@@ -0,0 +1,86 @@
+use aoc2019::{
+    cheat::{Cheats, Filter, Scanner},
+    IntcodeComputer,
+};
+use std::io::{BufRead, Write};
+
+const HELP: &str = "\
+run [input...]      run with the given inputs and print the outputs
+changed, unchanged, increased, decreased
+eq <value>, inc <n>, dec <n>
+                    keep the candidates matching the filter
+list                print the candidates and their values
+reset               start a new scan
+patch <addr> <value>
+freeze <addr> <value>
+unfreeze <addr>";
+
+/// Usage: cheat <program>
+///
+/// Reads commands from stdin; see `HELP`.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert!(args.len() == 2, "Usage: cheat <program>");
+
+    let mut pc = IntcodeComputer::from_file(&args[1]);
+    let mut scanner = Scanner::new(&pc);
+    let mut cheats = Cheats::new();
+    let stdin = std::io::stdin();
+    print!("> ");
+    std::io::stdout().flush().unwrap();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let numbers: Vec<i64> = words
+            .iter()
+            .skip(1)
+            .filter_map(|w| w.parse().ok())
+            .collect();
+        // An address within memory, for the commands taking one.
+        let addr = words
+            .get(1)
+            .and_then(|word| word.parse::<usize>().ok())
+            .filter(|&addr| addr < pc.mem.len());
+        let filter = match (words.first().copied(), numbers.as_slice()) {
+            (Some("changed"), []) => Some(Filter::Changed),
+            (Some("unchanged"), []) => Some(Filter::Unchanged),
+            (Some("increased"), []) => Some(Filter::Increased),
+            (Some("decreased"), []) => Some(Filter::Decreased),
+            (Some("eq"), &[value]) => Some(Filter::Equal(value)),
+            (Some("inc"), &[n]) => Some(Filter::IncreasedBy(n)),
+            (Some("dec"), &[n]) => Some(Filter::DecreasedBy(n)),
+            _ => None,
+        };
+
+        if let Some(filter) = filter {
+            scanner.filter(&pc, filter);
+            println!("{} candidates", scanner.candidates().len());
+        } else {
+            match (words.first().copied(), numbers.as_slice()) {
+                (Some("run"), inputs) => {
+                    pc.input.extend(inputs);
+                    cheats.run(&mut pc);
+                    println!("{:?}", pc.output.drain(..).collect::<Vec<_>>());
+                    if pc.halted {
+                        println!("halted");
+                    }
+                }
+                (Some("list"), []) => {
+                    for &addr in scanner.candidates().iter().take(50) {
+                        println!("{addr}: {}", pc.mem.get(addr).copied().unwrap_or(0));
+                    }
+                }
+                (Some("reset"), []) => scanner = Scanner::new(&pc),
+                (Some("patch" | "freeze" | "unfreeze"), _) if words.len() > 1 && addr.is_none() => {
+                    println!("{:?} isn't an address below {}", words[1], pc.mem.len())
+                }
+                (Some("patch"), &[_, value]) => Cheats::patch(&mut pc, addr.unwrap(), value),
+                (Some("freeze"), &[_, value]) => cheats.freeze(addr.unwrap(), value),
+                (Some("unfreeze"), &[_]) => cheats.unfreeze(addr.unwrap()),
+                _ => println!("{HELP}"),
+            }
+        }
+        print!("> ");
+        std::io::stdout().flush().unwrap();
+    }
+}
@@ -0,0 +1,18 @@
+use aoc2019::{compiler::compile, IntcodeComputer};
+
+/// Usage: compile <source> <output>
+///
+/// Compiles a program in the language described in `aoc2019::compiler`.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert!(args.len() == 3, "Usage: compile <source> <output>");
+
+    let source = std::fs::read_to_string(&args[1]).unwrap();
+    match compile(&source) {
+        Ok(program) => IntcodeComputer::new(program).save(&args[2]),
+        Err(error) => {
+            eprintln!("{}: {error}", args[1]);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use aoc2019::{console::Server, IntcodeComputer};
+use std::{net::TcpListener, sync::Arc};
+
+/// Usage: console <program> [port [log dir]]
+///
+/// Hosts the program on localhost, by default on port 2525. Connect with
+/// e.g. `nc localhost 2525`; every connection gets its own copy.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert!(
+        (2..=4).contains(&args.len()),
+        "Usage: console <program> [port [log dir]]"
+    );
+    let port: u16 = args.get(2).map_or(2525, |port| port.parse().unwrap());
+
+    let mut server = Server::new(IntcodeComputer::from_file(&args[1]));
+    server.log_dir = args.get(3).map(Into::into);
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+    println!("Listening on {}", listener.local_addr().unwrap());
+    Arc::new(server).serve(listener).unwrap();
+}
@@ -0,0 +1,11 @@
+use aoc2019::dap::Session;
+
+/// Usage: dap
+///
+/// Serves the Debug Adapter Protocol on stdin and stdout.
+fn main() {
+    let stdin = std::io::stdin();
+    Session::new()
+        .serve(stdin.lock(), std::io::stdout())
+        .unwrap();
+}
@@ -1,60 +1,57 @@
-use aoc2019::IntcodeComputer;
+use aoc2019::{device::Device, IntcodeComputer};
 use std::collections::HashMap;
 
-fn main() {
-    let mut pc = IntcodeComputer::from_file("data/day11");
-    let mut paint = HashMap::new();
-    let mut pos = (0, 0);
-    let mut dir = (-1, 0);
-    while !pc.halted {
-        pc.run();
+/// The hull painting robot. Its camera reports the color of the panel below
+/// it, and it receives pairs of the color to paint and the direction to turn.
+struct Robot {
+    paint: HashMap<(i64, i64), i64>,
+    pos: (i64, i64),
+    dir: (i64, i64),
+    color: Option<i64>,
+}
 
-        if let Some(color) = pc.output.pop_front() {
-            paint.insert(pos, color);
+impl Device for Robot {
+    fn input(&mut self) -> Option<i64> {
+        Some(self.paint.get(&self.pos).copied().unwrap_or_default())
+    }
 
-            let turn = pc.output.pop_front().unwrap();
-            if turn == 1 {
-                dir = (dir.1, -dir.0);
-            } else {
-                dir = (-dir.1, dir.0);
-            }
-            pos = (pos.0 + dir.0, pos.1 + dir.1);
-        }
+    fn output(&mut self, value: i64) {
+        let Some(color) = self.color.take() else {
+            self.color = Some(value);
+            return;
+        };
+        self.paint.insert(self.pos, color);
 
-        if !pc.halted {
-            let value = paint.get(&pos).copied().unwrap_or_default();
-            pc.input.push_back(value);
+        let turn = value;
+        if turn == 1 {
+            self.dir = (self.dir.1, -self.dir.0);
+        } else {
+            self.dir = (-self.dir.1, self.dir.0);
         }
+        self.pos = (self.pos.0 + self.dir.0, self.pos.1 + self.dir.1);
     }
-    dbg!(paint.len());
+}
 
+/// Run the robot on a hull whose panels are given by `paint`.
+fn run(paint: HashMap<(i64, i64), i64>) -> HashMap<(i64, i64), i64> {
     let mut pc = IntcodeComputer::from_file("data/day11");
-    let mut paint = HashMap::new();
-    // start the paint on a white panel
-    paint.insert((0, 0), 1);
-    let mut pos = (0, 0);
-    let mut dir = (-1, 0);
-    while !pc.halted {
-        pc.run();
+    let mut robot = Robot {
+        paint,
+        pos: (0, 0),
+        dir: (-1, 0),
+        color: None,
+    };
+    pc.run_with(&mut [&mut robot]);
+    robot.paint
+}
 
-        if let Some(color) = pc.output.pop_front() {
-            paint.insert(pos, color);
+fn main() {
+    let paint = run(HashMap::new());
+    dbg!(paint.len());
 
-            let turn = pc.output.pop_front().unwrap();
-            if turn == 1 {
-                dir = (dir.1, -dir.0);
-            } else {
-                dir = (-dir.1, dir.0);
-            }
-            pos = (pos.0 + dir.0, pos.1 + dir.1);
-        }
+    // start the paint on a white panel
+    let paint = run(HashMap::from([((0, 0), 1)]));
 
-        if !pc.halted {
-            let value = paint.get(&pos).copied().unwrap_or_default();
-            pc.input.push_back(value);
-        }
-    }
-    
     // render the paint
     let x_min = paint.keys().map(|&(x, _)| x).min().unwrap();
     let x_max = paint.keys().map(|&(x, _)| x).max().unwrap();
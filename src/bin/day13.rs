@@ -1,77 +1,58 @@
-use aoc2019::IntcodeComputer;
-use std::{collections::HashMap, time::Duration};
-
-fn print_screen(screen: &HashMap<(i64, i64), i64>) {
-    let tiles = [' ', 'H', 'X', '=', 'o'];
-
-    let x_min = screen.keys().map(|k| k.0).min().unwrap();
-    let x_max = screen.keys().map(|k| k.0).max().unwrap();
-    let y_min = screen.keys().map(|k| k.1).min().unwrap();
-    let y_max = screen.keys().map(|k| k.1).max().unwrap();
-    for y in y_min..=y_max {
-        for x in x_min..=x_max {
-            let id = screen[&(x, y)];
-            print!("{}", tiles[id as usize]);
-        }
-        println!();
-    }
+use aoc2019::{
+    device::{Device, Framebuffer, Tilt},
+    IntcodeComputer,
+};
+use std::time::Duration;
+
+const TILES: [char; 5] = [' ', 'H', 'X', '=', 'o'];
+
+/// Plays the game by following the ball with the paddle, printing every
+/// frame.
+struct Autopilot {
+    screen: Framebuffer,
 }
 
-fn main() {
-    let mut pc = IntcodeComputer::from_file("data/day13");
-    let mut screen = HashMap::new();
-    pc.run();
-    while let Some(x) = pc.output.pop_front() {
-        let y = pc.output.pop_front().unwrap();
-        let tile = pc.output.pop_front().unwrap();
-        screen.insert((x, y), tile);
+impl Device for Autopilot {
+    fn output(&mut self, value: i64) {
+        self.screen.output(value);
     }
-    let block_tile_count = screen.values().filter(|&&v| v == 2).count();
-    dbg!(block_tile_count);
-
-    let mut pc = IntcodeComputer::from_file("data/day13");
-    let mut screen = HashMap::new();
-    let mut score = 0;
-    let mut ball = None;
-    let mut paddle = None;
-    pc.mem[0] = 2;
-    while !pc.halted {
-        // Run the program and examine the output.
-        pc.run();
-        while let Some(x) = pc.output.pop_front() {
-            let y = pc.output.pop_front().unwrap();
-            let tile = pc.output.pop_front().unwrap();
-            if x == -1 {
-                score = tile;
-            } else {
-                screen.insert((x, y), tile);
-
-                if tile == 3 {
-                    paddle = Some((x, y));
-                }
-                if tile == 4 {
-                    ball = Some((x, y));
-                }
-            }
-        }
 
+    fn input(&mut self) -> Option<i64> {
         // Print the screen and the current score.
         println!("------------------------------------------");
-        println!("score = {score}");
+        println!("score = {}", self.screen.score.unwrap_or_default());
         println!();
-        print_screen(&screen);
+        print!("{}", self.screen.render(&TILES));
         println!();
 
         // Pause a bit.
         std::thread::sleep(Duration::from_millis(100));
 
-        // Provide input to the program.
-        if paddle.unwrap().0 > ball.unwrap().0 {
-            pc.input.push_back(-1);
-        } else if paddle.unwrap().0 < ball.unwrap().0 {
-            pc.input.push_back(1);
+        let paddle = self.screen.find(3).next().unwrap();
+        let ball = self.screen.find(4).next().unwrap();
+        let tilt = if paddle.0 > ball.0 {
+            Tilt::Left
+        } else if paddle.0 < ball.0 {
+            Tilt::Right
         } else {
-            pc.input.push_back(0);
-        }
+            Tilt::Neutral
+        };
+        Some(tilt as i64)
     }
 }
+
+fn main() {
+    let mut pc = IntcodeComputer::from_file("data/day13");
+    let mut screen = Framebuffer::new();
+    pc.run_with(&mut [&mut screen]);
+    let block_tile_count = screen.find(2).count();
+    dbg!(block_tile_count);
+
+    let mut pc = IntcodeComputer::from_file("data/day13");
+    pc.mem[0] = 2;
+    let mut autopilot = Autopilot {
+        screen: Framebuffer::new(),
+    };
+    pc.run_with(&mut [&mut autopilot]);
+    println!("score = {}", autopilot.screen.score.unwrap_or_default());
+}
@@ -1,41 +1,36 @@
-use aoc2019::IntcodeComputer;
+use aoc2019::{
+    device::{Droid, Move, Status},
+    IntcodeComputer,
+};
 use std::collections::HashSet;
 
 /// Search the area for the oxygen system, avoiding spaces that have been
 /// visited. Returns the location of the oxygen system if found.
 fn search(
     pc: &mut IntcodeComputer,
-    pos: (i64, i64),
+    droid: &mut Droid,
     vis: &mut HashSet<(i64, i64)>,
 ) -> Option<(i64, i64)> {
     let mut oxygen_system = None;
 
-    let commands = [
-        (1, (0, 1), 2),
-        (2, (0, -1), 1),
-        (3, (-1, 0), 4),
-        (4, (1, 0), 3),
-    ];
-    for (command, (dx, dy), reverse_command) in commands.into_iter() {
+    let pos = droid.pos;
+    for dir in Move::ALL {
+        let (dx, dy) = dir.delta();
         let next_pos = (pos.0 + dx, pos.1 + dy);
         if !vis.contains(&next_pos) {
-            pc.input.push_front(command);
-            pc.run();
-            let status = pc.output.pop_back().unwrap();
+            let status = droid.go(pc, dir);
 
-            if status != 0 {
+            if status != Status::Wall {
                 vis.insert(next_pos);
 
-                if let Some(result) = search(pc, next_pos, vis) {
+                if let Some(result) = search(pc, droid, vis) {
                     oxygen_system = Some(result);
                 }
 
-                pc.input.push_front(reverse_command);
-                pc.run();
-                let status = pc.output.pop_back().unwrap();
-                assert_ne!(status, 0);
+                let status = droid.go(pc, dir.reverse());
+                assert_ne!(status, Status::Wall);
             }
-            if status == 2 {
+            if status == Status::Found {
                 oxygen_system = Some(next_pos);
             }
         }
@@ -84,7 +79,7 @@ fn main() {
     let mut pc = IntcodeComputer::from_file("data/day15");
     let mut vis = HashSet::new();
     vis.insert((0, 0));
-    let oxygen_station = search(&mut pc, (0, 0), &mut vis).unwrap();
+    let oxygen_station = search(&mut pc, &mut Droid::new(), &mut vis).unwrap();
 
     print_map(&vis, oxygen_station); // minimum distance is 246 by counting :)
 
@@ -1,4 +1,4 @@
-use aoc2019::IntcodeComputer;
+use aoc2019::{script::Script, IntcodeComputer};
 use itertools::Itertools;
 use std::collections::HashSet;
 
@@ -53,11 +53,7 @@ fn main() {
     let mut computer = IntcodeComputer::from_file("data/day17");
     computer.mem[0] = 2;
 
-    let instruction = std::fs::read("data/day17_instruction").unwrap();
-    for byte in instruction {
-        computer.input.push_back(byte as i64);
-    }
-    
-    computer.run();
-    dbg!(computer.output);
+    let script = Script::load("data/day17_script").unwrap();
+    let captures = script.run(&mut computer).unwrap();
+    dbg!(captures["dust"]);
 }
@@ -0,0 +1,991 @@
+//! Generated by aoc2019::translate. Do not edit.
+
+#![allow(clippy::all, unused)]
+
+use aoc2019::{Fault, IntcodeComputer, MAX_ADDR};
+
+const PROGRAM: [i64; 424] = [
+    109, 424, 203, 1, 21102, 1, 11, 0, 1106, 0, 282, 21101, 18, 0, 0, 1105, 1, 259, 2102, 1, 1,
+    221, 203, 1, 21101, 0, 31, 0, 1105, 1, 282, 21101, 0, 38, 0, 1105, 1, 259, 20101, 0, 23, 2,
+    21202, 1, 1, 3, 21101, 0, 1, 1, 21101, 57, 0, 0, 1105, 1, 303, 1202, 1, 1, 222, 21002, 221, 1,
+    3, 21001, 221, 0, 2, 21102, 1, 259, 1, 21101, 0, 80, 0, 1105, 1, 225, 21101, 0, 175, 2, 21102,
+    1, 91, 0, 1106, 0, 303, 2101, 0, 1, 223, 21001, 222, 0, 4, 21102, 259, 1, 3, 21101, 225, 0, 2,
+    21102, 1, 225, 1, 21102, 1, 118, 0, 1105, 1, 225, 21002, 222, 1, 3, 21101, 70, 0, 2, 21101, 0,
+    133, 0, 1105, 1, 303, 21202, 1, -1, 1, 22001, 223, 1, 1, 21102, 1, 148, 0, 1105, 1, 259, 2102,
+    1, 1, 223, 21002, 221, 1, 4, 21002, 222, 1, 3, 21102, 24, 1, 2, 1001, 132, -2, 224, 1002, 224,
+    2, 224, 1001, 224, 3, 224, 1002, 132, -1, 132, 1, 224, 132, 224, 21001, 224, 1, 1, 21101, 195,
+    0, 0, 105, 1, 109, 20207, 1, 223, 2, 21002, 23, 1, 1, 21101, 0, -1, 3, 21102, 1, 214, 0, 1106,
+    0, 303, 22101, 1, 1, 1, 204, 1, 99, 0, 0, 0, 0, 109, 5, 2102, 1, -4, 249, 21202, -3, 1, 1,
+    22102, 1, -2, 2, 21201, -1, 0, 3, 21101, 0, 250, 0, 1106, 0, 225, 21201, 1, 0, -4, 109, -5,
+    2105, 1, 0, 109, 3, 22107, 0, -2, -1, 21202, -1, 2, -1, 21201, -1, -1, -1, 22202, -1, -2, -2,
+    109, -3, 2105, 1, 0, 109, 3, 21207, -2, 0, -1, 1206, -1, 294, 104, 0, 99, 21202, -2, 1, -2,
+    109, -3, 2106, 0, 0, 109, 5, 22207, -3, -4, -1, 1206, -1, 346, 22201, -4, -3, -4, 21202, -3,
+    -1, -1, 22201, -4, -1, 2, 21202, 2, -1, -1, 22201, -4, -1, 1, 22101, 0, -2, 3, 21101, 343, 0,
+    0, 1105, 1, 303, 1105, 1, 415, 22207, -2, -3, -1, 1206, -1, 387, 22201, -3, -2, -3, 21202, -2,
+    -1, -1, 22201, -3, -1, 3, 21202, 3, -1, -1, 22201, -3, -1, 2, 21201, -4, 0, 1, 21101, 0, 384,
+    0, 1105, 1, 303, 1105, 1, 415, 21202, -4, -1, -4, 22201, -4, -3, -4, 22202, -3, -2, -2, 22202,
+    -2, -4, -4, 22202, -3, -2, -3, 21202, -4, -1, -2, 22201, -3, -2, 1, 21201, 1, 0, -4, 109, -5,
+    2106, 0, 0,
+];
+
+pub fn run(input: &mut impl Iterator<Item = i64>, out: &mut Vec<i64>) -> Result<(), Fault> {
+    let mut mem = PROGRAM.to_vec();
+    let mut ip: usize = 0;
+    let mut rb: i64 = 0;
+    loop {
+        match ip {
+            0 => {
+                // RB 424
+                rb = rb.checked_add(424).ok_or(Fault::Overflow)?;
+                ip = 2;
+            }
+            2 => {
+                // IN [rb+1]
+                let Some(value) = input.next() else {
+                    return Ok(());
+                };
+                ip = 4;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            4 => {
+                // MUL 1, 11, [rb+0]
+                let value = i64::wrapping_mul(1, 11);
+                ip = 8;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            8 => {
+                // JZ 0, 282
+                ip = if 0 == 0 { 282 } else { 11 };
+            }
+            11 => {
+                // ADD 18, 0, [rb+0]
+                let value = i64::wrapping_add(18, 0);
+                ip = 15;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            15 => {
+                // JNZ 1, 259
+                ip = if 1 != 0 { 259 } else { 18 };
+            }
+            18 => {
+                // MUL 1, [rb+1], [221]
+                let value = i64::wrapping_mul(1, read(&mem, relative(rb, 1)?)?);
+                ip = 22;
+                if write(&mut mem, 221, value)? {
+                    break;
+                }
+            }
+            22 => {
+                // IN [rb+1]
+                let Some(value) = input.next() else {
+                    return Ok(());
+                };
+                ip = 24;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            24 => {
+                // ADD 0, 31, [rb+0]
+                let value = i64::wrapping_add(0, 31);
+                ip = 28;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            28 => {
+                // JNZ 1, 282
+                ip = if 1 != 0 { 282 } else { 31 };
+            }
+            31 => {
+                // ADD 0, 38, [rb+0]
+                let value = i64::wrapping_add(0, 38);
+                ip = 35;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            35 => {
+                // JNZ 1, 259
+                ip = if 1 != 0 { 259 } else { 38 };
+            }
+            38 => {
+                // ADD 0, [23], [rb+2]
+                let value = i64::wrapping_add(0, read(&mem, 23)?);
+                ip = 42;
+                if write(&mut mem, relative(rb, 2)?, value)? {
+                    break;
+                }
+            }
+            42 => {
+                // MUL [rb+1], 1, [rb+3]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, 1)?)?, 1);
+                ip = 46;
+                if write(&mut mem, relative(rb, 3)?, value)? {
+                    break;
+                }
+            }
+            46 => {
+                // ADD 0, 1, [rb+1]
+                let value = i64::wrapping_add(0, 1);
+                ip = 50;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            50 => {
+                // ADD 57, 0, [rb+0]
+                let value = i64::wrapping_add(57, 0);
+                ip = 54;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            54 => {
+                // JNZ 1, 303
+                ip = if 1 != 0 { 303 } else { 57 };
+            }
+            57 => {
+                // MUL [rb+1], 1, [222]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, 1)?)?, 1);
+                ip = 61;
+                if write(&mut mem, 222, value)? {
+                    break;
+                }
+            }
+            61 => {
+                // MUL [221], 1, [rb+3]
+                let value = i64::wrapping_mul(read(&mem, 221)?, 1);
+                ip = 65;
+                if write(&mut mem, relative(rb, 3)?, value)? {
+                    break;
+                }
+            }
+            65 => {
+                // ADD [221], 0, [rb+2]
+                let value = i64::wrapping_add(read(&mem, 221)?, 0);
+                ip = 69;
+                if write(&mut mem, relative(rb, 2)?, value)? {
+                    break;
+                }
+            }
+            69 => {
+                // MUL 1, 259, [rb+1]
+                let value = i64::wrapping_mul(1, 259);
+                ip = 73;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            73 => {
+                // ADD 0, 80, [rb+0]
+                let value = i64::wrapping_add(0, 80);
+                ip = 77;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            77 => {
+                // JNZ 1, 225
+                ip = if 1 != 0 { 225 } else { 80 };
+            }
+            80 => {
+                // ADD 0, 175, [rb+2]
+                let value = i64::wrapping_add(0, 175);
+                ip = 84;
+                if write(&mut mem, relative(rb, 2)?, value)? {
+                    break;
+                }
+            }
+            84 => {
+                // MUL 1, 91, [rb+0]
+                let value = i64::wrapping_mul(1, 91);
+                ip = 88;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            88 => {
+                // JZ 0, 303
+                ip = if 0 == 0 { 303 } else { 91 };
+            }
+            91 => {
+                // ADD 0, [rb+1], [223]
+                let value = i64::wrapping_add(0, read(&mem, relative(rb, 1)?)?);
+                ip = 95;
+                if write(&mut mem, 223, value)? {
+                    break;
+                }
+            }
+            95 => {
+                // ADD [222], 0, [rb+4]
+                let value = i64::wrapping_add(read(&mem, 222)?, 0);
+                ip = 99;
+                if write(&mut mem, relative(rb, 4)?, value)? {
+                    break;
+                }
+            }
+            99 => {
+                // MUL 259, 1, [rb+3]
+                let value = i64::wrapping_mul(259, 1);
+                ip = 103;
+                if write(&mut mem, relative(rb, 3)?, value)? {
+                    break;
+                }
+            }
+            103 => {
+                // ADD 225, 0, [rb+2]
+                let value = i64::wrapping_add(225, 0);
+                ip = 107;
+                if write(&mut mem, relative(rb, 2)?, value)? {
+                    break;
+                }
+            }
+            107 => {
+                // MUL 1, 225, [rb+1]
+                let value = i64::wrapping_mul(1, 225);
+                ip = 111;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            111 => {
+                // MUL 1, 118, [rb+0]
+                let value = i64::wrapping_mul(1, 118);
+                ip = 115;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            115 => {
+                // JNZ 1, 225
+                ip = if 1 != 0 { 225 } else { 118 };
+            }
+            118 => {
+                // MUL [222], 1, [rb+3]
+                let value = i64::wrapping_mul(read(&mem, 222)?, 1);
+                ip = 122;
+                if write(&mut mem, relative(rb, 3)?, value)? {
+                    break;
+                }
+            }
+            122 => {
+                // ADD 70, 0, [rb+2]
+                let value = i64::wrapping_add(70, 0);
+                ip = 126;
+                if write(&mut mem, relative(rb, 2)?, value)? {
+                    break;
+                }
+            }
+            126 => {
+                // ADD 0, 133, [rb+0]
+                let value = i64::wrapping_add(0, 133);
+                ip = 130;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            130 => {
+                // JNZ 1, 303
+                ip = if 1 != 0 { jump(mem[132])? } else { 133 };
+            }
+            133 => {
+                // MUL [rb+1], -1, [rb+1]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, 1)?)?, (-1));
+                ip = 137;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            137 => {
+                // ADD [223], [rb+1], [rb+1]
+                let value = i64::wrapping_add(read(&mem, 223)?, read(&mem, relative(rb, 1)?)?);
+                ip = 141;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            141 => {
+                // MUL 1, 148, [rb+0]
+                let value = i64::wrapping_mul(1, 148);
+                ip = 145;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            145 => {
+                // JNZ 1, 259
+                ip = if 1 != 0 { 259 } else { 148 };
+            }
+            148 => {
+                // MUL 1, [rb+1], [223]
+                let value = i64::wrapping_mul(1, read(&mem, relative(rb, 1)?)?);
+                ip = 152;
+                if write(&mut mem, 223, value)? {
+                    break;
+                }
+            }
+            152 => {
+                // MUL [221], 1, [rb+4]
+                let value = i64::wrapping_mul(read(&mem, 221)?, 1);
+                ip = 156;
+                if write(&mut mem, relative(rb, 4)?, value)? {
+                    break;
+                }
+            }
+            156 => {
+                // MUL [222], 1, [rb+3]
+                let value = i64::wrapping_mul(read(&mem, 222)?, 1);
+                ip = 160;
+                if write(&mut mem, relative(rb, 3)?, value)? {
+                    break;
+                }
+            }
+            160 => {
+                // MUL 24, 1, [rb+2]
+                let value = i64::wrapping_mul(24, 1);
+                ip = 164;
+                if write(&mut mem, relative(rb, 2)?, value)? {
+                    break;
+                }
+            }
+            164 => {
+                // ADD [132], -2, [224]
+                let value = i64::wrapping_add(read(&mem, 132)?, (-2));
+                ip = 168;
+                if write(&mut mem, 224, value)? {
+                    break;
+                }
+            }
+            168 => {
+                // MUL [224], 2, [224]
+                let value = i64::wrapping_mul(read(&mem, 224)?, 2);
+                ip = 172;
+                if write(&mut mem, 224, value)? {
+                    break;
+                }
+            }
+            172 => {
+                // ADD [224], 3, [224]
+                let value = i64::wrapping_add(read(&mem, 224)?, 3);
+                ip = 176;
+                if write(&mut mem, 224, value)? {
+                    break;
+                }
+            }
+            176 => {
+                // MUL [132], -1, [132]
+                let value = i64::wrapping_mul(read(&mem, 132)?, (-1));
+                ip = 180;
+                if write(&mut mem, 132, value)? {
+                    break;
+                }
+            }
+            180 => {
+                // ADD [224], [132], [224]
+                let value = i64::wrapping_add(read(&mem, 224)?, read(&mem, 132)?);
+                ip = 184;
+                if write(&mut mem, 224, value)? {
+                    break;
+                }
+            }
+            184 => {
+                // ADD [224], 1, [rb+1]
+                let value = i64::wrapping_add(read(&mem, 224)?, 1);
+                ip = 188;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            188 => {
+                // ADD 195, 0, [rb+0]
+                let value = i64::wrapping_add(195, 0);
+                ip = 192;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            192 => {
+                // JNZ 1, [109]
+                ip = if 1 != 0 { jump(read(&mem, 109)?)? } else { 195 };
+            }
+            195 => {
+                // LT [rb+1], [223], [rb+2]
+                let value = (read(&mem, relative(rb, 1)?)? < read(&mem, 223)?) as i64;
+                ip = 199;
+                if write(&mut mem, relative(rb, 2)?, value)? {
+                    break;
+                }
+            }
+            199 => {
+                // MUL [23], 1, [rb+1]
+                let value = i64::wrapping_mul(read(&mem, 23)?, 1);
+                ip = 203;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            203 => {
+                // ADD 0, -1, [rb+3]
+                let value = i64::wrapping_add(0, (-1));
+                ip = 207;
+                if write(&mut mem, relative(rb, 3)?, value)? {
+                    break;
+                }
+            }
+            207 => {
+                // MUL 1, 214, [rb+0]
+                let value = i64::wrapping_mul(1, 214);
+                ip = 211;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            211 => {
+                // JZ 0, 303
+                ip = if 0 == 0 { 303 } else { 214 };
+            }
+            214 => {
+                // ADD 1, [rb+1], [rb+1]
+                let value = i64::wrapping_add(1, read(&mem, relative(rb, 1)?)?);
+                ip = 218;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            218 => {
+                // OUT [rb+1]
+                out.push(read(&mem, relative(rb, 1)?)?);
+                ip = 220;
+            }
+            220 => {
+                // HALT
+                return Ok(());
+            }
+            225 => {
+                // RB 5
+                rb = rb.checked_add(5).ok_or(Fault::Overflow)?;
+                ip = 227;
+            }
+            227 => {
+                // MUL 1, [rb-4], [249]
+                let value = i64::wrapping_mul(1, read(&mem, relative(rb, (-4))?)?);
+                ip = 231;
+                if write(&mut mem, 249, value)? {
+                    break;
+                }
+            }
+            231 => {
+                // MUL [rb-3], 1, [rb+1]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, (-3))?)?, 1);
+                ip = 235;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            235 => {
+                // MUL 1, [rb-2], [rb+2]
+                let value = i64::wrapping_mul(1, read(&mem, relative(rb, (-2))?)?);
+                ip = 239;
+                if write(&mut mem, relative(rb, 2)?, value)? {
+                    break;
+                }
+            }
+            239 => {
+                // ADD [rb-1], 0, [rb+3]
+                let value = i64::wrapping_add(read(&mem, relative(rb, (-1))?)?, 0);
+                ip = 243;
+                if write(&mut mem, relative(rb, 3)?, value)? {
+                    break;
+                }
+            }
+            243 => {
+                // ADD 0, 250, [rb+0]
+                let value = i64::wrapping_add(0, 250);
+                ip = 247;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            247 => {
+                // JZ 0, 225
+                ip = if 0 == 0 { jump(mem[249])? } else { 250 };
+            }
+            250 => {
+                // ADD [rb+1], 0, [rb-4]
+                let value = i64::wrapping_add(read(&mem, relative(rb, 1)?)?, 0);
+                ip = 254;
+                if write(&mut mem, relative(rb, (-4))?, value)? {
+                    break;
+                }
+            }
+            254 => {
+                // RB -5
+                rb = rb.checked_add((-5)).ok_or(Fault::Overflow)?;
+                ip = 256;
+            }
+            256 => {
+                // JNZ 1, [rb+0]
+                ip = if 1 != 0 {
+                    jump(read(&mem, relative(rb, 0)?)?)?
+                } else {
+                    259
+                };
+            }
+            259 => {
+                // RB 3
+                rb = rb.checked_add(3).ok_or(Fault::Overflow)?;
+                ip = 261;
+            }
+            261 => {
+                // LT 0, [rb-2], [rb-1]
+                let value = (0 < read(&mem, relative(rb, (-2))?)?) as i64;
+                ip = 265;
+                if write(&mut mem, relative(rb, (-1))?, value)? {
+                    break;
+                }
+            }
+            265 => {
+                // MUL [rb-1], 2, [rb-1]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, (-1))?)?, 2);
+                ip = 269;
+                if write(&mut mem, relative(rb, (-1))?, value)? {
+                    break;
+                }
+            }
+            269 => {
+                // ADD [rb-1], -1, [rb-1]
+                let value = i64::wrapping_add(read(&mem, relative(rb, (-1))?)?, (-1));
+                ip = 273;
+                if write(&mut mem, relative(rb, (-1))?, value)? {
+                    break;
+                }
+            }
+            273 => {
+                // MUL [rb-1], [rb-2], [rb-2]
+                let value = i64::wrapping_mul(
+                    read(&mem, relative(rb, (-1))?)?,
+                    read(&mem, relative(rb, (-2))?)?,
+                );
+                ip = 277;
+                if write(&mut mem, relative(rb, (-2))?, value)? {
+                    break;
+                }
+            }
+            277 => {
+                // RB -3
+                rb = rb.checked_add((-3)).ok_or(Fault::Overflow)?;
+                ip = 279;
+            }
+            279 => {
+                // JNZ 1, [rb+0]
+                ip = if 1 != 0 {
+                    jump(read(&mem, relative(rb, 0)?)?)?
+                } else {
+                    282
+                };
+            }
+            282 => {
+                // RB 3
+                rb = rb.checked_add(3).ok_or(Fault::Overflow)?;
+                ip = 284;
+            }
+            284 => {
+                // LT [rb-2], 0, [rb-1]
+                let value = (read(&mem, relative(rb, (-2))?)? < 0) as i64;
+                ip = 288;
+                if write(&mut mem, relative(rb, (-1))?, value)? {
+                    break;
+                }
+            }
+            288 => {
+                // JZ [rb-1], 294
+                ip = if read(&mem, relative(rb, (-1))?)? == 0 {
+                    294
+                } else {
+                    291
+                };
+            }
+            291 => {
+                // OUT 0
+                out.push(0);
+                ip = 293;
+            }
+            293 => {
+                // HALT
+                return Ok(());
+            }
+            294 => {
+                // MUL [rb-2], 1, [rb-2]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, (-2))?)?, 1);
+                ip = 298;
+                if write(&mut mem, relative(rb, (-2))?, value)? {
+                    break;
+                }
+            }
+            298 => {
+                // RB -3
+                rb = rb.checked_add((-3)).ok_or(Fault::Overflow)?;
+                ip = 300;
+            }
+            300 => {
+                // JZ 0, [rb+0]
+                ip = if 0 == 0 {
+                    jump(read(&mem, relative(rb, 0)?)?)?
+                } else {
+                    303
+                };
+            }
+            303 => {
+                // RB 5
+                rb = rb.checked_add(5).ok_or(Fault::Overflow)?;
+                ip = 305;
+            }
+            305 => {
+                // LT [rb-3], [rb-4], [rb-1]
+                let value =
+                    (read(&mem, relative(rb, (-3))?)? < read(&mem, relative(rb, (-4))?)?) as i64;
+                ip = 309;
+                if write(&mut mem, relative(rb, (-1))?, value)? {
+                    break;
+                }
+            }
+            309 => {
+                // JZ [rb-1], 346
+                ip = if read(&mem, relative(rb, (-1))?)? == 0 {
+                    346
+                } else {
+                    312
+                };
+            }
+            312 => {
+                // ADD [rb-4], [rb-3], [rb-4]
+                let value = i64::wrapping_add(
+                    read(&mem, relative(rb, (-4))?)?,
+                    read(&mem, relative(rb, (-3))?)?,
+                );
+                ip = 316;
+                if write(&mut mem, relative(rb, (-4))?, value)? {
+                    break;
+                }
+            }
+            316 => {
+                // MUL [rb-3], -1, [rb-1]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, (-3))?)?, (-1));
+                ip = 320;
+                if write(&mut mem, relative(rb, (-1))?, value)? {
+                    break;
+                }
+            }
+            320 => {
+                // ADD [rb-4], [rb-1], [rb+2]
+                let value = i64::wrapping_add(
+                    read(&mem, relative(rb, (-4))?)?,
+                    read(&mem, relative(rb, (-1))?)?,
+                );
+                ip = 324;
+                if write(&mut mem, relative(rb, 2)?, value)? {
+                    break;
+                }
+            }
+            324 => {
+                // MUL [rb+2], -1, [rb-1]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, 2)?)?, (-1));
+                ip = 328;
+                if write(&mut mem, relative(rb, (-1))?, value)? {
+                    break;
+                }
+            }
+            328 => {
+                // ADD [rb-4], [rb-1], [rb+1]
+                let value = i64::wrapping_add(
+                    read(&mem, relative(rb, (-4))?)?,
+                    read(&mem, relative(rb, (-1))?)?,
+                );
+                ip = 332;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            332 => {
+                // ADD 0, [rb-2], [rb+3]
+                let value = i64::wrapping_add(0, read(&mem, relative(rb, (-2))?)?);
+                ip = 336;
+                if write(&mut mem, relative(rb, 3)?, value)? {
+                    break;
+                }
+            }
+            336 => {
+                // ADD 343, 0, [rb+0]
+                let value = i64::wrapping_add(343, 0);
+                ip = 340;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            340 => {
+                // JNZ 1, 303
+                ip = if 1 != 0 { 303 } else { 343 };
+            }
+            343 => {
+                // JNZ 1, 415
+                ip = if 1 != 0 { 415 } else { 346 };
+            }
+            346 => {
+                // LT [rb-2], [rb-3], [rb-1]
+                let value =
+                    (read(&mem, relative(rb, (-2))?)? < read(&mem, relative(rb, (-3))?)?) as i64;
+                ip = 350;
+                if write(&mut mem, relative(rb, (-1))?, value)? {
+                    break;
+                }
+            }
+            350 => {
+                // JZ [rb-1], 387
+                ip = if read(&mem, relative(rb, (-1))?)? == 0 {
+                    387
+                } else {
+                    353
+                };
+            }
+            353 => {
+                // ADD [rb-3], [rb-2], [rb-3]
+                let value = i64::wrapping_add(
+                    read(&mem, relative(rb, (-3))?)?,
+                    read(&mem, relative(rb, (-2))?)?,
+                );
+                ip = 357;
+                if write(&mut mem, relative(rb, (-3))?, value)? {
+                    break;
+                }
+            }
+            357 => {
+                // MUL [rb-2], -1, [rb-1]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, (-2))?)?, (-1));
+                ip = 361;
+                if write(&mut mem, relative(rb, (-1))?, value)? {
+                    break;
+                }
+            }
+            361 => {
+                // ADD [rb-3], [rb-1], [rb+3]
+                let value = i64::wrapping_add(
+                    read(&mem, relative(rb, (-3))?)?,
+                    read(&mem, relative(rb, (-1))?)?,
+                );
+                ip = 365;
+                if write(&mut mem, relative(rb, 3)?, value)? {
+                    break;
+                }
+            }
+            365 => {
+                // MUL [rb+3], -1, [rb-1]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, 3)?)?, (-1));
+                ip = 369;
+                if write(&mut mem, relative(rb, (-1))?, value)? {
+                    break;
+                }
+            }
+            369 => {
+                // ADD [rb-3], [rb-1], [rb+2]
+                let value = i64::wrapping_add(
+                    read(&mem, relative(rb, (-3))?)?,
+                    read(&mem, relative(rb, (-1))?)?,
+                );
+                ip = 373;
+                if write(&mut mem, relative(rb, 2)?, value)? {
+                    break;
+                }
+            }
+            373 => {
+                // ADD [rb-4], 0, [rb+1]
+                let value = i64::wrapping_add(read(&mem, relative(rb, (-4))?)?, 0);
+                ip = 377;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            377 => {
+                // ADD 0, 384, [rb+0]
+                let value = i64::wrapping_add(0, 384);
+                ip = 381;
+                if write(&mut mem, relative(rb, 0)?, value)? {
+                    break;
+                }
+            }
+            381 => {
+                // JNZ 1, 303
+                ip = if 1 != 0 { 303 } else { 384 };
+            }
+            384 => {
+                // JNZ 1, 415
+                ip = if 1 != 0 { 415 } else { 387 };
+            }
+            387 => {
+                // MUL [rb-4], -1, [rb-4]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, (-4))?)?, (-1));
+                ip = 391;
+                if write(&mut mem, relative(rb, (-4))?, value)? {
+                    break;
+                }
+            }
+            391 => {
+                // ADD [rb-4], [rb-3], [rb-4]
+                let value = i64::wrapping_add(
+                    read(&mem, relative(rb, (-4))?)?,
+                    read(&mem, relative(rb, (-3))?)?,
+                );
+                ip = 395;
+                if write(&mut mem, relative(rb, (-4))?, value)? {
+                    break;
+                }
+            }
+            395 => {
+                // MUL [rb-3], [rb-2], [rb-2]
+                let value = i64::wrapping_mul(
+                    read(&mem, relative(rb, (-3))?)?,
+                    read(&mem, relative(rb, (-2))?)?,
+                );
+                ip = 399;
+                if write(&mut mem, relative(rb, (-2))?, value)? {
+                    break;
+                }
+            }
+            399 => {
+                // MUL [rb-2], [rb-4], [rb-4]
+                let value = i64::wrapping_mul(
+                    read(&mem, relative(rb, (-2))?)?,
+                    read(&mem, relative(rb, (-4))?)?,
+                );
+                ip = 403;
+                if write(&mut mem, relative(rb, (-4))?, value)? {
+                    break;
+                }
+            }
+            403 => {
+                // MUL [rb-3], [rb-2], [rb-3]
+                let value = i64::wrapping_mul(
+                    read(&mem, relative(rb, (-3))?)?,
+                    read(&mem, relative(rb, (-2))?)?,
+                );
+                ip = 407;
+                if write(&mut mem, relative(rb, (-3))?, value)? {
+                    break;
+                }
+            }
+            407 => {
+                // MUL [rb-4], -1, [rb-2]
+                let value = i64::wrapping_mul(read(&mem, relative(rb, (-4))?)?, (-1));
+                ip = 411;
+                if write(&mut mem, relative(rb, (-2))?, value)? {
+                    break;
+                }
+            }
+            411 => {
+                // ADD [rb-3], [rb-2], [rb+1]
+                let value = i64::wrapping_add(
+                    read(&mem, relative(rb, (-3))?)?,
+                    read(&mem, relative(rb, (-2))?)?,
+                );
+                ip = 415;
+                if write(&mut mem, relative(rb, 1)?, value)? {
+                    break;
+                }
+            }
+            415 => {
+                // ADD [rb+1], 0, [rb-4]
+                let value = i64::wrapping_add(read(&mem, relative(rb, 1)?)?, 0);
+                ip = 419;
+                if write(&mut mem, relative(rb, (-4))?, value)? {
+                    break;
+                }
+            }
+            419 => {
+                // RB -5
+                rb = rb.checked_add((-5)).ok_or(Fault::Overflow)?;
+                ip = 421;
+            }
+            421 => {
+                // JZ 0, [rb+0]
+                ip = if 0 == 0 {
+                    jump(read(&mem, relative(rb, 0)?)?)?
+                } else {
+                    424
+                };
+            }
+            _ => break,
+        }
+    }
+    interpret(mem, ip, rb, input, out)
+}
+
+fn is_code(addr: usize) -> bool {
+    matches!(addr, 0..=131 | 133..=220 | 225..=248 | 250..=423)
+}
+
+fn read(mem: &[i64], addr: i64) -> Result<i64, Fault> {
+    if addr < 0 {
+        return Err(Fault::NegativeAddress { addr });
+    }
+    Ok(mem.get(addr as usize).copied().unwrap_or(0))
+}
+
+/// Write `value` to `addr`. Returns true if the write changed translated code.
+fn write(mem: &mut Vec<i64>, addr: i64, value: i64) -> Result<bool, Fault> {
+    if addr < 0 {
+        return Err(Fault::NegativeAddress { addr });
+    }
+    let addr = addr as usize;
+    if addr > MAX_ADDR {
+        return Err(Fault::MemoryLimit { addr });
+    }
+    if mem.len() <= addr {
+        mem.resize(addr + 1, 0);
+    }
+    mem[addr] = value;
+    Ok(is_code(addr))
+}
+
+fn relative(rb: i64, offset: i64) -> Result<i64, Fault> {
+    rb.checked_add(offset).ok_or(Fault::InvalidAddress)
+}
+
+fn jump(addr: i64) -> Result<usize, Fault> {
+    if addr < 0 {
+        return Err(Fault::NegativeAddress { addr });
+    }
+    Ok(addr as usize)
+}
+
+/// Continue running on the interpreter from the given state.
+fn interpret(
+    mem: Vec<i64>,
+    ip: usize,
+    rb: i64,
+    input: &mut impl Iterator<Item = i64>,
+    out: &mut Vec<i64>,
+) -> Result<(), Fault> {
+    let mut pc = IntcodeComputer::new(mem);
+    pc.ip = ip;
+    pc.rb = rb;
+    loop {
+        pc.run();
+        out.extend(pc.output.drain(..));
+        if let Some(fault) = pc.fault {
+            return Err(fault);
+        }
+        if pc.halted {
+            return Ok(());
+        }
+        match input.next() {
+            Some(value) => pc.input.push_back(value),
+            None => return Ok(()),
+        }
+    }
+}
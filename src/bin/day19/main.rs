@@ -1,18 +1,19 @@
-use aoc2019::IntcodeComputer;
 use itertools::Itertools;
-use std::{collections::HashSet, sync::LazyLock};
+use std::collections::HashSet;
 
-static PC: LazyLock<IntcodeComputer> = LazyLock::new(|| IntcodeComputer::from_file("data/day19"));
+/// The drone program, translated from `data/day19` with
+/// `cargo run --bin translate data/day19 src/bin/day19/beam.rs`.
+mod beam;
 
 /// Deploy a drone to target position and returns whether it's being pulled.
 fn scan(position: (usize, usize)) -> bool {
-    let mut pc = PC.clone();
-    pc.input.push_back(position.0 as i64);
-    pc.input.push_back(position.1 as i64);
-    pc.run();
-
-    let output = pc.output.pop_back().unwrap();
-    output == 1
+    let mut output = vec![];
+    beam::run(
+        &mut [position.0 as i64, position.1 as i64].into_iter(),
+        &mut output,
+    )
+    .unwrap();
+    output.pop().unwrap() == 1
 }
 
 fn main() {
@@ -51,3 +52,17 @@ fn main() {
         }
     }
 }
+
+#[test]
+fn translation_matches_interpreter() {
+    let pc = aoc2019::IntcodeComputer::from_file("data/day19");
+    for (i, j) in (0..50).cartesian_product(0..50) {
+        let mut expected = pc.clone();
+        expected.input.extend([i, j]);
+        expected.run();
+
+        let mut output = vec![];
+        beam::run(&mut [i, j].into_iter(), &mut output).unwrap();
+        assert_eq!(output, Vec::from(expected.output));
+    }
+}
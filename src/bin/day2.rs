@@ -1,74 +1,35 @@
-#[derive(Debug, Clone)]
-struct IntcodeComputer {
-    mem: Vec<usize>,
-    rip: usize,
-    halted: bool,
-}
-
-impl IntcodeComputer {
-    fn step(&mut self) {
-        let rip = self.rip;
-        let opcode = self.mem[rip];
-
-        if opcode == 99 {
-            self.halted = true;
-        } else if opcode == 1 {
-            let src1 = self.mem[rip + 1];
-            let src2 = self.mem[rip + 2];
-            let dst = self.mem[rip + 3];
-            self.mem[dst] = self.mem[src1] + self.mem[src2];
-            self.rip += 4;
-        } else if opcode == 2 {
-            let src1 = self.mem[rip + 1];
-            let src2 = self.mem[rip + 2];
-            let dst = self.mem[rip + 3];
-            self.mem[dst] = self.mem[src1] * self.mem[src2];
-            self.rip += 4;
-        } else {
-            panic!("Unknown opcode {opcode} at rip = {rip}");
-        }
-    }
-
-    fn run(&mut self) -> usize {
-        while !self.halted {
-            self.step();
-        }
-        self.mem[0]
-    }
-}
+use aoc2019::{
+    symbolic::{Expr, SymbolicComputer},
+    IntcodeComputer,
+};
+use std::collections::HashMap;
 
 fn main() {
-    let input = std::fs::read("data/day2").unwrap();
-    let input = String::from_utf8(input).unwrap();
-
-    let mem: Vec<usize> = input
-        .trim()
-        .split_terminator(',')
-        .map(|s| s.parse().unwrap())
-        .collect();
+    let mut pc = SymbolicComputer::from_computer(&IntcodeComputer::from_file("data/day2"));
+    pc.mem[1] = Expr::var("noun");
+    pc.mem[2] = Expr::var("verb");
+    let paths = pc.explore(1).unwrap();
+    let result = &paths[0].mem[0];
 
-    let mut pc1 = IntcodeComputer {
-        mem: mem.clone(),
-        rip: 0,
-        halted: false,
-    };
-    pc1.mem[1] = 12;
-    pc1.mem[2] = 2;
-    pc1.run();
-    dbg!(pc1.mem[0]);
+    let part1 = result.eval(&HashMap::from([("noun", 12), ("verb", 2)]));
+    dbg!(part1);
 
+    // The result is linear in noun and verb, so solve for verb given noun.
+    // Without a verb term, any verb works if the noun does.
+    let target = 19690720;
+    let linear = result.linear().unwrap();
+    let coefficient = |name| linear.coefficients.get(name).copied().unwrap_or(0);
+    let (a, b) = (coefficient("noun"), coefficient("verb"));
     for noun in 0..100 {
-        for verb in 0..100 {
-            let mut pc = IntcodeComputer {
-                mem: mem.clone(),
-                rip: 0,
-                halted: false,
-            };
-            pc.mem[1] = noun;
-            pc.mem[2] = verb;
-            if pc.run() == 19690720 {
-                dbg!(noun * 100 + verb);
-            }
+        let rest = target - linear.constant - a * noun;
+        let verb = match b {
+            0 if rest == 0 => Some(0),
+            0 => None,
+            _ if rest % b == 0 => Some(rest / b),
+            _ => None,
+        };
+        if let Some(verb) = verb.filter(|verb| (0..100).contains(verb)) {
+            dbg!(noun * 100 + verb);
         }
     }
 }
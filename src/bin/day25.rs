@@ -1,4 +1,4 @@
-use aoc2019::IntcodeComputer;
+use aoc2019::{transcript::Transcript, IntcodeComputer};
 use itertools::Itertools;
 use std::{collections::HashMap, fmt::Display, io::Write};
 
@@ -65,10 +65,16 @@ fn take_item(
     go(computer, &reverse_path(&items[name]));
 }
 
-fn interact(computer: &mut IntcodeComputer) -> ! {
+/// Play interactively, saving the session to the transcript file `record`
+/// after every command. `transcript` holds the session since the program
+/// started, so the saved file replays from the start.
+fn interact(computer: &mut IntcodeComputer, mut transcript: Transcript, record: Option<&str>) -> ! {
     let stdin = std::io::stdin();
     loop {
-        computer.run();
+        transcript.run(computer);
+        if let Some(file) = record {
+            transcript.save(file).unwrap();
+        }
 
         while let Some(byte) = computer.output.pop_front() {
             print!("{}", char::from_u32(byte as u32).unwrap());
@@ -113,11 +119,13 @@ fn main() {
         Dir::South,
     ];
 
+    // Usage: day25 [transcript]
+    let record = std::env::args().nth(1);
     let mut computer = IntcodeComputer::from_file("data/day25");
     // for name in items.keys() {
     //     take_item(&mut computer, &items, name);
     // }
-    // interact(&mut computer);
+    // interact(&mut computer, Transcript::new(), record.as_deref());
 
     for inventory in items.keys().powerset() {
         let mut computer = computer.clone();
@@ -126,7 +134,8 @@ fn main() {
         }
         go(&mut computer, &gate);
 
-        computer.run();
+        let mut transcript = Transcript::new();
+        transcript.run(&mut computer);
 
         let failure_message = "you are ejected back to the checkpoint";
         let output: String = computer
@@ -135,7 +144,7 @@ fn main() {
             .map(|&value| char::from_u32(value as u32).unwrap())
             .collect();
         if !output.contains(failure_message) {
-            interact(&mut computer);
+            interact(&mut computer, transcript, record.as_deref());
         } else {
             let msg = output
                 .split_terminator("\n")
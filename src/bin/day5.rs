@@ -1,13 +1,14 @@
-use aoc2019::IntcodeComputer;
+use aoc2019::{conformance::diagnose_test, IntcodeComputer};
 
 fn main() {
-    let mut pc1 = IntcodeComputer::from_file("data/day5");
-    pc1.input.push_back(1);
-    pc1.run();
-    dbg!(&pc1.output);
+    let pc = IntcodeComputer::from_file("data/day5");
+    match diagnose_test(pc.clone(), 1) {
+        Ok(code) => println!("{code}"),
+        Err(failures) => failures.iter().for_each(|failure| println!("{failure}")),
+    }
 
-    let mut pc2 = IntcodeComputer::from_file("data/day5");
-    pc2.input.push_back(5);
-    pc2.run();
-    dbg!(&pc2.output);
+    match diagnose_test(pc, 5) {
+        Ok(code) => println!("{code}"),
+        Err(failures) => failures.iter().for_each(|failure| println!("{failure}")),
+    }
 }
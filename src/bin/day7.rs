@@ -31,12 +31,14 @@ fn main() {
             })
             .collect_vec();
 
+        // Each amplifier runs until it passes on the signal, or halts.
         let mut signal = 0;
         while !pcs[0].halted {
             for pc in &mut pcs {
                 pc.input.push_back(signal);
-                pc.run();
-                signal = pc.output.pop_back().unwrap();
+                if let Some(value) = pc.outputs(|| None).next() {
+                    signal = value;
+                }
             }
         }
         part2 = part2.max(signal);
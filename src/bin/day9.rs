@@ -1,13 +1,14 @@
-use aoc2019::IntcodeComputer;
+use aoc2019::{conformance::diagnose_boost, IntcodeComputer};
 
 fn main() {
-    let mut pc = IntcodeComputer::from_file("data/day9");
-    pc.input.push_back(1);
-    pc.run();
-    dbg!(pc.output);
+    let pc = IntcodeComputer::from_file("data/day9");
+    match diagnose_boost(pc.clone(), 1) {
+        Ok(code) => println!("{code}"),
+        Err(failures) => failures.iter().for_each(|failure| println!("{failure}")),
+    }
 
-    let mut pc = IntcodeComputer::from_file("data/day9");
+    let mut pc = pc;
     pc.input.push_back(2);
     pc.run();
-    dbg!(pc.output);
+    println!("{}", pc.output[0]);
 }
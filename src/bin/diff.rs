@@ -0,0 +1,16 @@
+use aoc2019::{diff::diff, IntcodeComputer};
+
+/// Usage: diff <program> <program>
+///
+/// Prints the changes from the first program to the second, aligned by
+/// instruction; see `aoc2019::diff`.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert!(args.len() == 3, "Usage: diff <program> <program>");
+
+    let a = IntcodeComputer::from_file(&args[1]).mem;
+    let b = IntcodeComputer::from_file(&args[2]).mem;
+    for change in diff(&a, &b) {
+        println!("{change}");
+    }
+}
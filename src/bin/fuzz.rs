@@ -0,0 +1,53 @@
+use aoc2019::{fuzz::Fuzzer, load, IntcodeComputer};
+
+/// Usage: fuzz <program> <corpus> <iterations> [seed]
+///
+/// Fuzzes the program's input, starting from the `input-*` files already in
+/// the corpus directory, and saves the corpus, crashes and distinct outputs
+/// there.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert!(
+        args.len() == 4 || args.len() == 5,
+        "Usage: fuzz <program> <corpus> <iterations> [seed]"
+    );
+    let iterations: usize = args[3].parse().unwrap();
+    let seed = args.get(4).map_or(1, |seed| seed.parse().unwrap());
+
+    let program = IntcodeComputer::from_file(&args[1]).mem;
+    let mut fuzzer = Fuzzer::new(program, seed);
+    // Crashes, including those of the corpus, are reported below rather
+    // than by the panic hook.
+    std::panic::set_hook(Box::new(|_| {}));
+    if let Ok(entries) = std::fs::read_dir(&args[2]) {
+        for entry in entries {
+            let path = entry.unwrap().path();
+            if path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("input-")
+            {
+                fuzzer.add(load::read_file(&path).unwrap());
+            }
+        }
+    }
+
+    let new = fuzzer.fuzz(iterations);
+    let _ = std::panic::take_hook();
+
+    println!(
+        "{new} new inputs, {} in the corpus, {} instructions and {} branch directions covered",
+        fuzzer.corpus.len(),
+        fuzzer.coverage.ips.len(),
+        fuzzer.coverage.branches.len()
+    );
+    println!("{} distinct outputs", fuzzer.outputs.len());
+    for crash in &fuzzer.crashes {
+        println!(
+            "crash at {}: {} on {:?}",
+            crash.ip, crash.message, crash.input
+        );
+    }
+    fuzzer.save(&args[2]).unwrap();
+}
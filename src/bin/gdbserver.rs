@@ -0,0 +1,23 @@
+use aoc2019::{gdb::Stub, IntcodeComputer};
+use std::net::TcpListener;
+
+/// Usage: gdbserver <program> [port]
+///
+/// Serves the program to a single debugger on localhost, by default on port
+/// 1234. Attach with `target remote localhost:1234`.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert!(
+        args.len() == 2 || args.len() == 3,
+        "Usage: gdbserver <program> [port]"
+    );
+    let port: u16 = args.get(2).map_or(1234, |port| port.parse().unwrap());
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+    println!("Listening on {}", listener.local_addr().unwrap());
+    let (stream, peer) = listener.accept().unwrap();
+    println!("Debugger attached from {peer}");
+    let mut stub = Stub::new(IntcodeComputer::from_file(&args[1]));
+    stub.serve(stream).unwrap();
+    println!("Debugger detached");
+}
@@ -0,0 +1,12 @@
+use aoc2019::load;
+
+/// Usage: image <program> <output>
+///
+/// Writes a program, text or image, to `output` as a binary image.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert!(args.len() == 3, "Usage: image <program> <output>");
+
+    let memory = load::read_file(&args[1]).unwrap_or_else(|error| panic!("{}: {error}", args[1]));
+    load::write_image(&args[2], &memory).unwrap();
+}
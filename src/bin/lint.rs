@@ -0,0 +1,24 @@
+use aoc2019::{
+    lint::{lint, Severity},
+    IntcodeComputer,
+};
+
+/// Usage: lint <program>
+///
+/// Prints the findings of the static checks, and fails if any is an error.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert!(args.len() == 2, "Usage: lint <program>");
+
+    let program = IntcodeComputer::from_file(&args[1]).mem;
+    let findings = lint(&program);
+    for finding in &findings {
+        println!("{finding}");
+    }
+    if findings
+        .iter()
+        .any(|finding| finding.severity == Severity::Error)
+    {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,44 @@
+use aoc2019::{
+    optimize::{check, optimize},
+    IntcodeComputer,
+};
+
+/// Usage: optimize <program> <output> [input,input,...]...
+///
+/// Each extra argument is a comma-separated input sequence used to check the
+/// optimised program against the original.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert!(
+        args.len() >= 3,
+        "Usage: optimize <program> <output> [input,input,...]..."
+    );
+    let inputs: Vec<Vec<i64>> = args[3..]
+        .iter()
+        .map(|arg| {
+            arg.split_terminator(',')
+                .map(|s| s.trim().parse().unwrap())
+                .collect()
+        })
+        .collect();
+
+    let program = IntcodeComputer::from_file(&args[1]).mem;
+    let optimized = optimize(&program);
+    if let Err((i, mismatch)) = check(&program, &optimized, &inputs) {
+        panic!(
+            "Optimised program differs on input {:?}: {mismatch:?}",
+            inputs[i]
+        );
+    }
+
+    let changed = std::iter::zip(&program, &optimized)
+        .filter(|(a, b)| a != b)
+        .count();
+    println!(
+        "{} -> {} words, {} rewritten",
+        program.len(),
+        optimized.len(),
+        changed
+    );
+    IntcodeComputer::new(optimized).save(&args[2]);
+}
@@ -0,0 +1,31 @@
+use aoc2019::{transcript::Transcript, IntcodeComputer};
+
+/// Usage: replay <program> <transcript>...
+///
+/// Checks that the program still behaves as recorded in each transcript.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert!(args.len() >= 3, "Usage: replay <program> <transcript>...");
+
+    let program = IntcodeComputer::from_file(&args[1]);
+    let mut failed = false;
+    for file in &args[2..] {
+        let result = Transcript::load(file)
+            .map_err(|error| error.to_string())
+            .and_then(|transcript| {
+                transcript
+                    .replay(&mut program.clone())
+                    .map_err(|error| error.to_string())
+            });
+        match result {
+            Ok(()) => println!("{file}: ok"),
+            Err(error) => {
+                println!("{file}: {error}");
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,47 @@
+use aoc2019::{
+    conformance::{cases, diagnose_boost, diagnose_test, Failure},
+    IntcodeComputer,
+};
+
+fn report(name: &str, result: Result<i64, Vec<Failure>>) -> bool {
+    match result {
+        Ok(code) => {
+            println!("ok   {name}: {code}");
+            true
+        }
+        Err(failures) => {
+            for failure in failures {
+                println!("FAIL {name}: {failure}");
+            }
+            false
+        }
+    }
+}
+
+/// Run the conformance suite and the diagnostic programs of days 5 and 9.
+fn main() {
+    let mut passed = true;
+    let opcodes = IntcodeComputer::new(vec![]).opcodes().clone();
+    for case in cases() {
+        match case.check(&opcodes) {
+            Ok(()) => println!("ok   {} {:?}", case.name, case.input),
+            Err(output) => {
+                println!(
+                    "FAIL {} {:?}: expected {:?}, got {output:?}",
+                    case.name, case.input, case.expected
+                );
+                passed = false;
+            }
+        }
+    }
+
+    let day5 = IntcodeComputer::from_file("data/day5");
+    passed &= report("day5 TEST 1", diagnose_test(day5.clone(), 1));
+    passed &= report("day5 TEST 5", diagnose_test(day5, 5));
+    let day9 = IntcodeComputer::from_file("data/day9");
+    passed &= report("day9 BOOST 1", diagnose_boost(day9, 1));
+
+    if !passed {
+        std::process::exit(1);
+    }
+}
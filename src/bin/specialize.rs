@@ -0,0 +1,35 @@
+use aoc2019::{
+    specialize::{check_equivalence, specialize},
+    IntcodeComputer,
+};
+
+/// Usage: specialize <program> <output> [input...] [-- rest...]
+///
+/// Specialises the program for the inputs, and checks that the result
+/// behaves like the program on the inputs followed by `rest`.
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert!(
+        args.len() >= 3,
+        "Usage: specialize <program> <output> [input...] [-- rest...]"
+    );
+    let values =
+        |args: &[String]| -> Vec<i64> { args.iter().map(|s| s.parse().unwrap()).collect() };
+    let (inputs, rest) = match args[3..].iter().position(|arg| arg == "--") {
+        Some(split) => (values(&args[3..3 + split]), values(&args[4 + split..])),
+        None => (values(&args[3..]), vec![]),
+    };
+
+    let program = IntcodeComputer::from_file(&args[1]).mem;
+    let specialized = match specialize(&program, &inputs) {
+        Ok(specialized) => specialized,
+        Err(error) => {
+            eprintln!("Can't specialise {}: {error}", args[1]);
+            std::process::exit(1);
+        }
+    };
+    check_equivalence(&program, &specialized, &inputs, &rest).unwrap();
+
+    IntcodeComputer::new(specialized.clone()).save(&args[2]);
+    println!("{} -> {} words", program.len(), specialized.len());
+}
@@ -0,0 +1,10 @@
+use aoc2019::{translate::translate, IntcodeComputer};
+
+/// Usage: translate <program> <output.rs>
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    assert_eq!(args.len(), 3, "Usage: translate <program> <output.rs>");
+
+    let program = IntcodeComputer::from_file(&args[1]).mem;
+    std::fs::write(&args[2], translate(&program)).unwrap();
+}
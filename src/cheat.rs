@@ -0,0 +1,217 @@
+//! Locating and changing game state in the memory of a running program.
+//!
+//! A `Scanner` remembers a snapshot of memory and a set of candidate
+//! addresses. Each filter compares memory with the snapshot, keeps the
+//! candidates that match, and takes a new snapshot. `Cheats` patches and
+//! freezes values at chosen addresses.
+
+use crate::IntcodeComputer;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Changed,
+    Unchanged,
+    /// The value is now equal to the given value.
+    Equal(i64),
+    Increased,
+    Decreased,
+    /// The value increased by exactly the given amount.
+    IncreasedBy(i64),
+    /// The value decreased by exactly the given amount.
+    DecreasedBy(i64),
+}
+
+impl Filter {
+    pub fn matches(self, old: i64, new: i64) -> bool {
+        match self {
+            Self::Changed => old != new,
+            Self::Unchanged => old == new,
+            Self::Equal(value) => new == value,
+            Self::Increased => new > old,
+            Self::Decreased => new < old,
+            Self::IncreasedBy(n) => new.checked_sub(old) == Some(n),
+            Self::DecreasedBy(n) => old.checked_sub(new) == Some(n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scanner {
+    snapshot: Vec<i64>,
+    candidates: Vec<usize>,
+    /// The filters applied since the scan started.
+    filters: Vec<Filter>,
+}
+
+/// The word at `addr`, reading zero past the end of memory.
+fn word(mem: &[i64], addr: usize) -> i64 {
+    mem.get(addr).copied().unwrap_or(0)
+}
+
+impl Scanner {
+    /// Start a scan with every address of `pc` as a candidate.
+    pub fn new(pc: &IntcodeComputer) -> Self {
+        Self {
+            snapshot: pc.mem.clone(),
+            candidates: (0..pc.mem.len()).collect(),
+            filters: vec![],
+        }
+    }
+
+    /// Keep the candidates whose change since the last snapshot matches
+    /// `filter`, then take a new snapshot.
+    pub fn filter(&mut self, pc: &IntcodeComputer, filter: Filter) {
+        self.candidates
+            .retain(|&addr| filter.matches(word(&self.snapshot, addr), word(&pc.mem, addr)));
+        // Memory that appeared since the last snapshot read as zero before,
+        // so it is a candidate if zero matched every earlier filter.
+        if self.filters.iter().all(|earlier| earlier.matches(0, 0)) {
+            for addr in self.snapshot.len()..pc.mem.len() {
+                if filter.matches(0, pc.mem[addr]) {
+                    self.candidates.push(addr);
+                }
+            }
+        }
+        self.filters.push(filter);
+        self.snapshot = pc.mem.clone();
+    }
+
+    /// The remaining candidates, in increasing order.
+    pub fn candidates(&self) -> &[usize] {
+        &self.candidates
+    }
+}
+
+/// Values forced into the memory of a computer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cheats {
+    pub frozen: BTreeMap<usize, i64>,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the word at `addr` once.
+    pub fn patch(pc: &mut IntcodeComputer, addr: usize, value: i64) {
+        if pc.mem.len() <= addr {
+            pc.mem.resize(addr + 1, 0);
+        }
+        pc.mem[addr] = value;
+    }
+
+    /// Keep the word at `addr` at `value` while running with `run`.
+    pub fn freeze(&mut self, addr: usize, value: i64) {
+        self.frozen.insert(addr, value);
+    }
+
+    pub fn unfreeze(&mut self, addr: usize) {
+        self.frozen.remove(&addr);
+    }
+
+    /// Write every frozen value to `pc`.
+    pub fn apply(&self, pc: &mut IntcodeComputer) {
+        for (&addr, &value) in &self.frozen {
+            Self::patch(pc, addr, value);
+        }
+    }
+
+    /// Run `pc` like `IntcodeComputer::run`, restoring the frozen values
+    /// before every instruction.
+    pub fn run(&self, pc: &mut IntcodeComputer) {
+        if self.frozen.is_empty() {
+            return pc.run();
+        }
+        while !pc.halted {
+            self.apply(pc);
+            if !pc.step() {
+                break;
+            }
+        }
+        self.apply(pc);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Cheats, Filter, Scanner};
+    use crate::IntcodeComputer;
+
+    /// A game that reads a move, adds it to the position at [30], adds 10
+    /// to the score at [31], and outputs the score.
+    fn game() -> IntcodeComputer {
+        // IN [32]; ADD [30],[32],[30]; ADD [31],10,[31]; OUT [31]; JZ 0,0
+        IntcodeComputer::new(vec![
+            3, 32, 1, 30, 32, 30, 1001, 31, 10, 31, 4, 31, 1106, 0, 0,
+        ])
+    }
+
+    #[test]
+    fn scan() {
+        let mut pc = game();
+        pc.run();
+        let mut scanner = Scanner::new(&pc);
+
+        pc.input.push_back(1);
+        pc.run();
+        scanner.filter(&pc, Filter::Changed);
+        assert_eq!(scanner.candidates(), [30, 31, 32]);
+
+        pc.input.push_back(-1);
+        pc.run();
+        scanner.filter(&pc, Filter::DecreasedBy(1));
+        assert_eq!(scanner.candidates(), [30]);
+
+        let mut scanner = Scanner::new(&pc);
+        pc.input.push_back(0);
+        pc.run();
+        scanner.filter(&pc, Filter::IncreasedBy(10));
+        assert_eq!(scanner.candidates(), [31]);
+    }
+
+    #[test]
+    fn scan_new_memory() {
+        let mut pc = game();
+        pc.input.push_back(1);
+        pc.run();
+        let mut scanner = Scanner::new(&pc);
+        pc.input.push_back(0);
+        pc.run();
+        scanner.filter(&pc, Filter::Changed);
+        assert_eq!(scanner.candidates(), [31, 32]);
+
+        // [33] appears after the scan required a change, when it read as
+        // zero, so it isn't a candidate.
+        pc.mem.push(5);
+        pc.input.push_back(0);
+        pc.run();
+        scanner.filter(&pc, Filter::Increased);
+        assert_eq!(scanner.candidates(), [31]);
+
+        // New memory matching every filter so far is a candidate.
+        let mut scanner = Scanner::new(&pc);
+        scanner.filter(&pc, Filter::Unchanged);
+        pc.mem.push(7);
+        scanner.filter(&pc, Filter::Equal(7));
+        assert_eq!(scanner.candidates(), [34]);
+    }
+
+    #[test]
+    fn patch_and_freeze() {
+        let mut pc = game();
+        Cheats::patch(&mut pc, 31, 1000);
+        pc.input.push_back(0);
+        pc.run();
+        assert_eq!(pc.output, [1010]);
+
+        let mut cheats = Cheats::new();
+        cheats.freeze(31, 500);
+        pc.input.extend([0, 0]);
+        cheats.run(&mut pc);
+        // The score is reset before it is output.
+        assert_eq!(pc.output, [1010, 500, 500]);
+        assert_eq!(pc.mem[31], 500);
+    }
+}
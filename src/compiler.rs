@@ -0,0 +1,1172 @@
+//! A compiler from a small imperative language to Intcode.
+//!
+//! A program is global variables and functions, starting at `main`:
+//!
+//! ```text
+//! var total = 0;
+//! var squares[10];
+//!
+//! fn main() {
+//!     var n = input();
+//!     var i = 0;
+//!     while i < n {
+//!         squares[i] = square(i);
+//!         i = i + 1;
+//!     }
+//!     output(sum(squares, n));
+//! }
+//!
+//! fn square(x) { return x * x; }
+//!
+//! fn sum(values, n) {
+//!     var i = 0;
+//!     while i < n { total = total + values[i]; i = i + 1; }
+//!     return total;
+//! }
+//! ```
+//!
+//! All values are integers, and variables and arrays start out zero.
+//! `var name[size];` declares an array; using its name gives its address,
+//! and any value can be indexed as an address, so arrays are passed to
+//! functions by reference. Expressions have `+`, `-`, `*`, comparisons,
+//! `!`, `&&` and `||` (short-circuiting) and character literals like `'a'`,
+//! but no division, which Intcode has no instruction for. Statements are
+//! declarations, assignments, `if`/`else`, `while`, `break`, `continue`,
+//! `return` and calls. `input()` reads a value and `output(value)` writes
+//! one. `//` starts a comment.
+//!
+//! Functions keep their parameters, locals and temporaries in a frame at
+//! the relative base, which is moved past the caller's frame for a call.
+//! Indexing patches the address into the instruction that does the access.
+
+use crate::asm::assemble;
+use std::{
+    collections::{HashMap, HashSet},
+    error, fmt,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompileError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for CompileError {}
+
+type Result<T> = std::result::Result<T, CompileError>;
+
+fn error<T>(line: usize, message: String) -> Result<T> {
+    Err(CompileError { line, message })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Symbol(&'static str),
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(value) => write!(f, "{value}"),
+            Self::Ident(name) => write!(f, "{name}"),
+            Self::Symbol(symbol) => write!(f, "{symbol}"),
+            Self::End => write!(f, "end of input"),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 21] = [
+    "==", "!=", "<=", ">=", "&&", "||", "+", "-", "*", "<", ">", "=", "!", "(", ")", "{", "}", "[",
+    "]", ",", ";",
+];
+
+const KEYWORDS: [&str; 8] = [
+    "var", "fn", "if", "else", "while", "break", "continue", "return",
+];
+
+/// The largest array size a declaration may give.
+const MAX_ARRAY_SIZE: usize = 1 << 20;
+
+/// Split `source` into tokens and their line numbers.
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = vec![];
+    for (i, line) in source.lines().enumerate() {
+        let line_number = i + 1;
+        let mut rest = line.split("//").next().unwrap().trim_start();
+        while !rest.is_empty() {
+            let token_len;
+            if let Some(literal) = rest.strip_prefix('\'') {
+                let (value, len) = match literal.as_bytes() {
+                    [b'\\', b'n', b'\'', ..] => (b'\n', 3),
+                    [b'\\', c @ (b'\\' | b'\''), b'\'', ..] => (*c, 3),
+                    [c, b'\'', ..] if c.is_ascii() && *c != b'\\' => (*c, 2),
+                    _ => return error(line_number, "invalid character literal".to_string()),
+                };
+                tokens.push((Token::Number(value as i64), line_number));
+                token_len = 1 + len;
+            } else if rest.starts_with(|c: char| c.is_ascii_digit()) {
+                token_len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(rest.len());
+                let Ok(value) = rest[..token_len].parse() else {
+                    return error(
+                        line_number,
+                        format!("invalid number {}", &rest[..token_len]),
+                    );
+                };
+                tokens.push((Token::Number(value), line_number));
+            } else if rest.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+                token_len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                tokens.push((Token::Ident(rest[..token_len].to_string()), line_number));
+            } else if let Some(symbol) = SYMBOLS.into_iter().find(|s| rest.starts_with(s)) {
+                token_len = symbol.len();
+                tokens.push((Token::Symbol(symbol), line_number));
+            } else {
+                let c = rest.chars().next().unwrap();
+                return error(line_number, format!("unexpected character {c:?}"));
+            }
+            rest = rest[token_len..].trim_start();
+        }
+    }
+    let last_line = source.lines().count().max(1);
+    tokens.push((Token::End, last_line));
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Number(i64),
+    Var(String),
+    Index(Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Stmt {
+    /// A scalar with an optional initial value, or an array.
+    Var {
+        name: String,
+        size: Option<usize>,
+        init: Option<Expr>,
+    },
+    Assign {
+        target: Expr,
+        value: Expr,
+    },
+    If {
+        cond: Expr,
+        then: Block,
+        otherwise: Block,
+    },
+    While {
+        cond: Expr,
+        body: Block,
+    },
+    Break,
+    Continue,
+    Return(Option<Expr>),
+    Expr(Expr),
+}
+
+/// Statements and their line numbers.
+type Block = Vec<(Stmt, usize)>;
+
+#[derive(Debug)]
+struct Function {
+    name: String,
+    params: Vec<String>,
+    body: Block,
+    line: usize,
+}
+
+#[derive(Debug)]
+struct Global {
+    name: String,
+    size: Option<usize>,
+    init: i64,
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    /// Skip `symbol` if it's next.
+    fn eat(&mut self, symbol: &str) -> bool {
+        let found = match self.peek() {
+            Token::Symbol(s) => *s == symbol,
+            Token::Ident(s) => s == symbol,
+            _ => false,
+        };
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn unexpected<T>(&self, expected: &str) -> Result<T> {
+        error(
+            self.line(),
+            format!("expected {expected}, found {}", self.peek()),
+        )
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<()> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            self.unexpected(symbol)
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.peek() {
+            Token::Ident(name) if !KEYWORDS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.pos += 1;
+                Ok(name)
+            }
+            _ => self.unexpected("a name"),
+        }
+    }
+
+    fn number(&mut self) -> Result<i64> {
+        let negative = self.eat("-");
+        let Token::Number(value) = *self.peek() else {
+            return self.unexpected("a number");
+        };
+        self.pos += 1;
+        Ok(if negative { -value } else { value })
+    }
+
+    fn program(&mut self) -> Result<(Vec<Global>, Vec<Function>)> {
+        let mut globals = vec![];
+        let mut functions = vec![];
+        while *self.peek() != Token::End {
+            let line = self.line();
+            if self.eat("var") {
+                let name = self.ident()?;
+                let mut global = Global {
+                    name,
+                    size: None,
+                    init: 0,
+                };
+                if self.eat("[") {
+                    global.size = Some(self.size()?);
+                    self.expect("]")?;
+                } else if self.eat("=") {
+                    global.init = self.number()?;
+                }
+                self.expect(";")?;
+                globals.push(global);
+            } else if self.eat("fn") {
+                let name = self.ident()?;
+                self.expect("(")?;
+                let mut params = vec![];
+                while !self.eat(")") {
+                    if !params.is_empty() {
+                        self.expect(",")?;
+                    }
+                    params.push(self.ident()?);
+                }
+                let body = self.block()?;
+                functions.push(Function {
+                    name,
+                    params,
+                    body,
+                    line,
+                });
+            } else {
+                return self.unexpected("var or fn");
+            }
+        }
+        Ok((globals, functions))
+    }
+
+    fn size(&mut self) -> Result<usize> {
+        let line = self.line();
+        match usize::try_from(self.number()?) {
+            Ok(size) if size > MAX_ARRAY_SIZE => error(
+                line,
+                format!("array size should be at most {MAX_ARRAY_SIZE}"),
+            ),
+            Ok(size) if size > 0 => Ok(size),
+            _ => error(line, "array size should be positive".to_string()),
+        }
+    }
+
+    fn block(&mut self) -> Result<Block> {
+        self.expect("{")?;
+        let mut block = vec![];
+        while !self.eat("}") {
+            let line = self.line();
+            block.push((self.statement()?, line));
+        }
+        Ok(block)
+    }
+
+    fn statement(&mut self) -> Result<Stmt> {
+        let stmt = if self.eat("var") {
+            let name = self.ident()?;
+            let (mut size, mut init) = (None, None);
+            if self.eat("[") {
+                size = Some(self.size()?);
+                self.expect("]")?;
+            } else if self.eat("=") {
+                init = Some(self.expr()?);
+            }
+            Stmt::Var { name, size, init }
+        } else if self.eat("if") {
+            return self.if_statement();
+        } else if self.eat("while") {
+            let cond = self.expr()?;
+            let body = self.block()?;
+            return Ok(Stmt::While { cond, body });
+        } else if self.eat("break") {
+            Stmt::Break
+        } else if self.eat("continue") {
+            Stmt::Continue
+        } else if self.eat("return") {
+            if *self.peek() == Token::Symbol(";") {
+                Stmt::Return(None)
+            } else {
+                Stmt::Return(Some(self.expr()?))
+            }
+        } else {
+            let expr = self.expr()?;
+            if self.eat("=") {
+                if !matches!(expr, Expr::Var(_) | Expr::Index(..)) {
+                    return error(self.line(), "can't assign to an expression".to_string());
+                }
+                let value = self.expr()?;
+                Stmt::Assign {
+                    target: expr,
+                    value,
+                }
+            } else {
+                Stmt::Expr(expr)
+            }
+        };
+        self.expect(";")?;
+        Ok(stmt)
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt> {
+        let cond = self.expr()?;
+        let then = self.block()?;
+        let otherwise = if !self.eat("else") {
+            vec![]
+        } else if *self.peek() == Token::Ident("if".to_string()) {
+            let line = self.line();
+            self.pos += 1;
+            vec![(self.if_statement()?, line)]
+        } else {
+            self.block()?
+        };
+        Ok(Stmt::If {
+            cond,
+            then,
+            otherwise,
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        self.binary(0)
+    }
+
+    /// Parse operators binding at least as tightly as `LEVELS[level]`.
+    fn binary(&mut self, level: usize) -> Result<Expr> {
+        const LEVELS: [&[(&str, BinaryOp)]; 5] = [
+            &[("||", BinaryOp::Or)],
+            &[("&&", BinaryOp::And)],
+            &[
+                ("==", BinaryOp::Eq),
+                ("!=", BinaryOp::Ne),
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+            ],
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            &[("*", BinaryOp::Mul)],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(&(_, op)) = LEVELS[level].iter().find(|(symbol, _)| self.eat(symbol)) {
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let mut expr = self.primary()?;
+        while self.eat("[") {
+            let index = self.expr()?;
+            self.expect("]")?;
+            expr = Expr::Index(Box::new(expr), Box::new(index));
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        if let Token::Number(value) = *self.peek() {
+            self.pos += 1;
+            return Ok(Expr::Number(value));
+        }
+        if self.eat("(") {
+            let expr = self.expr()?;
+            self.expect(")")?;
+            return Ok(expr);
+        }
+        let name = self.ident()?;
+        if !self.eat("(") {
+            return Ok(Expr::Var(name));
+        }
+        let mut args = vec![];
+        while !self.eat(")") {
+            if !args.is_empty() {
+                self.expect(",")?;
+            }
+            args.push(self.expr()?);
+        }
+        Ok(Expr::Call(name, args))
+    }
+}
+
+/// A compiled operand, in assembler syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Number(i64),
+    /// A label or memory parameter.
+    Other(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(value) => write!(f, "{value}"),
+            Self::Other(text) => write!(f, "{text}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Local {
+    /// A value in a frame slot.
+    Scalar(usize),
+    /// An array starting at a frame slot.
+    Array(usize),
+}
+
+/// The state of the function being compiled. Slot 0 of the frame holds the
+/// return address and the return value goes in slot 1. Parameters follow
+/// from slot 1, then locals and temporaries.
+struct Frame {
+    scopes: Vec<HashMap<String, Local>>,
+    /// The first free slot.
+    next: usize,
+    /// Slots used so far, the size of the frame.
+    size: usize,
+    /// Labels to continue and break the enclosing loops at.
+    loops: Vec<(String, String)>,
+}
+
+impl Frame {
+    fn alloc(&mut self, slots: usize) -> usize {
+        let slot = self.next;
+        self.next += slots;
+        self.size = self.size.max(self.next);
+        slot
+    }
+
+    fn temp(&mut self) -> String {
+        format!("[rb+{}]", self.alloc(1))
+    }
+}
+
+/// The placeholder for the frame size plus `offset`, filled in once the
+/// function is compiled.
+fn frame_size(offset: usize) -> String {
+    format!("{{F+{offset}}}")
+}
+
+/// Replace the frame size placeholders in `line`.
+fn resolve(line: &str, size: usize) -> String {
+    let mut result = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find("{F+") {
+        let end = start + rest[start..].find('}').unwrap();
+        let offset: usize = rest[start + 3..end].parse().unwrap();
+        result += &rest[..start];
+        result += &(size + offset).to_string();
+        rest = &rest[end + 1..];
+    }
+    result + rest
+}
+
+struct Compiler {
+    lines: Vec<String>,
+    labels: usize,
+    /// Global names, with their array sizes.
+    globals: HashMap<String, Option<usize>>,
+    /// Function names, with their numbers of parameters.
+    functions: HashMap<String, usize>,
+    /// Whether some function has a local array, whose address needs the
+    /// value of the relative base. It's then kept in `fp`.
+    uses_fp: bool,
+}
+
+impl Compiler {
+    fn emit(&mut self, line: String) {
+        self.lines.push(line);
+    }
+
+    fn label(&mut self) -> String {
+        self.labels += 1;
+        format!("L{}", self.labels)
+    }
+
+    fn lookup(&self, frame: &Frame, name: &str, line: usize) -> Result<Option<Local>> {
+        if let Some(local) = frame.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            return Ok(Some(*local));
+        }
+        if self.globals.contains_key(name) {
+            return Ok(None);
+        }
+        error(line, format!("unknown variable {name}"))
+    }
+
+    /// The value of variable `name`: its contents, or an array's address.
+    fn var(&mut self, frame: &mut Frame, name: &str, line: usize) -> Result<Value> {
+        Ok(match self.lookup(frame, name, line)? {
+            Some(Local::Scalar(slot)) => Value::Other(format!("[rb+{slot}]")),
+            Some(Local::Array(slot)) => {
+                let temp = frame.temp();
+                self.emit(format!("ADD [fp], {slot}, {temp}"));
+                Value::Other(temp)
+            }
+            None if self.globals[name].is_some() => Value::Other(format!("g_{name}")),
+            None => Value::Other(format!("[g_{name}]")),
+        })
+    }
+
+    /// The memory parameter `target` refers to, possibly after emitting code
+    /// that patches the word at `patch`.
+    fn place(
+        &mut self,
+        frame: &mut Frame,
+        target: &Expr,
+        patch: &str,
+        line: usize,
+    ) -> Result<String> {
+        match target {
+            Expr::Var(name) => match self.lookup(frame, name, line)? {
+                Some(Local::Scalar(slot)) => Ok(format!("[rb+{slot}]")),
+                None if self.globals[name].is_none() => Ok(format!("[g_{name}]")),
+                _ => error(line, format!("can't assign to array {name}")),
+            },
+            Expr::Index(base, index) => {
+                let base = self.expr(frame, base, line)?;
+                let index = self.expr(frame, index, line)?;
+                if let (Value::Other(label), Value::Number(offset)) = (&base, &index) {
+                    if !label.starts_with('[') {
+                        return Ok(format!("[{label}{offset:+}]"));
+                    }
+                }
+                self.emit(format!("ADD {base}, {index}, [{patch}]"));
+                Ok("[0]".to_string())
+            }
+            _ => unreachable!("checked by the parser"),
+        }
+    }
+
+    fn expr(&mut self, frame: &mut Frame, expr: &Expr, line: usize) -> Result<Value> {
+        match expr {
+            Expr::Number(value) => Ok(Value::Number(*value)),
+            Expr::Var(name) => self.var(frame, name, line),
+            Expr::Index(..) => {
+                let label = self.label();
+                let source = self.place(frame, expr, &format!("{label}+1"), line)?;
+                let temp = frame.temp();
+                self.emit(format!("{label}: ADD {source}, 0, {temp}"));
+                Ok(Value::Other(temp))
+            }
+            Expr::Call(name, args) => self.call(frame, name, args, line),
+            Expr::Neg(inner) => match self.expr(frame, inner, line)? {
+                Value::Number(value) if value.checked_neg().is_some() => Ok(Value::Number(-value)),
+                value => {
+                    let temp = frame.temp();
+                    self.emit(format!("MUL {value}, -1, {temp}"));
+                    Ok(Value::Other(temp))
+                }
+            },
+            Expr::Not(inner) => match self.expr(frame, inner, line)? {
+                Value::Number(value) => Ok(Value::Number((value == 0) as i64)),
+                value => {
+                    let temp = frame.temp();
+                    self.emit(format!("EQ {value}, 0, {temp}"));
+                    Ok(Value::Other(temp))
+                }
+            },
+            Expr::Binary(op @ (BinaryOp::And | BinaryOp::Or), lhs, rhs) => {
+                // temp = lhs is decisive ? lhs != 0 : rhs != 0
+                let (end, temp) = (self.label(), frame.temp());
+                let (decided, jump) = match op {
+                    BinaryOp::And => (0, "JZ"),
+                    _ => (1, "JNZ"),
+                };
+                self.emit(format!("ADD {decided}, 0, {temp}"));
+                let lhs = self.expr(frame, lhs, line)?;
+                self.emit(format!("{jump} {lhs}, {end}"));
+                let rhs = self.expr(frame, rhs, line)?;
+                self.emit(format!("EQ {rhs}, 0, {temp}"));
+                self.emit(format!("EQ {temp}, 0, {temp}"));
+                self.emit(format!("{end}:"));
+                Ok(Value::Other(temp))
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.expr(frame, lhs, line)?;
+                let rhs = self.expr(frame, rhs, line)?;
+                if let (Value::Number(a), Value::Number(b)) = (&lhs, &rhs) {
+                    if let Some(value) = fold(*op, *a, *b) {
+                        return Ok(Value::Number(value));
+                    }
+                }
+                let temp = frame.temp();
+                match op {
+                    BinaryOp::Add => self.emit(format!("ADD {lhs}, {rhs}, {temp}")),
+                    BinaryOp::Sub => {
+                        self.emit(format!("MUL {rhs}, -1, {temp}"));
+                        self.emit(format!("ADD {lhs}, {temp}, {temp}"));
+                    }
+                    BinaryOp::Mul => self.emit(format!("MUL {lhs}, {rhs}, {temp}")),
+                    BinaryOp::Lt => self.emit(format!("LT {lhs}, {rhs}, {temp}")),
+                    BinaryOp::Gt => self.emit(format!("LT {rhs}, {lhs}, {temp}")),
+                    BinaryOp::Eq => self.emit(format!("EQ {lhs}, {rhs}, {temp}")),
+                    BinaryOp::Le | BinaryOp::Ge | BinaryOp::Ne => {
+                        let test = match op {
+                            BinaryOp::Le => format!("LT {rhs}, {lhs}"),
+                            BinaryOp::Ge => format!("LT {lhs}, {rhs}"),
+                            _ => format!("EQ {lhs}, {rhs}"),
+                        };
+                        self.emit(format!("{test}, {temp}"));
+                        self.emit(format!("EQ {temp}, 0, {temp}"));
+                    }
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+                Ok(Value::Other(temp))
+            }
+        }
+    }
+
+    fn call(&mut self, frame: &mut Frame, name: &str, args: &[Expr], line: usize) -> Result<Value> {
+        let arity = match name {
+            "input" => 0,
+            "output" => 1,
+            _ => match self.functions.get(name) {
+                Some(&arity) => arity,
+                None => return error(line, format!("unknown function {name}")),
+            },
+        };
+        if args.len() != arity {
+            return error(line, format!("{name} takes {arity} arguments"));
+        }
+        let args = args
+            .iter()
+            .map(|arg| self.expr(frame, arg, line))
+            .collect::<Result<Vec<_>>>()?;
+        match name {
+            "input" => {
+                let temp = frame.temp();
+                self.emit(format!("IN {temp}"));
+                return Ok(Value::Other(temp));
+            }
+            "output" => {
+                self.emit(format!("OUT {}", args[0]));
+                return Ok(Value::Number(0));
+            }
+            _ => {}
+        }
+
+        for (i, arg) in args.iter().enumerate() {
+            self.emit(format!("ADD {arg}, 0, [rb+{}]", frame_size(1 + i)));
+        }
+        let ret = self.label();
+        if self.uses_fp {
+            self.emit(format!("ADD [fp], {}, [fp]", frame_size(0)));
+        }
+        self.emit(format!("RB {}", frame_size(0)));
+        self.emit(format!("ADD {ret}, 0, [rb+0]"));
+        self.emit(format!("JZ 0, f_{name}"));
+        self.emit(format!("{ret}: RB -{}", frame_size(0)));
+        if self.uses_fp {
+            self.emit(format!("ADD [fp], -{}, [fp]", frame_size(0)));
+        }
+        let temp = frame.temp();
+        self.emit(format!("ADD [rb+{}], 0, {temp}", frame_size(1)));
+        Ok(Value::Other(temp))
+    }
+
+    fn block(&mut self, frame: &mut Frame, block: &Block) -> Result<()> {
+        let next = frame.next;
+        frame.scopes.push(HashMap::new());
+        for (stmt, line) in block {
+            self.statement(frame, stmt, *line)?;
+        }
+        frame.scopes.pop();
+        frame.next = next;
+        Ok(())
+    }
+
+    fn statement(&mut self, frame: &mut Frame, stmt: &Stmt, line: usize) -> Result<()> {
+        // Temporaries are only live within a statement.
+        let next = frame.next;
+        match stmt {
+            Stmt::Var { name, size, init } => {
+                let value = match init {
+                    Some(init) => self.expr(frame, init, line)?,
+                    None => Value::Number(0),
+                };
+                frame.next = next;
+                if frame.scopes.last().unwrap().contains_key(name) {
+                    return error(line, format!("duplicate variable {name}"));
+                }
+                let local = match size {
+                    Some(size) => {
+                        // The frame may hold values from an earlier call, so
+                        // zero the elements in a loop that patches the
+                        // address into its store.
+                        let slot = frame.alloc(*size);
+                        let (addr, count) = (frame.temp(), frame.temp());
+                        let (start, store) = (self.label(), self.label());
+                        self.emit(format!("ADD [fp], {slot}, {addr}"));
+                        self.emit(format!("ADD {size}, 0, {count}"));
+                        self.emit(format!("{start}: ADD {addr}, 0, [{store}+3]"));
+                        self.emit(format!("{store}: ADD 0, 0, [0]"));
+                        self.emit(format!("ADD {addr}, 1, {addr}"));
+                        self.emit(format!("ADD {count}, -1, {count}"));
+                        self.emit(format!("JNZ {count}, {start}"));
+                        frame.next = slot + size;
+                        Local::Array(slot)
+                    }
+                    None => {
+                        let slot = frame.alloc(1);
+                        self.emit(format!("ADD {value}, 0, [rb+{slot}]"));
+                        Local::Scalar(slot)
+                    }
+                };
+                frame.scopes.last_mut().unwrap().insert(name.clone(), local);
+                return Ok(());
+            }
+            Stmt::Assign { target, value } => {
+                let value = self.expr(frame, value, line)?;
+                let label = self.label();
+                let place = self.place(frame, target, &format!("{label}+3"), line)?;
+                self.emit(format!("{label}: ADD {value}, 0, {place}"));
+            }
+            Stmt::If {
+                cond,
+                then,
+                otherwise,
+            } => {
+                let (other, end) = (self.label(), self.label());
+                let cond = self.expr(frame, cond, line)?;
+                frame.next = next;
+                self.emit(format!("JZ {cond}, {other}"));
+                self.block(frame, then)?;
+                if !otherwise.is_empty() {
+                    self.emit(format!("JZ 0, {end}"));
+                }
+                self.emit(format!("{other}:"));
+                self.block(frame, otherwise)?;
+                self.emit(format!("{end}:"));
+            }
+            Stmt::While { cond, body } => {
+                let (start, end) = (self.label(), self.label());
+                self.emit(format!("{start}:"));
+                let cond = self.expr(frame, cond, line)?;
+                frame.next = next;
+                self.emit(format!("JZ {cond}, {end}"));
+                frame.loops.push((start.clone(), end.clone()));
+                self.block(frame, body)?;
+                frame.loops.pop();
+                self.emit(format!("JZ 0, {start}"));
+                self.emit(format!("{end}:"));
+            }
+            Stmt::Break | Stmt::Continue => {
+                let Some((start, end)) = frame.loops.last() else {
+                    return error(line, "break or continue outside a loop".to_string());
+                };
+                let target = if *stmt == Stmt::Break { end } else { start };
+                self.emit(format!("JZ 0, {target}"));
+            }
+            Stmt::Return(value) => {
+                let value = match value {
+                    Some(value) => self.expr(frame, value, line)?,
+                    None => Value::Number(0),
+                };
+                self.emit(format!("ADD {value}, 0, [rb+1]"));
+                self.emit("JZ 0, [rb+0]".to_string());
+            }
+            Stmt::Expr(expr) => {
+                self.expr(frame, expr, line)?;
+            }
+        }
+        frame.next = next;
+        Ok(())
+    }
+
+    fn function(&mut self, function: &Function) -> Result<()> {
+        let mut params = HashMap::new();
+        for (i, param) in function.params.iter().enumerate() {
+            if params.insert(param.clone(), Local::Scalar(1 + i)).is_some() {
+                return error(function.line, format!("duplicate parameter {param}"));
+            }
+        }
+        let mut frame = Frame {
+            scopes: vec![params],
+            next: 1 + function.params.len(),
+            size: 1 + function.params.len(),
+            loops: vec![],
+        };
+        let start = self.lines.len();
+        self.emit(format!("f_{}:", function.name));
+        self.block(&mut frame, &function.body)?;
+        self.emit("ADD 0, 0, [rb+1]".to_string());
+        self.emit("JZ 0, [rb+0]".to_string());
+        // The return value needs slot 1 even without parameters.
+        let size = frame.size.max(2);
+        for line in &mut self.lines[start..] {
+            *line = resolve(line, size);
+        }
+        Ok(())
+    }
+}
+
+fn fold(op: BinaryOp, a: i64, b: i64) -> Option<i64> {
+    match op {
+        BinaryOp::Add => a.checked_add(b),
+        BinaryOp::Sub => a.checked_sub(b),
+        BinaryOp::Mul => a.checked_mul(b),
+        BinaryOp::Lt => Some((a < b) as i64),
+        BinaryOp::Gt => Some((a > b) as i64),
+        BinaryOp::Le => Some((a <= b) as i64),
+        BinaryOp::Ge => Some((a >= b) as i64),
+        BinaryOp::Eq => Some((a == b) as i64),
+        BinaryOp::Ne => Some((a != b) as i64),
+        BinaryOp::And | BinaryOp::Or => None,
+    }
+}
+
+/// Whether `block` declares an array.
+fn has_array(block: &Block) -> bool {
+    block.iter().any(|(stmt, _)| match stmt {
+        Stmt::Var { size, .. } => size.is_some(),
+        Stmt::If {
+            then, otherwise, ..
+        } => has_array(then) || has_array(otherwise),
+        Stmt::While { body, .. } => has_array(body),
+        _ => false,
+    })
+}
+
+/// Compile `source` to assembly for `asm::assemble`.
+pub fn compile_to_asm(source: &str) -> Result<String> {
+    let (globals, functions) = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+    }
+    .program()?;
+
+    let mut compiler = Compiler {
+        lines: vec![],
+        labels: 0,
+        globals: HashMap::new(),
+        functions: HashMap::new(),
+        uses_fp: functions.iter().any(|function| has_array(&function.body)),
+    };
+    let mut names = HashSet::new();
+    for global in &globals {
+        if !names.insert(global.name.as_str()) {
+            return error(1, format!("duplicate global {}", global.name));
+        }
+        compiler.globals.insert(global.name.clone(), global.size);
+    }
+    for function in &functions {
+        if ["input", "output"].contains(&function.name.as_str())
+            || !names.insert(function.name.as_str())
+        {
+            return error(function.line, format!("duplicate name {}", function.name));
+        }
+        compiler
+            .functions
+            .insert(function.name.clone(), function.params.len());
+    }
+    match compiler.functions.get("main") {
+        Some(0) => {}
+        Some(_) => return error(1, "main takes no arguments".to_string()),
+        None => return error(1, "no main function".to_string()),
+    }
+
+    // Call main with the stack after the program, then halt.
+    compiler.emit("RB stack".to_string());
+    if compiler.uses_fp {
+        compiler.emit("ADD stack, 0, [fp]".to_string());
+    }
+    compiler.emit("ADD end, 0, [rb+0]".to_string());
+    compiler.emit("JZ 0, f_main".to_string());
+    compiler.emit("end: HALT".to_string());
+    for function in &functions {
+        compiler.function(function)?;
+    }
+    for global in &globals {
+        let words = match global.size {
+            Some(size) => vec!["0"; size].join(", "),
+            None => global.init.to_string(),
+        };
+        compiler.emit(format!("g_{}: DATA {words}", global.name));
+    }
+    if compiler.uses_fp {
+        compiler.emit("fp: DATA 0".to_string());
+    }
+    compiler.emit("stack:".to_string());
+    Ok(compiler.lines.join("\n") + "\n")
+}
+
+/// Compile `source` to an Intcode program.
+pub fn compile(source: &str) -> Result<Vec<i64>> {
+    let asm = compile_to_asm(source)?;
+    Ok(assemble(&asm)
+        .expect("compiled assembly should assemble")
+        .program)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compile, CompileError};
+    use crate::IntcodeComputer;
+
+    /// Compile and run `source` with `input`, returning its output.
+    fn run(source: &str, input: &[i64]) -> Vec<i64> {
+        let mut pc = IntcodeComputer::new(compile(source).unwrap());
+        pc.input.extend(input);
+        pc.run();
+        assert!(pc.halted, "program should halt");
+        pc.output.into()
+    }
+
+    #[test]
+    fn functions() {
+        let source = "
+            // Recursion, with locals in each frame.
+            fn fact(n) {
+                if n <= 1 { return 1; }
+                var rest = fact(n - 1);
+                return n * rest;
+            }
+            fn fib(n) {
+                if n < 2 { return n; }
+                return fib(n - 1) + fib(n - 2);
+            }
+            fn nothing() {}
+            fn main() {
+                output(fact(input()));
+                output(fib(15) + nothing());
+                output(-fact(3) * 2);
+            }
+        ";
+        assert_eq!(run(source, &[10]), [3628800, 610, -12]);
+    }
+
+    #[test]
+    fn arrays() {
+        let source = "
+            var sorted[8];
+            var count;
+
+            fn sort(values, n) {
+                var i = 1;
+                while i < n {
+                    var j = i;
+                    while j > 0 && values[j - 1] > values[j] {
+                        var t = values[j];
+                        values[j] = values[j - 1];
+                        values[j - 1] = t;
+                        j = j - 1;
+                    }
+                    i = i + 1;
+                }
+            }
+
+            fn main() {
+                var local[8];
+                count = input();
+                var i = 0;
+                while i < count {
+                    local[i] = input();
+                    i = i + 1;
+                }
+                sort(local, count);
+                i = 0;
+                while 1 {
+                    if i == count { break; }
+                    sorted[i] = local[i];
+                    i = i + 1;
+                }
+                sorted[7] = 'z';
+                i = 0;
+                while i < 8 {
+                    output(sorted[i]);
+                    i = i + 1;
+                }
+            }
+        ";
+        assert_eq!(
+            run(source, &[5, 3, -1, 4, 1, 5]),
+            [-1, 1, 3, 4, 5, 0, 0, 122]
+        );
+
+        // Local arrays are zeroed on every call.
+        let source = "
+            fn f(x) {
+                var a[3];
+                var r = a[0];
+                a[0] = x;
+                return r;
+            }
+
+            fn main() {
+                output(f(5));
+                output(f(7));
+            }
+        ";
+        assert_eq!(run(source, &[]), [0, 0]);
+
+        // Zeroing a large array doesn't take code per element.
+        let source = "
+            fn main() {
+                var a[100000];
+                a[99999] = 1;
+                output(a[99998] + a[99999]);
+            }
+        ";
+        assert!(compile(source).unwrap().len() < 200);
+        assert_eq!(run(source, &[]), [1]);
+    }
+
+    #[test]
+    fn control_flow() {
+        let source = "
+            var calls = 0;
+            fn check(x) { calls = calls + 1; return x; }
+            fn main() {
+                var i = 0;
+                var odd = 0;
+                while i < 10 {
+                    i = i + 1;
+                    var j = i;
+                    while j > 1 { j = j - 2; }
+                    if j == 0 { continue; }
+                    odd = odd + 1;
+                }
+                output(odd);
+                // Only the first operand is needed.
+                output(check(0) && check(1));
+                output(check(2) || check(0));
+                output(calls);
+                if !(1 != 1) && 3 >= 3 { output('\\n'); } else if 1 { output(0); }
+            }
+        ";
+        assert_eq!(run(source, &[]), [5, 0, 1, 2, 10]);
+    }
+
+    #[test]
+    fn errors() {
+        let error = |line, message: &str| {
+            Err(CompileError {
+                line,
+                message: message.to_string(),
+            })
+        };
+        assert_eq!(compile("fn f() {}"), error(1, "no main function"));
+        assert_eq!(
+            compile("fn main() {\n  x = 1;\n}"),
+            error(2, "unknown variable x")
+        );
+        assert_eq!(
+            compile("fn main() {\n  output(1, 2);\n}"),
+            error(2, "output takes 1 arguments")
+        );
+        assert_eq!(
+            compile("fn main() {\n  var a[2];\n  a = 1;\n}"),
+            error(3, "can't assign to array a")
+        );
+        assert_eq!(
+            compile("fn main() {\n  var x = 1 +;\n}"),
+            error(2, "expected a name, found ;")
+        );
+        assert_eq!(
+            compile("fn main() {\n  var a[100000000000];\n}"),
+            error(2, "array size should be at most 1048576")
+        );
+        assert_eq!(
+            compile("var a[0];\nfn main() {}"),
+            error(1, "array size should be positive")
+        );
+        assert_eq!(
+            compile("fn main() { break; }"),
+            error(1, "break or continue outside a loop")
+        );
+        assert_eq!(
+            compile("fn main() { output(1 / 2); }"),
+            error(1, "unexpected character '/'")
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! Reference programs every Intcode computer should run correctly, and
+//! parsing of the diagnostic programs of days 5 and 9.
+
+use crate::{
+    asm::{Instruction, Opcode},
+    opcodes::OpcodeTable,
+    IntcodeComputer, ParameterMode,
+};
+use std::{collections::VecDeque, fmt, sync::Arc};
+
+/// A program with its expected outputs for a given input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub name: &'static str,
+    pub program: &'static [i64],
+    pub input: &'static [i64],
+    pub expected: Vec<i64>,
+}
+
+const EQUAL_POSITION: &[i64] = &[3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+const LESS_POSITION: &[i64] = &[3, 9, 7, 9, 10, 9, 4, 9, 99, -1, 8];
+const EQUAL_IMMEDIATE: &[i64] = &[3, 3, 1108, -1, 8, 3, 4, 3, 99];
+const LESS_IMMEDIATE: &[i64] = &[3, 3, 1107, -1, 8, 3, 4, 3, 99];
+const JUMP_POSITION: &[i64] = &[3, 12, 6, 12, 15, 1, 13, 14, 13, 4, 13, 99, -1, 0, 1, 9];
+const JUMP_IMMEDIATE: &[i64] = &[3, 3, 1105, -1, 9, 1101, 0, 0, 12, 4, 12, 99, 1];
+const COMPARE_8: &[i64] = &[
+    3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
+    1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20, 1105,
+    1, 46, 98, 99,
+];
+const QUINE: &[i64] = &[
+    109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+];
+const SIXTEEN_DIGITS: &[i64] = &[1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+const LARGE_NUMBER: &[i64] = &[104, 1125899906842624, 99];
+
+/// The example programs of days 5 and 9.
+pub fn cases() -> Vec<Case> {
+    let case = |name, program, input, expected: &[i64]| Case {
+        name,
+        program,
+        input,
+        expected: expected.to_vec(),
+    };
+    vec![
+        case("day5 equal to 8, position mode", EQUAL_POSITION, &[8], &[1]),
+        case("day5 equal to 8, position mode", EQUAL_POSITION, &[7], &[0]),
+        case("day5 less than 8, position mode", LESS_POSITION, &[7], &[1]),
+        case("day5 less than 8, position mode", LESS_POSITION, &[8], &[0]),
+        case(
+            "day5 equal to 8, immediate mode",
+            EQUAL_IMMEDIATE,
+            &[8],
+            &[1],
+        ),
+        case(
+            "day5 equal to 8, immediate mode",
+            EQUAL_IMMEDIATE,
+            &[9],
+            &[0],
+        ),
+        case(
+            "day5 less than 8, immediate mode",
+            LESS_IMMEDIATE,
+            &[-3],
+            &[1],
+        ),
+        case(
+            "day5 less than 8, immediate mode",
+            LESS_IMMEDIATE,
+            &[8],
+            &[0],
+        ),
+        case("day5 jump, position mode", JUMP_POSITION, &[0], &[0]),
+        case("day5 jump, position mode", JUMP_POSITION, &[5], &[1]),
+        case("day5 jump, immediate mode", JUMP_IMMEDIATE, &[0], &[0]),
+        case("day5 jump, immediate mode", JUMP_IMMEDIATE, &[-5], &[1]),
+        case("day5 compare to 8", COMPARE_8, &[7], &[999]),
+        case("day5 compare to 8", COMPARE_8, &[8], &[1000]),
+        case("day5 compare to 8", COMPARE_8, &[9], &[1001]),
+        case("day9 quine", QUINE, &[], QUINE),
+        case(
+            "day9 16-digit number",
+            SIXTEEN_DIGITS,
+            &[],
+            &[1219070632396864],
+        ),
+        case("day9 large number", LARGE_NUMBER, &[], &[1125899906842624]),
+    ]
+}
+
+impl Case {
+    /// Run the case on a computer executing `opcodes`. Returns the outputs
+    /// if they differ from the expected ones.
+    pub fn check(&self, opcodes: &Arc<OpcodeTable>) -> Result<(), Vec<i64>> {
+        let mut pc = IntcodeComputer::with_opcodes(self.program.to_vec(), opcodes.clone());
+        pc.input.extend(self.input);
+        pc.run();
+        let output: Vec<i64> = pc.output.into();
+        if pc.halted && output == self.expected {
+            Ok(())
+        } else {
+            Err(output)
+        }
+    }
+}
+
+/// A problem reported by a diagnostic program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// The check producing the `index`-th output of the TEST program was
+    /// off by `value`. `instruction` is the instruction under test.
+    Check {
+        index: usize,
+        value: i64,
+        instruction: Option<Instruction>,
+    },
+    /// The BOOST program reported the opcode with parameter modes `code` as
+    /// malfunctioning.
+    Malfunction { code: i64 },
+    /// The program stopped without a diagnostic code.
+    NoCode,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Check {
+                index,
+                value,
+                instruction: Some(instruction),
+            } => write!(f, "check {index} is off by {value} after {instruction}"),
+            Self::Check { index, value, .. } => write!(f, "check {index} is off by {value}"),
+            Self::Malfunction { code } => {
+                let Some(opcode) = Opcode::from_code(code % 100) else {
+                    return write!(f, "malfunction {code}");
+                };
+                write!(f, "{} malfunctions", opcode.mnemonic())?;
+                let modes: Option<Vec<ParameterMode>> = (1..=opcode.arity())
+                    .map(|n| ParameterMode::try_new(code / 100, n))
+                    .collect();
+                match modes {
+                    Some(modes) if !modes.is_empty() => write!(f, " with modes {modes:?}"),
+                    _ => Ok(()),
+                }
+            }
+            Self::NoCode => write!(f, "no diagnostic code"),
+        }
+    }
+}
+
+/// Run the day 5 TEST program in `pc` with system ID `input`. Every output
+/// but the last is a check that should be zero; the instruction under test
+/// is the one executed two steps before the check is output. Returns the
+/// diagnostic code if every check passed.
+pub fn diagnose_test(mut pc: IntcodeComputer, input: i64) -> Result<i64, Vec<Failure>> {
+    pc.input.push_back(input);
+    let mut recent = VecDeque::from([None, None, None]);
+    let mut outputs = vec![];
+    while !pc.halted {
+        recent.pop_front();
+        recent.push_back(Instruction::decode(&pc.mem, pc.ip));
+        let output_len = pc.output.len();
+        if !pc.step() {
+            break;
+        }
+        if pc.output.len() > output_len {
+            outputs.push((*pc.output.back().unwrap(), recent[0].clone()));
+        }
+    }
+
+    let Some((code, _)) = outputs.pop().filter(|_| pc.halted) else {
+        return Err(vec![Failure::NoCode]);
+    };
+    let failures: Vec<Failure> = outputs
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (value, _))| *value != 0)
+        .map(|(index, (value, instruction))| Failure::Check {
+            index,
+            value,
+            instruction,
+        })
+        .collect();
+    if failures.is_empty() {
+        Ok(code)
+    } else {
+        Err(failures)
+    }
+}
+
+/// Run the day 9 BOOST program in `pc` with `input`. A working computer
+/// outputs just the keycode; otherwise the program outputs the malfunctioning
+/// opcodes.
+pub fn diagnose_boost(mut pc: IntcodeComputer, input: i64) -> Result<i64, Vec<Failure>> {
+    pc.input.push_back(input);
+    pc.run();
+    match pc.output.len() {
+        0 => Err(vec![Failure::NoCode]),
+        1 if pc.halted => Ok(pc.output[0]),
+        _ => Err(pc
+            .output
+            .iter()
+            .filter(|&&code| code != 0)
+            .map(|&code| Failure::Malfunction { code })
+            .collect()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{cases, diagnose_boost, diagnose_test, Failure};
+    use crate::IntcodeComputer;
+
+    #[test]
+    fn conformance() {
+        let opcodes = IntcodeComputer::new(vec![]).opcodes().clone();
+        for case in cases() {
+            assert_eq!(
+                case.check(&opcodes),
+                Ok(()),
+                "{} {:?}",
+                case.name,
+                case.input
+            );
+        }
+    }
+
+    #[test]
+    fn broken_opcode() {
+        let mut pc = IntcodeComputer::new(vec![]);
+        // LT that compares with <=
+        pc.register(7, 3, &[2], |pc, p| {
+            pc.store(p[2], (p[0] <= p[1]) as i64).into()
+        });
+        let failed: Vec<_> = cases()
+            .into_iter()
+            .filter(|case| case.check(pc.opcodes()).is_err())
+            .map(|case| case.name)
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                "day5 less than 8, position mode",
+                "day5 less than 8, immediate mode"
+            ]
+        );
+    }
+
+    #[test]
+    fn diagnose() {
+        // OUT 0; ADD 2,3,[20]; ADD [20],-5,[20]; OUT [20]; OUT 42; HALT
+        let program = vec![104, 0, 1101, 2, 3, 20, 1001, 20, -5, 20, 4, 20, 104, 42, 99];
+        let pc = IntcodeComputer::new(program.clone());
+        assert_eq!(diagnose_test(pc, 1), Ok(42));
+
+        let mut pc = IntcodeComputer::new(program);
+        pc.register(1, 3, &[2], |pc, p| pc.store(p[2], p[0] + p[1] + 1).into());
+        let failures = diagnose_test(pc, 1).unwrap_err();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(
+            failures[0].to_string(),
+            "check 1 is off by 2 after ADD 2, 3, [20]"
+        );
+
+        let pc = IntcodeComputer::new(vec![104, 203, 104, 0, 99]);
+        let failures = diagnose_boost(pc, 1).unwrap_err();
+        assert_eq!(failures, [Failure::Malfunction { code: 203 }]);
+        assert_eq!(
+            failures[0].to_string(),
+            "IN malfunctions with modes [Relative]"
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! A TCP server hosting an interactive ASCII Intcode program.
+//!
+//! Each connection plays on its own copy of the program. Lines from the
+//! client are sent as input; output is sent back as text, with values
+//! outside the ASCII range written as numbers on their own line. Lines
+//! starting with `!` are commands to the server:
+//!
+//! - `!save <name>` stores the current state in the checkpoint library,
+//!   which is shared by all connections.
+//! - `!load <name>` continues from a stored state.
+//! - `!list` lists the stored states.
+//! - `!quit` closes the connection.
+//!
+//! A session ends if the program runs `Server::max_steps` instructions
+//! without waiting for input.
+
+use crate::IntcodeComputer;
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+const HELP: &str = "Commands: !save <name>, !load <name>, !list, !quit\n";
+
+/// Default for `Server::max_steps`.
+const MAX_STEPS: u64 = 10_000_000;
+
+/// States saved by the `!save` command, by name.
+pub type Checkpoints = Arc<Mutex<BTreeMap<String, IntcodeComputer>>>;
+
+pub struct Server {
+    pub program: IntcodeComputer,
+    /// Directory to write a log of each session to, as `session<n>.log`.
+    pub log_dir: Option<PathBuf>,
+    pub checkpoints: Checkpoints,
+    /// Instructions the program may run between inputs.
+    pub max_steps: u64,
+    sessions: AtomicUsize,
+}
+
+/// Text for output values.
+fn render(values: impl Iterator<Item = i64>) -> String {
+    let mut text = String::new();
+    for value in values {
+        match u8::try_from(value) {
+            Ok(byte) if byte.is_ascii() => text.push(byte as char),
+            _ => text += &format!("{value}\n"),
+        }
+    }
+    text
+}
+
+/// Write `text` to the client and the log.
+fn send(writer: &mut TcpStream, log: &mut Option<File>, text: &str) -> io::Result<()> {
+    if let Some(log) = log {
+        log.write_all(text.as_bytes())?;
+    }
+    writer.write_all(text.as_bytes())
+}
+
+impl Server {
+    pub fn new(program: IntcodeComputer) -> Self {
+        Self {
+            program,
+            log_dir: None,
+            checkpoints: Checkpoints::default(),
+            max_steps: MAX_STEPS,
+            sessions: AtomicUsize::new(0),
+        }
+    }
+
+    /// Accept connections on `listener` forever, serving each on its own
+    /// thread.
+    pub fn serve(self: Arc<Self>, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let id = self.sessions.fetch_add(1, Ordering::SeqCst);
+            let server = Arc::clone(&self);
+            thread::spawn(move || {
+                if let Err(error) = server.session(stream, id) {
+                    eprintln!("session {id}: {error}");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Play a copy of the program with a client until the program halts,
+    /// faults or runs out of steps, or the client quits.
+    fn session(&self, stream: TcpStream, id: usize) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        let mut log = match &self.log_dir {
+            Some(dir) => Some(File::create(dir.join(format!("session{id}.log")))?),
+            None => None,
+        };
+        let mut pc = self.program.clone();
+        loop {
+            let mut steps = 0;
+            while !pc.halted && steps < self.max_steps && pc.step() {
+                steps += 1;
+            }
+            send(&mut writer, &mut log, &render(pc.output.drain(..)))?;
+            if let Some(fault) = pc.fault {
+                send(&mut writer, &mut log, &format!("Fault: {fault}\n"))?;
+            }
+            if steps == self.max_steps {
+                let message = format!("Stopped after {steps} steps without input\n");
+                send(&mut writer, &mut log, &message)?;
+            }
+            if pc.halted || pc.fault.is_some() || steps == self.max_steps {
+                return Ok(());
+            }
+
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            if let Some(log) = &mut log {
+                writeln!(log, "{line}")?;
+            }
+            let Some(command) = line.strip_prefix('!') else {
+                pc.input.extend(line.bytes().map(i64::from));
+                pc.input.push_back(b'\n' as i64);
+                continue;
+            };
+
+            let mut checkpoints = self.checkpoints.lock().unwrap();
+            let reply = match command.split_once(' ').unwrap_or((command, "")) {
+                ("save", name) if !name.is_empty() => {
+                    checkpoints.insert(name.to_string(), pc.clone());
+                    format!("Saved {name}.\n")
+                }
+                ("load", name) => match checkpoints.get(name) {
+                    Some(saved) => {
+                        pc = saved.clone();
+                        format!("Loaded {name}.\n")
+                    }
+                    None => format!("No checkpoint {name}.\n"),
+                },
+                ("list", "") => checkpoints.keys().map(|name| format!("{name}\n")).collect(),
+                ("quit", "") => return Ok(()),
+                _ => HELP.to_string(),
+            };
+            drop(checkpoints);
+            send(&mut writer, &mut log, &reply)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Server;
+    use crate::{asm::assemble, IntcodeComputer};
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::{TcpListener, TcpStream},
+        sync::Arc,
+        thread,
+    };
+
+    /// A local client of the server.
+    struct Client {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Client {
+        fn connect(listener: &TcpListener) -> Self {
+            let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            Self {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            }
+        }
+
+        fn read_line(&mut self) -> String {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            line
+        }
+
+        /// Send `line` and return the first line of the reply.
+        fn send(&mut self, line: &str) -> String {
+            writeln!(self.writer, "{line}").unwrap();
+            self.read_line()
+        }
+    }
+
+    #[test]
+    fn sessions() {
+        // Output a count starting at 1000 and increment it for every line
+        // of input, halting at 1003.
+        let assembly = assemble(
+            "
+            loop:  OUT [count]
+            read:  IN [char]
+                   EQ [char], 10, [flag]
+                   JZ [flag], read
+                   ADD [count], 1, [count]
+                   EQ [count], 1003, [flag]
+                   JZ [flag], loop
+                   HALT
+            count: DATA 1000
+            char:  DATA 0
+            flag:  DATA 0
+            ",
+        )
+        .unwrap();
+        let count = assembly.labels["count"];
+        let log_dir = std::env::temp_dir().join(format!("console-test-{}", std::process::id()));
+        std::fs::create_dir_all(&log_dir).unwrap();
+        let mut server = Server::new(IntcodeComputer::new(assembly.program));
+        server.log_dir = Some(log_dir.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut a = Client::connect(&listener);
+        let accept = listener.try_clone().unwrap();
+        let server = Arc::new(server);
+        let checkpoints = Arc::clone(&server.checkpoints);
+        thread::spawn(move || server.serve(accept));
+
+        assert_eq!(a.read_line(), "1000\n");
+        assert_eq!(a.send("go"), "1001\n");
+        assert_eq!(a.send("!save one"), "Saved one.\n");
+        assert_eq!(a.send("x"), "1002\n");
+
+        // Another connection starts over, and can continue from the state
+        // saved by the first.
+        let mut b = Client::connect(&listener);
+        assert_eq!(b.read_line(), "1000\n");
+        assert_eq!(b.send("!list"), "one\n");
+        assert_eq!(b.send("!load one"), "Loaded one.\n");
+        assert_eq!(b.send("y"), "1002\n");
+        // The program halts and the server closes the connection.
+        assert_eq!(b.send("z"), "");
+        assert_eq!(a.send("!load two"), "No checkpoint two.\n");
+        assert_eq!(a.send("!quit"), "");
+
+        assert_eq!(checkpoints.lock().unwrap()["one"].mem[count], 1001);
+        let log = std::fs::read_to_string(log_dir.join("session1.log")).unwrap();
+        assert_eq!(
+            log,
+            "1000\n!list\none\n!load one\nLoaded one.\ny\n1002\nz\n"
+        );
+        std::fs::remove_dir_all(log_dir).unwrap();
+    }
+    #[test]
+    fn step_limit() {
+        // OUT 1000; JZ 0,2
+        let mut server = Server::new(IntcodeComputer::new(vec![104, 1000, 1106, 0, 2]));
+        server.max_steps = 1000;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = Client::connect(&listener);
+        let accept = listener.try_clone().unwrap();
+        thread::spawn(move || Arc::new(server).serve(accept));
+
+        assert_eq!(client.read_line(), "1000\n");
+        assert_eq!(
+            client.read_line(),
+            "Stopped after 1000 steps without input\n"
+        );
+        assert_eq!(client.read_line(), "");
+    }
+}
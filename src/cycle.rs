@@ -0,0 +1,84 @@
+//! Detecting a computer that loops without doing I/O.
+//!
+//! The state of the computer is its instruction pointer, relative base and a
+//! hash of memory. The memory hash is the sum of a hash of every non-zero
+//! word and its address, so a store updates it in constant time. Cycles are
+//! found with Brent's algorithm, which only remembers a single earlier state.
+
+use crate::word::Word;
+#[cfg(not(feature = "std"))]
+#[allow(deprecated)]
+use core::hash::SipHasher as DefaultHasher;
+use core::hash::{Hash, Hasher};
+#[cfg(feature = "std")]
+use std::hash::DefaultHasher;
+
+type State = (usize, i64, u64);
+
+#[derive(Debug, Clone)]
+pub(crate) struct CycleDetector {
+    hash: u64,
+    /// The state compared against, and the number of steps since it.
+    saved: State,
+    steps: usize,
+    /// Steps until `saved` is replaced.
+    power: usize,
+}
+
+/// The contribution of `value` at `addr` to the memory hash.
+// Without std, the deprecated SipHasher is the hasher core provides.
+#[cfg_attr(not(feature = "std"), allow(deprecated))]
+fn word_hash<W: Word>(addr: usize, value: W) -> u64 {
+    // Zero words don't count, so growing memory doesn't change the hash.
+    if value == W::from(0) {
+        return 0;
+    }
+    let mut hasher = DefaultHasher::new();
+    (addr, value).hash(&mut hasher);
+    hasher.finish()
+}
+
+impl CycleDetector {
+    pub(crate) fn new<W: Word>(mem: &[W], ip: usize, rb: i64) -> Self {
+        let hash = mem.iter().enumerate().fold(0, |hash: u64, (addr, &value)| {
+            hash.wrapping_add(word_hash(addr, value))
+        });
+        Self {
+            hash,
+            saved: (ip, rb, hash),
+            steps: 0,
+            power: 1,
+        }
+    }
+
+    /// Record that the word at `addr` changed from `old` to `new`.
+    pub(crate) fn store<W: Word>(&mut self, addr: usize, old: W, new: W) {
+        self.hash = self
+            .hash
+            .wrapping_sub(word_hash(addr, old))
+            .wrapping_add(word_hash(addr, new));
+    }
+
+    /// Forget the states before the current one, after input or output.
+    pub(crate) fn reset(&mut self, ip: usize, rb: i64) {
+        self.saved = (ip, rb, self.hash);
+        self.steps = 0;
+        self.power = 1;
+    }
+
+    /// Record the state after a step. Returns the cycle length if the
+    /// computer has been in this state before.
+    pub(crate) fn check(&mut self, ip: usize, rb: i64) -> Option<usize> {
+        let state = (ip, rb, self.hash);
+        self.steps += 1;
+        if state == self.saved {
+            return Some(self.steps);
+        }
+        if self.steps == self.power {
+            self.saved = state;
+            self.steps = 0;
+            self.power *= 2;
+        }
+        None
+    }
+}
@@ -0,0 +1,575 @@
+//! A Debug Adapter Protocol server for stepping Intcode programs in an IDE.
+//!
+//! The `launch` request loads `program` with `load::read_file`. It
+//! optionally takes the assembly `source` the program was assembled from,
+//! `input` values, `stopOnEntry`, and `maxSteps`, the number of instructions
+//! a `continue` runs before the program pauses so the client can interrupt
+//! a program that loops. Breakpoints can be set on lines
+//! of the source, or as function or instruction breakpoints naming an
+//! address or a label. The variables show the registers, memory, and the
+//! input and output queues. In the debug console, `input <values>` queues
+//! input and an address or label evaluates to the word stored there.
+
+use crate::{
+    asm::{assemble, Assembly, Instruction},
+    load, IntcodeComputer,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeSet,
+    io::{self, BufRead, Write},
+    panic::{self, AssertUnwindSafe},
+};
+
+const REGISTERS: i64 = 1;
+const MEMORY: i64 = 2;
+const INPUT: i64 = 3;
+const OUTPUT: i64 = 4;
+
+/// Default for the `maxSteps` launch argument.
+const MAX_STEPS: u64 = 10_000_000;
+
+/// A debugging session on a single program.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pc: Option<IntcodeComputer>,
+    /// Path and assembly of the program's source, if given.
+    source: Option<(String, Assembly)>,
+    stop_on_entry: bool,
+    /// Instructions a `continue` runs before pausing.
+    max_steps: u64,
+    line_breakpoints: BTreeSet<usize>,
+    function_breakpoints: BTreeSet<usize>,
+    instruction_breakpoints: BTreeSet<usize>,
+    /// Sequence number of the last message sent.
+    seq: i64,
+    done: bool,
+}
+
+/// Read a message framed by a Content-Length header. Returns None at the
+/// end of the stream.
+fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "missing Content-Length",
+        ));
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message<W: Write>(writer: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve requests from `reader` until the client disconnects or closes
+    /// the stream.
+    pub fn serve<R: BufRead, W: Write>(&mut self, mut reader: R, mut writer: W) -> io::Result<()> {
+        while !self.done {
+            let Some(request) = read_message(&mut reader)? else {
+                break;
+            };
+            for message in self.handle(&request) {
+                write_message(&mut writer, &message)?;
+            }
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn next_seq(&mut self) -> i64 {
+        self.seq += 1;
+        self.seq
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> Value {
+        let mut message = json!({"seq": self.next_seq(), "type": "event", "event": event});
+        if !body.is_null() {
+            message["body"] = body;
+        }
+        message
+    }
+
+    /// The response to `request` and the events that follow it.
+    pub fn handle(&mut self, request: &Value) -> Vec<Value> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+        let mut events = vec![];
+        let body = self.execute(command, args, &mut events);
+        let mut response = json!({
+            "seq": self.next_seq(),
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": body.is_ok(),
+        });
+        match body {
+            Ok(Value::Null) => {}
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = message.into(),
+        }
+
+        let mut messages = vec![response];
+        for (event, body) in events {
+            let event = self.event(event, body);
+            messages.push(event);
+        }
+        messages
+    }
+
+    /// Execute a request. Events to send after the response are pushed to
+    /// `events`.
+    fn execute(
+        &mut self,
+        command: &str,
+        args: &Value,
+        events: &mut Vec<(&'static str, Value)>,
+    ) -> Result<Value, String> {
+        match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsFunctionBreakpoints": true,
+                "supportsInstructionBreakpoints": true,
+            })),
+            "launch" => {
+                self.launch(args)?;
+                events.push(("initialized", Value::Null));
+                Ok(Value::Null)
+            }
+            "setBreakpoints" => self.set_line_breakpoints(args),
+            "setFunctionBreakpoints" => {
+                let (breakpoints, addrs) = self.resolve_breakpoints(args, "name");
+                self.function_breakpoints = addrs;
+                Ok(breakpoints)
+            }
+            "setInstructionBreakpoints" => {
+                let (breakpoints, addrs) = self.resolve_breakpoints(args, "instructionReference");
+                self.instruction_breakpoints = addrs;
+                Ok(breakpoints)
+            }
+            "configurationDone" => {
+                self.pc()?;
+                if self.stop_on_entry {
+                    events.push(stopped("entry", None));
+                } else {
+                    events.extend(self.resume(false)?);
+                }
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({"threads": [{"id": 1, "name": "main"}]})),
+            "stackTrace" => self.stack_trace(),
+            "scopes" => self.scopes(),
+            "variables" => self.variables(args),
+            "continue" => {
+                events.extend(self.resume(false)?);
+                Ok(json!({"allThreadsContinued": true}))
+            }
+            "next" | "stepIn" => {
+                events.extend(self.resume(true)?);
+                Ok(Value::Null)
+            }
+            "pause" => {
+                self.pc()?;
+                events.push(stopped("pause", None));
+                Ok(Value::Null)
+            }
+            "evaluate" => self.evaluate(args),
+            "disconnect" => {
+                self.done = true;
+                Ok(Value::Null)
+            }
+            _ => Err(format!("Unsupported request {command}")),
+        }
+    }
+
+    fn pc(&mut self) -> Result<&mut IntcodeComputer, String> {
+        self.pc
+            .as_mut()
+            .ok_or_else(|| "No program launched".to_string())
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<(), String> {
+        let program = args["program"].as_str().ok_or("Missing program")?;
+        if !std::path::Path::new(program).is_file() {
+            return Err(format!("Cannot read {program}"));
+        }
+        if let Some(path) = args["source"].as_str() {
+            let source =
+                std::fs::read_to_string(path).map_err(|error| format!("{path}: {error}"))?;
+            let assembly = assemble(&source).map_err(|error| format!("{path}: {error}"))?;
+            self.source = Some((path.to_string(), assembly));
+        }
+        let program = load::read_file(program).map_err(|error| format!("{program}: {error}"))?;
+        let mut pc = IntcodeComputer::new(program);
+        if let Some(input) = args["input"].as_array() {
+            pc.input.extend(input.iter().filter_map(Value::as_i64));
+        }
+        self.pc = Some(pc);
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        self.max_steps = args["maxSteps"].as_u64().unwrap_or(MAX_STEPS);
+        Ok(())
+    }
+
+    /// The address named by `reference`, a number or a label.
+    fn resolve(&self, reference: &str) -> Option<usize> {
+        let reference = reference.trim();
+        reference.parse().ok().or_else(|| {
+            let (_, assembly) = self.source.as_ref()?;
+            assembly.labels.get(reference).copied()
+        })
+    }
+
+    fn resolve_breakpoints(&self, args: &Value, key: &str) -> (Value, BTreeSet<usize>) {
+        let mut addrs = BTreeSet::new();
+        let mut breakpoints = vec![];
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let reference = breakpoint[key].as_str().unwrap_or_default();
+            let offset = breakpoint["offset"].as_i64().unwrap_or(0);
+            let addr = self
+                .resolve(reference)
+                .and_then(|addr| usize::try_from(addr as i64 + offset).ok());
+            breakpoints.push(match addr {
+                Some(addr) => {
+                    addrs.insert(addr);
+                    json!({"verified": true, "instructionReference": addr.to_string()})
+                }
+                None => {
+                    json!({"verified": false, "message": format!("Unknown address {reference}")})
+                }
+            });
+        }
+        (json!({ "breakpoints": breakpoints }), addrs)
+    }
+
+    fn set_line_breakpoints(&mut self, args: &Value) -> Result<Value, String> {
+        let path = args["source"]["path"].as_str();
+        let assembly = match &self.source {
+            Some((source, assembly)) if Some(source.as_str()) == path => Some(assembly),
+            _ => None,
+        };
+        let mut addrs = BTreeSet::new();
+        let mut breakpoints = vec![];
+        for breakpoint in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = breakpoint["line"].as_u64().unwrap_or(0) as usize;
+            // A line without code breaks at the next line with code.
+            let found = assembly.and_then(|assembly| assembly.lines.range(line..).next());
+            breakpoints.push(match found {
+                Some((&line, &addr)) => {
+                    addrs.insert(addr);
+                    json!({
+                        "verified": true,
+                        "line": line,
+                        "instructionReference": addr.to_string(),
+                    })
+                }
+                None if assembly.is_none() => {
+                    json!({"verified": false, "message": "Not the launched source"})
+                }
+                None => json!({"verified": false, "message": "No code at or after this line"}),
+            });
+        }
+        self.line_breakpoints = addrs;
+        Ok(json!({ "breakpoints": breakpoints }))
+    }
+
+    /// Step once or continue, and return the events describing why the
+    /// program stopped.
+    fn resume(&mut self, single: bool) -> Result<Vec<(&'static str, Value)>, String> {
+        let breakpoints: BTreeSet<usize> = (self.line_breakpoints.iter())
+            .chain(&self.function_breakpoints)
+            .chain(&self.instruction_breakpoints)
+            .copied()
+            .collect();
+        let max_steps = self.max_steps;
+        let pc = self.pc()?;
+        let mut steps = 0;
+        let event = loop {
+            if pc.halted {
+                return Ok(vec![
+                    ("exited", json!({"exitCode": 0})),
+                    ("terminated", json!({})),
+                ]);
+            }
+            if steps > 0 && breakpoints.contains(&pc.ip) {
+                break stopped("breakpoint", None);
+            }
+            if steps == max_steps {
+                break stopped("pause", Some(format!("Paused after {steps} steps")));
+            }
+            match panic::catch_unwind(AssertUnwindSafe(|| pc.step())) {
+                Ok(true) => {}
+                Ok(false) => {
+                    break match pc.fault {
+                        Some(fault) => stopped("exception", Some(fault.to_string())),
+                        None => stopped("pause", Some("Waiting on input".to_string())),
+                    }
+                }
+                Err(payload) => {
+                    let message = match payload.downcast::<String>() {
+                        Ok(message) => *message,
+                        Err(payload) => match payload.downcast::<&str>() {
+                            Ok(message) => message.to_string(),
+                            Err(_) => "panic".to_string(),
+                        },
+                    };
+                    break stopped("exception", Some(message));
+                }
+            }
+            if single && !pc.halted {
+                break stopped("step", None);
+            }
+            steps += 1;
+        };
+        Ok(vec![event])
+    }
+
+    /// The source line of the instruction at `addr`.
+    fn line(&self, addr: usize) -> Option<(&str, usize)> {
+        let (path, assembly) = self.source.as_ref()?;
+        let (&line, _) = assembly.lines.iter().find(|&(_, &a)| a == addr)?;
+        Some((path, line))
+    }
+
+    fn stack_trace(&mut self) -> Result<Value, String> {
+        let pc = self.pc()?;
+        let ip = pc.ip;
+        let instruction = match Instruction::decode(&pc.mem, ip) {
+            Some(instruction) => instruction.to_string(),
+            None => format!("DATA {}", pc.mem.get(ip).copied().unwrap_or(0)),
+        };
+        let label = self
+            .source
+            .as_ref()
+            .and_then(|(_, assembly)| assembly.label_at(ip));
+        let mut frame = json!({
+            "id": 1,
+            "name": format!("{}: {instruction}", label.map_or(ip.to_string(), str::to_string)),
+            "line": 0,
+            "column": 0,
+            "instructionPointerReference": ip.to_string(),
+        });
+        if let Some((path, line)) = self.line(ip) {
+            frame["source"] = json!({ "path": path });
+            frame["line"] = line.into();
+            frame["column"] = 1.into();
+        }
+        Ok(json!({"stackFrames": [frame], "totalFrames": 1}))
+    }
+
+    fn scopes(&mut self) -> Result<Value, String> {
+        let pc = self.pc()?;
+        let scope = |name: &str, reference: i64, count: usize| {
+            json!({
+                "name": name,
+                "variablesReference": reference,
+                "indexedVariables": count,
+                "expensive": false,
+            })
+        };
+        Ok(json!({"scopes": [
+            scope("Registers", REGISTERS, 2),
+            scope("Memory", MEMORY, pc.mem.len()),
+            scope("Input", INPUT, pc.input.len()),
+            scope("Output", OUTPUT, pc.output.len()),
+        ]}))
+    }
+
+    fn variables(&mut self, args: &Value) -> Result<Value, String> {
+        let start = args["start"].as_u64().unwrap_or(0) as usize;
+        // A count of 0 or none means all variables.
+        let count = match args["count"].as_u64() {
+            None | Some(0) => usize::MAX,
+            Some(count) => count as usize,
+        };
+        let labels = self.source.as_ref().map(|(_, assembly)| assembly);
+        let pc = self.pc.as_ref().ok_or("No program launched")?;
+        let values: Vec<(String, i64)> = match args["variablesReference"].as_i64() {
+            Some(REGISTERS) => vec![("ip".to_string(), pc.ip as i64), ("rb".to_string(), pc.rb)],
+            Some(MEMORY) => pc
+                .mem
+                .iter()
+                .enumerate()
+                .map(
+                    |(addr, &value)| match labels.and_then(|a| a.label_at(addr)) {
+                        Some(label) => (format!("{addr} ({label})"), value),
+                        None => (addr.to_string(), value),
+                    },
+                )
+                .collect(),
+            Some(INPUT) => pc.input.iter().map(|&v| (String::new(), v)).collect(),
+            Some(OUTPUT) => pc.output.iter().map(|&v| (String::new(), v)).collect(),
+            _ => return Err("Unknown variables reference".to_string()),
+        };
+        let variables: Vec<Value> = values
+            .into_iter()
+            .enumerate()
+            .skip(start)
+            .take(count)
+            .map(|(i, (name, value))| {
+                let name = if name.is_empty() { i.to_string() } else { name };
+                json!({"name": name, "value": value.to_string(), "variablesReference": 0})
+            })
+            .collect();
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn evaluate(&mut self, args: &Value) -> Result<Value, String> {
+        let expression = args["expression"].as_str().unwrap_or_default().trim();
+        let (command, values) = expression
+            .split_once(char::is_whitespace)
+            .unwrap_or((expression, ""));
+        if command == "input" {
+            let values: Vec<i64> = values
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| "Expected input values".to_string())?;
+            let pc = self.pc()?;
+            pc.input.extend(values);
+            let result = format!("{} queued", pc.input.len());
+            return Ok(json!({"result": result, "variablesReference": 0}));
+        }
+        let addr = self
+            .resolve(expression)
+            .ok_or_else(|| format!("Unknown address {expression}"))?;
+        let value = self.pc()?.mem.get(addr).copied().unwrap_or(0);
+        Ok(json!({"result": value.to_string(), "variablesReference": 0}))
+    }
+}
+
+fn stopped(reason: &str, description: Option<String>) -> (&'static str, Value) {
+    let mut body = json!({"reason": reason, "threadId": 1, "allThreadsStopped": true});
+    if let Some(description) = description {
+        body["description"] = description.into();
+    }
+    ("stopped", body)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_message, write_message, Session};
+    use serde_json::{json, Value};
+    use std::io::BufReader;
+
+    /// Replay the requests of a recorded session and compare the replies
+    /// with the recorded ones.
+    fn replay(fixture: &str) {
+        let text = std::fs::read_to_string(fixture).unwrap();
+        let messages: Vec<Value> = serde_json::from_str(&text).unwrap();
+        let mut input = vec![];
+        for message in messages.iter().filter(|m| m["type"] == "request") {
+            write_message(&mut input, message).unwrap();
+        }
+        let mut output = vec![];
+        Session::new().serve(&input[..], &mut output).unwrap();
+
+        let mut reader = BufReader::new(&output[..]);
+        let expected = messages.iter().filter(|m| m["type"] != "request");
+        for (i, expected) in expected.enumerate() {
+            let actual = read_message(&mut reader).unwrap();
+            assert_eq!(actual.as_ref(), Some(expected), "message {i} of {fixture}");
+        }
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn session() {
+        replay("data/dap/session.json");
+    }
+
+    #[test]
+    fn errors() {
+        replay("data/dap/errors.json");
+    }
+
+    #[test]
+    fn bad_programs() {
+        let dir = std::env::temp_dir().join(format!("dap-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let launch = |session: &mut Session, program: &str, args: Value| {
+            let path = dir.join(program.split(',').next().unwrap());
+            std::fs::write(&path, program).unwrap();
+            let mut args = args;
+            args["program"] = path.to_str().unwrap().into();
+            let request =
+                json!({"seq": 1, "type": "request", "command": "launch", "arguments": args});
+            session.handle(&request).remove(0)
+        };
+        let request = |command: &str| json!({"seq": 2, "type": "request", "command": command});
+
+        let response = launch(&mut Session::new(), "1,hello,99", json!({}));
+        assert_eq!(response["success"], false);
+        assert!(response["message"]
+            .as_str()
+            .unwrap()
+            .ends_with("value 1 \"hello\" isn't an integer"));
+
+        let mut session = Session::new();
+        launch(&mut session, "42", json!({}));
+        let messages = session.handle(&request("configurationDone"));
+        assert_eq!(messages[1]["body"]["reason"], "exception");
+        assert_eq!(
+            messages[1]["body"]["description"],
+            "Unknown opcode in instruction 42"
+        );
+
+        // JZ 0,0 runs until the step limit.
+        let mut session = Session::new();
+        launch(&mut session, "1106,0,0", json!({"maxSteps": 1000}));
+        let messages = session.handle(&request("configurationDone"));
+        assert_eq!(messages[1]["body"]["reason"], "pause");
+        assert_eq!(
+            messages[1]["body"]["description"],
+            "Paused after 1000 steps"
+        );
+        let messages = session.handle(&request("continue"));
+        assert_eq!(messages[1]["body"]["reason"], "pause");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn variables_and_evaluate() {
+        let path = std::env::temp_dir().join(format!("dap-eval-{}", std::process::id()));
+        std::fs::write(&path, "3,0,99").unwrap();
+        let mut session = Session::new();
+        let request = |command: &str, args: Value| json!({"seq": 1, "type": "request", "command": command, "arguments": args});
+        let args = json!({"program": path.to_str().unwrap(), "stopOnEntry": true});
+        session.handle(&request("launch", args));
+        std::fs::remove_file(path).unwrap();
+
+        // A count of 0 means all variables.
+        let args = json!({"variablesReference": super::MEMORY, "count": 0});
+        let response = session.handle(&request("variables", args)).remove(0);
+        assert_eq!(response["body"]["variables"].as_array().unwrap().len(), 3);
+
+        let args = json!({"expression": "input 1 2"});
+        let response = session.handle(&request("evaluate", args)).remove(0);
+        assert_eq!(response["body"]["result"], "2 queued");
+        let args = json!({"expression": "inputs"});
+        let response = session.handle(&request("evaluate", args)).remove(0);
+        assert_eq!(response["message"], "Unknown address inputs");
+    }
+}
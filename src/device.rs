@@ -0,0 +1,277 @@
+//! Peripherals attached to the I/O of an IntcodeComputer.
+//!
+//! `IntcodeComputer::run_with` runs the computer, hands every output value
+//! to every device, and asks the devices in order for a value whenever the
+//! computer waits on input. Each device parses the outputs it cares about,
+//! so devices with different protocols can share a computer.
+
+use crate::IntcodeComputer;
+use std::collections::{HashMap, VecDeque};
+
+pub trait Device {
+    /// Receive a value output by the computer.
+    fn output(&mut self, _value: i64) {}
+
+    /// Provide the next input, or None if this device has none.
+    fn input(&mut self) -> Option<i64> {
+        None
+    }
+}
+
+impl IntcodeComputer {
+    /// Run the computer with `devices` attached until it halts, faults, or
+    /// waits on input that no device provides.
+    pub fn run_with(&mut self, devices: &mut [&mut dyn Device]) {
+        loop {
+            self.run();
+            for value in self.output.drain(..) {
+                for device in devices.iter_mut() {
+                    device.output(value);
+                }
+            }
+            if self.halted || self.fault.is_some() {
+                return;
+            }
+            match devices.iter_mut().find_map(|device| device.input()) {
+                Some(value) => self.input.push_back(value),
+                None => return,
+            }
+        }
+    }
+}
+
+/// A screen drawn by output triples `x, y, tile`. The triple `-1, 0, score`
+/// sets the score instead.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Framebuffer {
+    pub tiles: HashMap<(i64, i64), i64>,
+    pub score: Option<i64>,
+    pending: Vec<i64>,
+}
+
+impl Framebuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Positions showing `tile`.
+    pub fn find(&self, tile: i64) -> impl Iterator<Item = (i64, i64)> + '_ {
+        self.tiles
+            .iter()
+            .filter(move |&(_, &t)| t == tile)
+            .map(|(&pos, _)| pos)
+    }
+
+    /// Draw the screen with `palette[tile]` for each tile. Tiles outside the
+    /// palette are drawn as `?`.
+    pub fn render(&self, palette: &[char]) -> String {
+        if self.tiles.is_empty() {
+            return String::new();
+        }
+        let x_min = self.tiles.keys().map(|p| p.0).min().unwrap();
+        let x_max = self.tiles.keys().map(|p| p.0).max().unwrap();
+        let y_min = self.tiles.keys().map(|p| p.1).min().unwrap();
+        let y_max = self.tiles.keys().map(|p| p.1).max().unwrap();
+        let mut result = String::new();
+        for y in y_min..=y_max {
+            for x in x_min..=x_max {
+                let tile = self.tiles.get(&(x, y)).copied().unwrap_or(0);
+                let glyph = usize::try_from(tile)
+                    .ok()
+                    .and_then(|tile| palette.get(tile));
+                result.push(glyph.copied().unwrap_or('?'));
+            }
+            result.push('\n');
+        }
+        result
+    }
+}
+
+impl Device for Framebuffer {
+    fn output(&mut self, value: i64) {
+        self.pending.push(value);
+        if let [x, y, tile] = self.pending[..] {
+            if (x, y) == (-1, 0) {
+                self.score = Some(tile);
+            } else {
+                self.tiles.insert((x, y), tile);
+            }
+            self.pending.clear();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tilt {
+    Left = -1,
+    Neutral = 0,
+    Right = 1,
+}
+
+/// An input device that reads its position from a function, such as a
+/// keyboard handler or an autopilot.
+pub struct Joystick<F> {
+    read: F,
+}
+
+impl<F: FnMut() -> Tilt> Joystick<F> {
+    pub fn new(read: F) -> Self {
+        Self { read }
+    }
+}
+
+impl<F: FnMut() -> Tilt> Device for Joystick<F> {
+    fn input(&mut self) -> Option<i64> {
+        Some((self.read)() as i64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Move {
+    North = 1,
+    South = 2,
+    West = 3,
+    East = 4,
+}
+
+impl Move {
+    pub const ALL: [Move; 4] = [Move::North, Move::South, Move::West, Move::East];
+
+    pub fn reverse(self) -> Self {
+        match self {
+            Self::North => Self::South,
+            Self::South => Self::North,
+            Self::West => Self::East,
+            Self::East => Self::West,
+        }
+    }
+
+    pub fn delta(self) -> (i64, i64) {
+        match self {
+            Self::North => (0, 1),
+            Self::South => (0, -1),
+            Self::West => (-1, 0),
+            Self::East => (1, 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Wall,
+    Moved,
+    /// Moved onto the target.
+    Found,
+}
+
+/// A droid that takes movement commands and reports whether it moved. It
+/// tracks its position and the statuses it has seen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Droid {
+    pub pos: (i64, i64),
+    pub map: HashMap<(i64, i64), Status>,
+    /// Moves to send to the computer.
+    pub moves: VecDeque<Move>,
+    /// Moves sent whose status hasn't arrived yet.
+    sent: VecDeque<Move>,
+    pub last: Option<Status>,
+    /// Outputs that weren't the status of a sent move, in order.
+    pub invalid: Vec<i64>,
+}
+
+impl Droid {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make a single move on `pc` and return its status.
+    pub fn go(&mut self, pc: &mut IntcodeComputer, dir: Move) -> Status {
+        self.moves.push_back(dir);
+        pc.run_with(&mut [self]);
+        self.last.expect("Droid should report a status")
+    }
+}
+
+impl Device for Droid {
+    fn input(&mut self) -> Option<i64> {
+        let dir = self.moves.pop_front()?;
+        self.sent.push_back(dir);
+        Some(dir as i64)
+    }
+
+    fn output(&mut self, value: i64) {
+        let status = match value {
+            0 => Status::Wall,
+            1 => Status::Moved,
+            2 => Status::Found,
+            _ => {
+                self.invalid.push(value);
+                return;
+            }
+        };
+        let Some(dir) = self.sent.pop_front() else {
+            self.invalid.push(value);
+            return;
+        };
+        let (dx, dy) = dir.delta();
+        let target = (self.pos.0 + dx, self.pos.1 + dy);
+        self.map.insert(target, status);
+        if status != Status::Wall {
+            self.pos = target;
+        }
+        self.last = Some(status);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Droid, Framebuffer, Joystick, Move, Status, Tilt};
+    use crate::IntcodeComputer;
+
+    #[test]
+    fn framebuffer() {
+        // Draw a wall at (0, 0) and a ball at (1, 1), set the score, then
+        // output the joystick position.
+        let mut pc = IntcodeComputer::new(vec![
+            104, 0, 104, 0, 104, 1, 104, 1, 104, 1, 104, 4, 104, -1, 104, 0, 104, 7, 3, 100, 4,
+            100, 99,
+        ]);
+        let mut screen = Framebuffer::new();
+        let mut joystick = Joystick::new(|| Tilt::Right);
+        pc.run_with(&mut [&mut screen, &mut joystick]);
+        assert!(pc.halted);
+        assert_eq!(screen.score, Some(7));
+        assert_eq!(screen.find(4).collect::<Vec<_>>(), [(1, 1)]);
+        assert_eq!(screen.render(&[' ', '#', '.', '-', 'o']), "# \n o\n");
+        assert_eq!(screen.render(&[' ', '#']), "# \n ?\n");
+        // The joystick output isn't a complete triple.
+        assert_eq!(screen.tiles.len(), 2);
+    }
+
+    #[test]
+    fn droid() {
+        // Report a wall for north and moved otherwise, forever.
+        // IN [20]; EQ [20],1,[21]; JNZ [21],14; OUT 1; JZ 0,0; OUT 0; JZ 0,0
+        let mut pc = IntcodeComputer::new(vec![
+            3, 20, 1008, 20, 1, 21, 1005, 21, 14, 104, 1, 1106, 0, 0, 104, 0, 1106, 0, 0,
+        ]);
+        let mut droid = Droid::new();
+        assert_eq!(droid.go(&mut pc, Move::North), Status::Wall);
+        assert_eq!(droid.pos, (0, 0));
+        assert_eq!(droid.go(&mut pc, Move::East), Status::Moved);
+        assert_eq!(droid.go(&mut pc, Move::South), Status::Moved);
+        assert_eq!(droid.pos, (1, -1));
+        assert_eq!(droid.map[&(0, 1)], Status::Wall);
+    }
+
+    #[test]
+    fn droid_invalid_status() {
+        // Output 7, then a status without a move, then report moved.
+        // OUT 7; OUT 1; IN [20]; OUT 1; HALT
+        let mut pc = IntcodeComputer::new(vec![104, 7, 104, 1, 3, 20, 104, 1, 99]);
+        let mut droid = Droid::new();
+        assert_eq!(droid.go(&mut pc, Move::West), Status::Moved);
+        assert_eq!(droid.pos, (-1, 0));
+        assert_eq!(droid.invalid, [7, 1]);
+    }
+}
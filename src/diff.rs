@@ -0,0 +1,300 @@
+//! Structural diffs of Intcode programs.
+//!
+//! Both programs are split into their reachable instructions, as found by the
+//! linter, and data words, and the two sequences are aligned by instruction
+//! rather than by address. Aligned instructions are compared operand by
+//! operand. An operand that refers to an aligned word in both programs isn't
+//! a change, even if the word moved, so code and data shifted by an inserted
+//! block only show up as the insertion.
+
+use crate::{lint::reachable, ParameterMode};
+use std::{collections::HashMap, fmt, ops::Range};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// An operand or data word at `a` in the first program and `b` in the
+    /// second has a different value.
+    Constant {
+        a: usize,
+        b: usize,
+        old: i64,
+        new: i64,
+    },
+    /// Words only in the first program.
+    Deleted { a: Range<usize> },
+    /// Words only in the second program.
+    Inserted { b: Range<usize> },
+    /// A run of data at a different address. Single words, usually
+    /// variables between code, aren't reported.
+    Shifted { a: Range<usize>, b: Range<usize> },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Constant { a, b, old, new } => write!(f, "{a} -> {b}: {old} -> {new}"),
+            Self::Deleted { a } => write!(f, "deleted {}..{}", a.start, a.end),
+            Self::Inserted { b } => write!(f, "inserted {}..{}", b.start, b.end),
+            Self::Shifted { a, b } => {
+                write!(f, "moved {}..{} to {}..{}", a.start, a.end, b.start, b.end)
+            }
+        }
+    }
+}
+
+/// What a unit is compared by when aligning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    /// An instruction, by its opcode and modes.
+    Code(i64),
+    Data(i64),
+}
+
+/// An instruction or data word.
+#[derive(Debug, Clone, Copy)]
+struct Unit {
+    addr: usize,
+    len: usize,
+    key: Key,
+}
+
+impl Unit {
+    fn words(&self) -> Range<usize> {
+        self.addr..self.addr + self.len
+    }
+}
+
+fn units(program: &[i64]) -> Vec<Unit> {
+    let code = reachable(program);
+    let mut units = vec![];
+    let mut addr = 0;
+    while addr < program.len() {
+        let unit = match code.get(&addr) {
+            Some(instruction) if addr + instruction.size() <= program.len() => Unit {
+                addr,
+                len: instruction.size(),
+                key: Key::Code(program[addr]),
+            },
+            _ => Unit {
+                addr,
+                len: 1,
+                key: Key::Data(program[addr]),
+            },
+        };
+        units.push(unit);
+        addr += unit.len;
+    }
+    units
+}
+
+/// Indices of a longest common subsequence of `a` and `b` by key.
+fn align(a: &[Unit], b: &[Unit]) -> Vec<(usize, usize)> {
+    // Trim the common prefix and suffix, which is most of the programs.
+    let prefix = a.iter().zip(b).take_while(|(a, b)| a.key == b.key).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(a, b)| a.key == b.key)
+        .count();
+    let (middle_a, middle_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    // lengths[i][j] is the length of an LCS of middle_a[i..] and middle_b[j..].
+    let (n, m) = (middle_a.len(), middle_b.len());
+    let mut lengths = vec![0u32; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * (m + 1) + j] = if middle_a[i].key == middle_b[j].key {
+                lengths[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lengths[(i + 1) * (m + 1) + j].max(lengths[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let mut pairs: Vec<_> = (0..prefix).map(|i| (i, i)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if middle_a[i].key == middle_b[j].key {
+            pairs.push((prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * (m + 1) + j] >= lengths[i * (m + 1) + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs.extend((0..suffix).map(|k| (a.len() - suffix + k, b.len() - suffix + k)));
+    pairs
+}
+
+/// Compare the words of aligned units, reporting values that differ and
+/// aren't references to aligned words.
+fn compare(
+    a: &[i64],
+    b: &[i64],
+    (unit_a, unit_b): (Unit, Unit),
+    moved: &HashMap<usize, usize>,
+    changes: &mut Vec<Change>,
+) {
+    for k in 0..unit_a.len {
+        let (addr_a, addr_b) = (unit_a.addr + k, unit_b.addr + k);
+        let (old, new) = (a[addr_a], b[addr_b]);
+        if old == new {
+            continue;
+        }
+        let relative = matches!(unit_a.key, Key::Code(word) if k > 0
+            && ParameterMode::try_new(word / 100, k) == Some(ParameterMode::Relative));
+        let reference = usize::try_from(old)
+            .ok()
+            .and_then(|old| moved.get(&old))
+            .is_some_and(|&moved| moved as i64 == new);
+        if relative || !reference {
+            changes.push(Change::Constant {
+                a: addr_a,
+                b: addr_b,
+                old,
+                new,
+            });
+        }
+    }
+}
+
+/// The changes from program `a` to program `b`, in program order.
+pub fn diff(a: &[i64], b: &[i64]) -> Vec<Change> {
+    let (units_a, units_b) = (units(a), units(b));
+    let pairs = align(&units_a, &units_b);
+
+    let mut moved = HashMap::new();
+    for &(i, j) in &pairs {
+        moved.extend(units_a[i].words().zip(units_b[j].words()));
+    }
+
+    let mut changes = vec![];
+    let (mut next_a, mut next_b) = (0, 0);
+    let mut data_run: Option<(Range<usize>, Range<usize>)> = None;
+    let end = (units_a.len(), units_b.len());
+    for (i, j) in pairs.iter().copied().chain([end]) {
+        if (i, j) != (next_a, next_b) || (i, j) == end {
+            flush(&mut data_run, &mut changes);
+        }
+
+        // Units between aligned ones were replaced, or only in one program.
+        let (gap_a, gap_b) = (&units_a[next_a..i], &units_b[next_b..j]);
+        let same_shape = gap_a.len() == gap_b.len()
+            && gap_a.iter().zip(gap_b).all(|(a, b)| match (a.key, b.key) {
+                (Key::Data(_), Key::Data(_)) => true,
+                (Key::Code(a), Key::Code(b)) => a == b,
+                _ => false,
+            });
+        if same_shape {
+            for (&unit_a, &unit_b) in gap_a.iter().zip(gap_b) {
+                compare(a, b, (unit_a, unit_b), &moved, &mut changes);
+            }
+        } else {
+            let words = |gap: &[Unit]| gap[0].addr..gap[gap.len() - 1].words().end;
+            if !gap_a.is_empty() {
+                changes.push(Change::Deleted { a: words(gap_a) });
+            }
+            if !gap_b.is_empty() {
+                changes.push(Change::Inserted { b: words(gap_b) });
+            }
+        }
+        if (i, j) == end {
+            break;
+        }
+
+        let (unit_a, unit_b) = (units_a[i], units_b[j]);
+        compare(a, b, (unit_a, unit_b), &moved, &mut changes);
+        if matches!(unit_a.key, Key::Data(_)) && unit_a.addr != unit_b.addr {
+            data_run = Some(match data_run {
+                Some((run_a, run_b)) => {
+                    (run_a.start..unit_a.addr + 1, run_b.start..unit_b.addr + 1)
+                }
+                None => (unit_a.words(), unit_b.words()),
+            });
+        } else {
+            flush(&mut data_run, &mut changes);
+        }
+        (next_a, next_b) = (i + 1, j + 1);
+    }
+    changes
+}
+
+/// Report a run of shifted data, if there is one and it's long enough.
+fn flush(data_run: &mut Option<(Range<usize>, Range<usize>)>, changes: &mut Vec<Change>) {
+    if let Some((a, b)) = data_run.take().filter(|(a, _)| a.len() > 1) {
+        changes.push(Change::Shifted { a, b });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asm::assemble;
+
+    #[test]
+    fn same_program() {
+        let program = crate::IntcodeComputer::from_file("data/day9").mem;
+        assert_eq!(diff(&program, &program), []);
+    }
+
+    #[test]
+    fn changes() {
+        let a = assemble(
+            "
+                   IN [x]
+                   ADD [x], 5, [x]
+                   OUT [x]
+                   JZ [x], end
+            end:   HALT
+            x:     DATA 0
+            table: DATA 1, 2, 3, 4
+            ",
+        )
+        .unwrap()
+        .program;
+        let b = assemble(
+            "
+                   IN [x]
+                   ADD [x], 7, [x]
+                   OUT [x]
+                   JZ [x], end
+                   OUT 1
+                   OUT 2
+            end:   HALT
+            x:     DATA 0
+            table: DATA 1, 2, 3, 9
+            ",
+        )
+        .unwrap()
+        .program;
+
+        // References to x, end and the moved table aren't changes.
+        assert_eq!(
+            diff(&a, &b),
+            [
+                Change::Constant {
+                    a: 4,
+                    b: 4,
+                    old: 5,
+                    new: 7
+                },
+                Change::Inserted { b: 11..15 },
+                Change::Shifted {
+                    a: 12..16,
+                    b: 16..20
+                },
+                Change::Constant {
+                    a: 16,
+                    b: 20,
+                    old: 4,
+                    new: 9
+                },
+            ]
+        );
+        assert_eq!(diff(&a, &b)[1].to_string(), "inserted 11..15");
+        assert_eq!(diff(&b, &a)[1], Change::Deleted { a: 11..15 });
+    }
+}
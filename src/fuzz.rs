@@ -0,0 +1,397 @@
+//! Coverage-guided fuzzing of a program's input.
+//!
+//! Inputs are mutated from a corpus, and an input is kept when its run
+//! executes an instruction or takes a branch direction no earlier run did.
+//! Runs that panic or fault are crashes, kept once per address and message
+//! ignoring numbers, and each different output is kept with the first input
+//! producing it.
+//!
+//! Crashes are caught with `catch_unwind`, so the panic hook still reports
+//! them; callers fuzzing for long should install a quiet hook.
+
+use crate::{IntcodeComputer, Limits, ParameterMode};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+
+/// The instructions and branch directions a run executed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    pub ips: BTreeSet<usize>,
+    /// Conditional jumps, and whether they jumped.
+    pub branches: BTreeSet<(usize, bool)>,
+}
+
+impl Coverage {
+    /// Add `other` to this coverage, returning whether it had anything new.
+    pub fn merge(&mut self, other: &Coverage) -> bool {
+        let (ips, branches) = (self.ips.len(), self.branches.len());
+        self.ips.extend(&other.ips);
+        self.branches.extend(&other.branches);
+        (ips, branches) != (self.ips.len(), self.branches.len())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Halted,
+    /// The program wanted more input than it was given.
+    Blocked,
+    /// The program didn't stop within the step limit.
+    Timeout,
+    /// The program panicked or faulted at `ip`.
+    Crash {
+        ip: usize,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    pub outcome: Outcome,
+    pub output: Vec<i64>,
+    pub coverage: Coverage,
+}
+
+/// Whether the instruction at `pc.ip` is a branch that will jump, decoded
+/// from its condition rather than from where it lands, since the target may
+/// be the next instruction. None if it isn't a branch.
+fn branch_taken(pc: &IntcodeComputer) -> Option<bool> {
+    let instruction = pc.fetch(pc.ip);
+    let jump_if = match instruction % 100 {
+        5 => true,
+        6 => false,
+        _ => return None,
+    };
+    let value = pc.fetch(pc.ip + 1);
+    let condition = match ParameterMode::try_new(instruction / 100, 1)? {
+        ParameterMode::Immediate => value,
+        mode => pc.fetch(pc.absolute(value, mode).ok()? as usize),
+    };
+    Some((condition != 0) == jump_if)
+}
+
+/// Run `program` on `input` for at most `max_steps` instructions, recording
+/// its coverage.
+pub fn execute(program: &[i64], input: &[i64], limits: &Limits, max_steps: usize) -> Run {
+    let mut pc = IntcodeComputer::new(program.to_vec());
+    pc.limits = limits.clone();
+    pc.input.extend(input);
+    let mut coverage = Coverage::default();
+    // Whether the program stopped within the step limit.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        for _ in 0..max_steps {
+            let ip = pc.ip;
+            let taken = branch_taken(&pc);
+            if pc.halted || !pc.step() {
+                return true;
+            }
+            coverage.ips.insert(ip);
+            if let Some(taken) = taken {
+                coverage.branches.insert((ip, taken));
+            }
+        }
+        pc.halted
+    }));
+
+    let outcome = match result {
+        Err(payload) => Outcome::Crash {
+            ip: pc.ip,
+            message: match payload.downcast::<String>() {
+                Ok(message) => *message,
+                Err(payload) => match payload.downcast::<&str>() {
+                    Ok(message) => message.to_string(),
+                    Err(_) => "panic".to_string(),
+                },
+            },
+        },
+        Ok(false) => Outcome::Timeout,
+        Ok(true) if pc.halted => Outcome::Halted,
+        Ok(true) => match pc.fault {
+            Some(fault) => Outcome::Crash {
+                ip: pc.ip,
+                message: fault.to_string(),
+            },
+            None => Outcome::Blocked,
+        },
+    };
+    Run {
+        outcome,
+        output: pc.output.into(),
+        coverage,
+    }
+}
+
+/// A crashing input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crash {
+    pub input: Vec<i64>,
+    pub ip: usize,
+    pub message: String,
+}
+
+/// A crash message without its numbers, so crashes at the same place
+/// differing only in values, like unknown opcodes from input written into
+/// code, count as one.
+fn kind(message: &str) -> String {
+    message
+        .chars()
+        .filter(|c| !c.is_ascii_digit() && *c != '-')
+        .collect()
+}
+
+/// A xorshift64* generator, so runs are reproducible from a seed.
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A number in `0..n`, which must be positive.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+pub struct Fuzzer {
+    program: Vec<i64>,
+    pub limits: Limits,
+    pub max_steps: usize,
+    /// Inputs that found new coverage.
+    pub corpus: Vec<Vec<i64>>,
+    pub coverage: Coverage,
+    pub crashes: Vec<Crash>,
+    /// Distinct outputs, with the first input producing each.
+    pub outputs: BTreeMap<Vec<i64>, Vec<i64>>,
+    /// Values mutations insert: small numbers, ASCII and the program's words.
+    dictionary: Vec<i64>,
+    rng: Rng,
+}
+
+impl Fuzzer {
+    /// A fuzzer for `program` whose mutations are determined by `seed`. The
+    /// corpus starts with an empty input.
+    pub fn new(program: Vec<i64>, seed: u64) -> Self {
+        let mut dictionary: BTreeSet<i64> = [-1, 0, 1, 10].into_iter().chain(32..127).collect();
+        dictionary.extend(&program);
+        let mut fuzzer = Self {
+            program,
+            limits: Limits {
+                max_addr: Some(1 << 20),
+                ..Limits::default()
+            },
+            max_steps: 100_000,
+            corpus: vec![],
+            coverage: Coverage::default(),
+            crashes: vec![],
+            outputs: BTreeMap::new(),
+            dictionary: dictionary.into_iter().collect(),
+            // Zero is a fixed point of xorshift.
+            rng: Rng(seed | 1),
+        };
+        if !fuzzer.add(vec![]) {
+            fuzzer.corpus.push(vec![]);
+        }
+        fuzzer
+    }
+
+    /// Run `input`, keeping it if it finds new coverage, a new crash or a new
+    /// output. Returns whether it found new coverage.
+    pub fn add(&mut self, input: Vec<i64>) -> bool {
+        let run = execute(&self.program, &input, &self.limits, self.max_steps);
+        if let Outcome::Crash { ip, message } = run.outcome {
+            if !self
+                .crashes
+                .iter()
+                .any(|crash| crash.ip == ip && kind(&crash.message) == kind(&message))
+            {
+                self.crashes.push(Crash {
+                    input: input.clone(),
+                    ip,
+                    message,
+                });
+            }
+        }
+        self.outputs
+            .entry(run.output)
+            .or_insert_with(|| input.clone());
+        let new = self.coverage.merge(&run.coverage);
+        if new {
+            self.corpus.push(input);
+        }
+        new
+    }
+
+    /// Run `iterations` mutated inputs. Returns how many found new coverage.
+    pub fn fuzz(&mut self, iterations: usize) -> usize {
+        (0..iterations)
+            .filter(|_| {
+                let input = self.mutate();
+                self.add(input)
+            })
+            .count()
+    }
+
+    fn value(&mut self) -> i64 {
+        match self.rng.below(4) {
+            0 => self.rng.next() as i64,
+            _ => self.dictionary[self.rng.below(self.dictionary.len())],
+        }
+    }
+
+    /// A mutation of a random corpus input.
+    fn mutate(&mut self) -> Vec<i64> {
+        let mut input = self.corpus[self.rng.below(self.corpus.len())].clone();
+        for _ in 0..=self.rng.below(4) {
+            let len = input.len();
+            match self.rng.below(6) {
+                0 if len > 0 => {
+                    let i = self.rng.below(len);
+                    input[i] = self.value();
+                }
+                1 if len > 0 => {
+                    let i = self.rng.below(len);
+                    input[i] = input[i].wrapping_add(if self.rng.below(2) == 0 { 1 } else { -1 });
+                }
+                2 if len > 0 => {
+                    input.remove(self.rng.below(len));
+                }
+                3 => {
+                    // Splice in part of another input.
+                    let other = &self.corpus[self.rng.below(self.corpus.len())];
+                    let start = self.rng.below(other.len() + 1);
+                    let end = start + self.rng.below(other.len() - start + 1);
+                    let at = self.rng.below(len + 1);
+                    input.splice(at..at, other[start..end].to_vec());
+                }
+                _ => {
+                    let at = self.rng.below(len + 1);
+                    let value = self.value();
+                    input.insert(at, value);
+                }
+            }
+        }
+        input
+    }
+
+    /// Save the corpus as `input-<n>`, the crashes as `crash-<n>` and the
+    /// inputs giving each output as `output-<n>` in `dir`, replacing those
+    /// of an earlier save. The files are program text, with `#` comments for
+    /// the crash message and output.
+    pub fn save(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if ["input-", "crash-", "output-"]
+                .iter()
+                .any(|prefix| name.starts_with(prefix))
+            {
+                fs::remove_file(&path)?;
+            }
+        }
+        let text = |values: &[i64]| {
+            let values: Vec<String> = values.iter().map(i64::to_string).collect();
+            values.join(",")
+        };
+        for (i, input) in self.corpus.iter().enumerate() {
+            fs::write(dir.join(format!("input-{i}")), text(input) + "\n")?;
+        }
+        for (i, crash) in self.crashes.iter().enumerate() {
+            let contents = format!(
+                "# ip {}: {}\n{}\n",
+                crash.ip,
+                crash.message.replace('\n', " "),
+                text(&crash.input)
+            );
+            fs::write(dir.join(format!("crash-{i}")), contents)?;
+        }
+        for (i, (output, input)) in self.outputs.iter().enumerate() {
+            let contents = format!("# output {}\n{}\n", text(output), text(input));
+            fs::write(dir.join(format!("output-{i}")), contents)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{compiler::compile, load};
+
+    #[test]
+    fn coverage() {
+        // Output 1 if the input is 8, else 0.
+        let program = [3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8];
+        let run = execute(&program, &[8], &Limits::default(), 100);
+        assert_eq!(run.outcome, Outcome::Halted);
+        assert_eq!(run.output, [1]);
+        assert_eq!(run.coverage.ips, BTreeSet::from([0, 2, 6, 8]));
+
+        let program = crate::IntcodeComputer::from_file("data/day9").mem;
+        let run = execute(&program, &[], &Limits::default(), 100);
+        assert_eq!(run.outcome, Outcome::Blocked);
+        let run = execute(&program, &[1], &Limits::default(), 100);
+        assert_eq!(run.outcome, Outcome::Timeout);
+        let mut all = run.coverage.clone();
+        assert!(!all.merge(&run.coverage));
+        let run = execute(&program, &[1], &Limits::default(), 100_000);
+        assert_eq!(run.outcome, Outcome::Halted);
+        assert!(run.coverage.branches.len() > 10);
+        assert!(all.merge(&run.coverage));
+
+        // JNZ [7],3; JNZ 1,6; HALT; DATA 0: both branches land on the
+        // next instruction, one jumping and one not.
+        let program = [1005, 7, 3, 1105, 1, 6, 99, 0];
+        let run = execute(&program, &[], &Limits::default(), 100);
+        assert_eq!(
+            run.coverage.branches,
+            BTreeSet::from([(0, false), (3, true)])
+        );
+    }
+
+    #[test]
+    fn finds_crash() {
+        let program = compile(
+            "
+            fn main() {
+                if input() == 7 {
+                    output(1);
+                    if input() == 'x' {
+                        var p = -5;
+                        output(p[0]);
+                    }
+                }
+            }
+            ",
+        )
+        .unwrap();
+        let mut fuzzer = Fuzzer::new(program, 1);
+        fuzzer.fuzz(1000);
+
+        assert_eq!(fuzzer.crashes.len(), 1);
+        let crash = &fuzzer.crashes[0];
+        assert_eq!(crash.message, "Negative address -5");
+        assert_eq!(crash.input[..2], [7, 'x' as i64]);
+        assert!(fuzzer.outputs.contains_key(&vec![]));
+        assert!(fuzzer.outputs.contains_key(&vec![1]));
+
+        let dir = std::env::temp_dir().join(format!("fuzz-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("input-9999"), "1\n").unwrap();
+        fuzzer.save(&dir).unwrap();
+        assert!(!dir.join("input-9999").exists());
+        let saved = load::read_file(dir.join("crash-0")).unwrap();
+        assert_eq!(saved, crash.input);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
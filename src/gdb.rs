@@ -0,0 +1,538 @@
+//! A GDB remote serial protocol stub exposing an IntcodeComputer.
+//!
+//! Memory is byte addressed: word `n` occupies bytes `8 * n .. 8 * n + 8`,
+//! little-endian, and reads past the end of memory return zeros. The
+//! registers are `ip` (register 0, the program counter) and `rb` (register
+//! 1), both given as byte addresses.
+//!
+//! Besides registers, memory, single-stepping, continuing and breakpoints,
+//! the stub understands the monitor commands `input <values>` and
+//! `ascii <text>`, which queue input for the program. Values the program
+//! outputs are sent to the debugger console, one per line.
+//!
+//! While continuing, the stub checks for an interrupt from the debugger
+//! every `POLL_STEPS` instructions, so a program looping without I/O can be
+//! stopped. A handler panicking stops the program with SIGSEGV.
+
+use crate::{Fault, IntcodeComputer};
+use std::{
+    collections::BTreeSet,
+    io::{self, Read, Write},
+    net::TcpStream,
+    panic::{self, AssertUnwindSafe},
+};
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGFPE: u8 = 8;
+const SIGSEGV: u8 = 11;
+/// Stopped reading from the terminal, used when the program waits on input.
+const SIGTTIN: u8 = 21;
+
+/// Bytes per word.
+const WORD: usize = 8;
+
+/// Maximum size of a packet we send or accept.
+const PACKET_SIZE: usize = 0x4000;
+
+/// Instructions executed between checks for an interrupt.
+const POLL_STEPS: usize = 10_000;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.intcode.core">
+    <reg name="ip" bitsize="64" type="code_ptr" regnum="0"/>
+    <reg name="rb" bitsize="64" type="data_ptr" regnum="1"/>
+  </feature>
+</target>
+"#;
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &b| sum.wrapping_add(b))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+fn read_byte<S: Read>(stream: &mut S) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    match stream.read(&mut byte)? {
+        0 => Ok(None),
+        _ => Ok(Some(byte[0])),
+    }
+}
+
+/// Read the next packet and acknowledge it. An interrupt is returned as the
+/// packet "\x03". Returns None at the end of the stream.
+fn read_packet<S: Read + Write>(stream: &mut S) -> io::Result<Option<String>> {
+    loop {
+        match read_byte(stream)? {
+            None => return Ok(None),
+            Some(b'$') => {}
+            Some(0x03) => return Ok(Some("\x03".to_string())),
+            // Acknowledgements of our packets.
+            Some(_) => continue,
+        }
+        let mut data = vec![];
+        loop {
+            match read_byte(stream)? {
+                None => return Ok(None),
+                Some(b'#') => break,
+                Some(byte) => data.push(byte),
+            }
+        }
+        let mut sum = [0; 2];
+        stream.read_exact(&mut sum)?;
+        if unhex(&String::from_utf8_lossy(&sum)) == Some(vec![checksum(&data)]) {
+            stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+        stream.write_all(b"-")?;
+    }
+}
+
+fn write_packet<S: Write>(stream: &mut S, data: &str) -> io::Result<()> {
+    write!(stream, "${data}#{:02x}", checksum(data.as_bytes()))
+}
+
+/// A stream to a debugger that can tell whether it has sent anything.
+pub trait Connection: Read + Write {
+    /// Whether reading wouldn't block.
+    fn ready(&mut self) -> io::Result<bool>;
+}
+
+impl Connection for TcpStream {
+    fn ready(&mut self) -> io::Result<bool> {
+        self.set_nonblocking(true)?;
+        let result = match self.peek(&mut [0]) {
+            Ok(_) => Ok(true),
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(error) => Err(error),
+        };
+        self.set_nonblocking(false)?;
+        result
+    }
+}
+
+/// Whether the debugger sent an interrupt. Other bytes sent while the
+/// program runs are acknowledgements, and are skipped. A closed or broken
+/// connection counts as an interrupt, so the program stops.
+fn interrupted<S: Connection>(stream: &mut S) -> bool {
+    loop {
+        match stream.ready() {
+            Ok(true) => {}
+            Ok(false) => return false,
+            Err(_) => return true,
+        }
+        match read_byte(stream) {
+            Ok(Some(0x03)) | Ok(None) | Err(_) => return true,
+            Ok(Some(_)) => {}
+        }
+    }
+}
+
+/// A debugging session on a single computer.
+#[derive(Debug, Clone)]
+pub struct Stub {
+    pub pc: IntcodeComputer,
+    /// Word addresses to stop at when continuing.
+    pub breakpoints: BTreeSet<usize>,
+    detached: bool,
+}
+
+impl Stub {
+    pub fn new(pc: IntcodeComputer) -> Self {
+        Self {
+            pc,
+            breakpoints: BTreeSet::new(),
+            detached: false,
+        }
+    }
+
+    /// Serve a debugger on `stream` until it kills or detaches from the
+    /// program, or closes the connection.
+    pub fn serve<S: Connection>(&mut self, mut stream: S) -> io::Result<()> {
+        self.detached = false;
+        while let Some(packet) = read_packet(&mut stream)? {
+            if packet == "k" {
+                break;
+            }
+            let reply = self.reply(&packet, &mut || interrupted(&mut stream));
+            for value in self.pc.output.drain(..) {
+                let text = format!("{value}\n");
+                write_packet(&mut stream, &format!("O{}", hex(text.as_bytes())))?;
+            }
+            write_packet(&mut stream, &reply)?;
+            stream.flush()?;
+            if self.detached {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// The reply to `packet`. Unsupported requests get an empty reply.
+    pub fn handle(&mut self, packet: &str) -> String {
+        self.reply(packet, &mut || false)
+    }
+
+    /// The reply to `packet`, continuing until `interrupted` returns true
+    /// when it is polled.
+    fn reply(&mut self, packet: &str, interrupted: &mut dyn FnMut() -> bool) -> String {
+        let reply = match packet {
+            "\x03" => Some(format!("S{SIGINT:02x}")),
+            "?" if self.pc.halted => Some("W00".to_string()),
+            "?" => Some(format!("S{SIGTRAP:02x}")),
+            "g" => Some(hex(&[self.register(0), self.register(1)].concat())),
+            "D" => {
+                self.detached = true;
+                Some("OK".to_string())
+            }
+            "qAttached" => Some("1".to_string()),
+            "qC" => Some("QC1".to_string()),
+            "qfThreadInfo" => Some("m1".to_string()),
+            "qsThreadInfo" => Some("l".to_string()),
+            _ if packet.starts_with('H') => Some("OK".to_string()),
+            _ if packet.starts_with("qSupported") => {
+                Some(format!("PacketSize={PACKET_SIZE:x};qXfer:features:read+"))
+            }
+            _ => match packet.as_bytes().first() {
+                Some(b's' | b'c') => self.resume(packet, interrupted),
+                Some(b'G') => self.write_registers(&packet[1..]),
+                Some(b'p') => parse_hex(&packet[1..]).map(|n| hex(&self.register(n))),
+                Some(b'P') => self.write_register(&packet[1..]),
+                Some(b'm') => self.read_memory(&packet[1..]),
+                Some(b'M') => self.write_memory(&packet[1..]),
+                Some(b'Z' | b'z') => self.breakpoint(packet),
+                Some(b'q') => self.query(packet),
+                _ => Some(String::new()),
+            },
+        };
+        reply.unwrap_or_else(|| "E01".to_string())
+    }
+
+    /// Register `n` as little-endian bytes.
+    fn register(&self, n: usize) -> Vec<u8> {
+        let value = match n {
+            0 => self.pc.ip as i64 * WORD as i64,
+            1 => self.pc.rb * WORD as i64,
+            _ => 0,
+        };
+        value.to_le_bytes().to_vec()
+    }
+
+    fn set_register(&mut self, n: usize, bytes: &[u8]) -> Option<()> {
+        let value = i64::from_le_bytes(bytes.try_into().ok()?) / WORD as i64;
+        match n {
+            0 => self.pc.ip = usize::try_from(value).ok()?,
+            1 => self.pc.rb = value,
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn write_registers(&mut self, args: &str) -> Option<String> {
+        let bytes = unhex(args)?;
+        for (n, value) in bytes.chunks(WORD).enumerate() {
+            self.set_register(n, value)?;
+        }
+        Some("OK".to_string())
+    }
+
+    fn write_register(&mut self, args: &str) -> Option<String> {
+        let (n, value) = args.split_once('=')?;
+        self.set_register(parse_hex(n)?, &unhex(value)?)?;
+        Some("OK".to_string())
+    }
+
+    fn read_byte(&self, addr: usize) -> u8 {
+        let word = self.pc.mem.get(addr / WORD).copied().unwrap_or(0);
+        word.to_le_bytes()[addr % WORD]
+    }
+
+    fn write_byte(&mut self, addr: usize, byte: u8) {
+        let index = addr / WORD;
+        if self.pc.mem.len() <= index {
+            self.pc.mem.resize(index + 1, 0);
+        }
+        let mut bytes = self.pc.mem[index].to_le_bytes();
+        bytes[addr % WORD] = byte;
+        self.pc.mem[index] = i64::from_le_bytes(bytes);
+    }
+
+    /// Parse `addr,length`.
+    fn range(args: &str) -> Option<(usize, usize)> {
+        let (addr, length) = args.split_once(',')?;
+        Some((parse_hex(addr)?, parse_hex(length)?))
+    }
+
+    fn read_memory(&self, args: &str) -> Option<String> {
+        let (addr, length) = Self::range(args)?;
+        let bytes: Vec<u8> = (addr..addr.saturating_add(length.min(PACKET_SIZE / 2)))
+            .map(|addr| self.read_byte(addr))
+            .collect();
+        Some(hex(&bytes))
+    }
+
+    fn write_memory(&mut self, args: &str) -> Option<String> {
+        let (range, data) = args.split_once(':')?;
+        let (addr, length) = Self::range(range)?;
+        let bytes = unhex(data).filter(|bytes| bytes.len() == length)?;
+        for (i, byte) in bytes.into_iter().enumerate() {
+            self.write_byte(addr + i, byte);
+        }
+        Some("OK".to_string())
+    }
+
+    /// Insert or remove a breakpoint. Software and hardware breakpoints are
+    /// the same thing here.
+    fn breakpoint(&mut self, packet: &str) -> Option<String> {
+        let mut parts = packet[1..].split(',');
+        let kind = parts.next()?;
+        let addr = parse_hex(parts.next()?)? / WORD;
+        if kind != "0" && kind != "1" {
+            return Some(String::new());
+        }
+        if packet.starts_with('Z') {
+            self.breakpoints.insert(addr);
+        } else {
+            self.breakpoints.remove(&addr);
+        }
+        Some("OK".to_string())
+    }
+
+    /// Step once or continue, optionally from a new address, and return the
+    /// stop reply.
+    fn resume(&mut self, packet: &str, interrupted: &mut dyn FnMut() -> bool) -> Option<String> {
+        if packet.len() > 1 {
+            self.pc.ip = parse_hex(&packet[1..])? / WORD;
+        }
+        let single = packet.starts_with('s');
+        let mut steps = 0;
+        let signal = loop {
+            if self.pc.halted {
+                return Some("W00".to_string());
+            }
+            if steps > 0 && self.breakpoints.contains(&self.pc.ip) {
+                break SIGTRAP;
+            }
+            if steps > 0 && steps % POLL_STEPS == 0 && interrupted() {
+                break SIGINT;
+            }
+            let pc = &mut self.pc;
+            match panic::catch_unwind(AssertUnwindSafe(|| pc.step())) {
+                Ok(true) => {}
+                Ok(false) => {
+                    break match self.pc.fault {
+                        Some(Fault::Overflow) => SIGFPE,
+                        Some(
+                            Fault::InvalidOpcode { .. }
+                            | Fault::InvalidMode { .. }
+                            | Fault::ImmediateWrite { .. },
+                        ) => SIGILL,
+                        Some(_) => SIGSEGV,
+                        None => SIGTTIN,
+                    }
+                }
+                Err(_) => break SIGSEGV,
+            }
+            if single && !self.pc.halted {
+                break SIGTRAP;
+            }
+            steps += 1;
+        };
+        Some(format!("S{signal:02x}"))
+    }
+
+    fn query(&mut self, packet: &str) -> Option<String> {
+        if let Some(command) = packet.strip_prefix("qRcmd,") {
+            let command = String::from_utf8(unhex(command)?).ok()?;
+            let (name, args) = command.split_once(' ').unwrap_or((&command, ""));
+            match name {
+                "input" => {
+                    let values: Result<Vec<i64>, _> =
+                        args.split_whitespace().map(str::parse).collect();
+                    self.pc.input.extend(values.ok()?);
+                }
+                "ascii" => {
+                    self.pc.input.extend(args.bytes().map(i64::from));
+                    self.pc.input.push_back(b'\n' as i64);
+                }
+                _ => return None,
+            }
+            return Some("OK".to_string());
+        }
+        if let Some(range) = packet.strip_prefix("qXfer:features:read:target.xml:") {
+            let (offset, length) = Self::range(range)?;
+            let start = offset.min(TARGET_XML.len());
+            let end = (offset + length).min(TARGET_XML.len());
+            let more = if end < TARGET_XML.len() { 'm' } else { 'l' };
+            return Some(format!("{more}{}", &TARGET_XML[start..end]));
+        }
+        Some(String::new())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_packet, write_packet, Stub};
+    use crate::{opcodes::Flow, IntcodeComputer};
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        thread,
+    };
+
+    /// A scripted debugger client.
+    struct Client {
+        stream: TcpStream,
+    }
+
+    impl Client {
+        /// Send `request` and return the packets received up to and
+        /// including the reply.
+        fn send(&mut self, request: &str) -> Vec<String> {
+            write_packet(&mut self.stream, request).unwrap();
+            let mut ack = [0];
+            self.stream.read_exact(&mut ack).unwrap();
+            assert_eq!(ack, *b"+");
+            let mut packets = vec![];
+            loop {
+                let packet = read_packet(&mut self.stream).unwrap().unwrap();
+                let console = packet.starts_with('O') && packet != "OK";
+                packets.push(packet);
+                if !console {
+                    return packets;
+                }
+            }
+        }
+
+        fn request(&mut self, request: &str) -> String {
+            self.send(request).pop().unwrap()
+        }
+    }
+
+    #[test]
+    fn session() {
+        // OUT 7; IN [20]; OUT [20]; HALT
+        let pc = IntcodeComputer::new(vec![104, 7, 3, 20, 4, 20, 99]);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut stub = Stub::new(pc);
+            let (stream, _) = listener.accept().unwrap();
+            stub.serve(stream).unwrap();
+            stub
+        });
+        let mut client = Client {
+            stream: TcpStream::connect(addr).unwrap(),
+        };
+
+        assert!(client
+            .request("qSupported:xmlRegisters=i386")
+            .contains("qXfer"));
+        assert_eq!(client.request("?"), "S05");
+        assert_eq!(client.request("g"), "00000000000000000000000000000000");
+
+        // Read words 0 and 1.
+        assert_eq!(client.request("m0,10"), "68000000000000000700000000000000");
+        // Words past the end of memory read as zero.
+        assert_eq!(client.request("m100,2"), "0000");
+
+        // Step over OUT 7; the output goes to the console.
+        assert_eq!(client.send("s"), ["O370a", "S05"]);
+        assert_eq!(client.request("p0"), "1000000000000000");
+
+        // IN blocks without input.
+        assert_eq!(client.request("c"), "S15");
+        assert_eq!(
+            client.request(&format!("qRcmd,{}", super::hex(b"input 42"))),
+            "OK"
+        );
+
+        // Break at OUT [20], at word 4.
+        assert_eq!(client.request("Z0,20,1"), "OK");
+        assert_eq!(client.request("c"), "S05");
+        assert_eq!(client.request("p0"), "2000000000000000");
+        assert_eq!(client.request("ma0,8"), "2a00000000000000");
+
+        // Patch the input before it is output.
+        assert_eq!(client.request("Ma0,1:2b"), "OK");
+        assert_eq!(client.request("z0,20,1"), "OK");
+        assert_eq!(client.send("c"), ["O34330a", "W00"]);
+        assert_eq!(client.request("D"), "OK");
+
+        let stub = server.join().unwrap();
+        assert!(stub.pc.halted);
+        assert!(stub.breakpoints.is_empty());
+    }
+
+    #[test]
+    fn interrupt() {
+        // JZ 0,0
+        let pc = IntcodeComputer::new(vec![1106, 0, 0]);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut stub = Stub::new(pc);
+            let (stream, _) = listener.accept().unwrap();
+            stub.serve(stream).unwrap();
+        });
+        let mut client = Client {
+            stream: TcpStream::connect(addr).unwrap(),
+        };
+
+        write_packet(&mut client.stream, "c").unwrap();
+        let mut ack = [0];
+        client.stream.read_exact(&mut ack).unwrap();
+        client.stream.write_all(&[0x03]).unwrap();
+        let reply = read_packet(&mut client.stream).unwrap().unwrap();
+        assert_eq!(reply, "S02");
+        assert_eq!(client.request("D"), "OK");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn invalid_instructions() {
+        let mut stub = Stub::new(IntcodeComputer::new(vec![42]));
+        assert_eq!(stub.handle("c"), "S04");
+
+        // Past the end of memory.
+        let mut stub = Stub::new(IntcodeComputer::new(vec![99]));
+        assert_eq!(stub.handle("P0=0004000000000000"), "OK");
+        assert_eq!(stub.handle("s"), "S04");
+
+        let mut pc = IntcodeComputer::new(vec![10, 99]);
+        pc.register(10, 0, &[], |_, _| -> Flow { panic!("handler failed") });
+        let mut stub = Stub::new(pc);
+        assert_eq!(stub.handle("c"), "S0b");
+        assert_eq!(stub.pc.ip, 0);
+    }
+
+    #[test]
+    fn bad_packets() {
+        let mut stub = Stub::new(IntcodeComputer::new(vec![99]));
+        assert_eq!(stub.handle("mzz,1"), "E01");
+        assert_eq!(stub.handle("vMustReplyEmpty"), "");
+        assert_eq!(stub.handle("P5=0000000000000000"), "E01");
+        let xml = stub.handle("qXfer:features:read:target.xml:0,10");
+        assert_eq!(xml, "m<?xml version=\"1");
+    }
+}
@@ -0,0 +1,349 @@
+//! Stepping an IntcodeComputer backwards.
+//!
+//! While recording, every step that changes the computer appends an undo
+//! record with the words it overwrote, the previous ip, rb and status, and
+//! the inputs it consumed and outputs it produced. To bound memory, the steps
+//! are split into segments of `interval` steps. Only the current segment
+//! keeps its undo records; earlier segments keep a snapshot of the computer
+//! at their start and the inputs they consumed, and their undo records are
+//! rebuilt by replaying them when the computer steps back into them. At
+//! most `max_checkpoints` segments are kept.
+
+use crate::{word::Word, Fault, IntcodeComputer};
+use alloc::{boxed::Box, collections::VecDeque, vec, vec::Vec};
+
+#[derive(Debug, Clone)]
+pub(crate) struct Undo<W> {
+    ip: usize,
+    rb: i64,
+    halted: bool,
+    fault: Option<Fault>,
+    /// Length of memory before the step.
+    len: usize,
+    /// Overwritten words, in the order they were written.
+    writes: Vec<(usize, W)>,
+    /// The consumed inputs.
+    input: Vec<W>,
+    /// The produced outputs.
+    output: Vec<W>,
+}
+
+/// The computer at the start of a segment.
+#[derive(Debug, Clone)]
+struct Checkpoint<W> {
+    mem: Vec<W>,
+    ip: usize,
+    rb: i64,
+    halted: bool,
+    fault: Option<Fault>,
+    /// Inputs consumed during the segment, once it is complete.
+    inputs: Vec<W>,
+    /// Number of steps in the segment, once it is complete.
+    steps: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct History<W> {
+    interval: usize,
+    max_checkpoints: usize,
+    /// The start of every kept segment, oldest first.
+    checkpoints: VecDeque<Checkpoint<W>>,
+    /// Undo records of the current segment.
+    log: Vec<Undo<W>>,
+    /// Words overwritten by the step being executed.
+    pub(crate) writes: Vec<(usize, W)>,
+}
+
+impl<W: Word> IntcodeComputer<W> {
+    fn checkpoint(&self) -> Checkpoint<W> {
+        Checkpoint {
+            mem: self.mem.clone(),
+            ip: self.ip,
+            rb: self.rb,
+            halted: self.halted,
+            fault: self.fault,
+            inputs: vec![],
+            steps: 0,
+        }
+    }
+
+    /// Start recording steps so they can be undone, keeping up to
+    /// `max_checkpoints` segments of `interval` steps.
+    pub fn record_history(&mut self, interval: usize, max_checkpoints: usize) {
+        assert!(interval > 0, "Checkpoint interval should be positive");
+        assert!(
+            max_checkpoints > 0,
+            "At least one checkpoint should be kept"
+        );
+        self.history = Some(Box::new(History {
+            interval,
+            max_checkpoints,
+            checkpoints: VecDeque::from([self.checkpoint()]),
+            log: vec![],
+            writes: vec![],
+        }));
+    }
+
+    /// Stop recording and forget the recorded steps.
+    pub fn stop_recording(&mut self) {
+        self.history = None;
+    }
+
+    /// Called by `step` before executing an instruction.
+    pub(crate) fn begin_undo(&mut self) -> Option<Undo<W>> {
+        let history = self.history.as_mut()?;
+        history.writes.clear();
+        // Built-in opcodes consume at most one input, custom ones any number.
+        let inputs = if self.opcodes.custom { usize::MAX } else { 1 };
+        Some(Undo {
+            ip: self.ip,
+            rb: self.rb,
+            halted: self.halted,
+            fault: self.fault,
+            len: self.mem.len(),
+            writes: vec![],
+            input: self.input.iter().take(inputs).copied().collect(),
+            output: vec![],
+        })
+    }
+
+    /// Called by `step` after executing an instruction with the undo record
+    /// from `begin_undo` and the I/O queue lengths before the instruction.
+    pub(crate) fn end_undo(&mut self, mut undo: Undo<W>, input_len: usize, output_len: usize) {
+        undo.input
+            .truncate(input_len.saturating_sub(self.input.len()));
+        undo.output = self.output.iter().skip(output_len).copied().collect();
+        let checkpoint = {
+            let history = self.history.as_mut().unwrap();
+            undo.writes = core::mem::take(&mut history.writes);
+            history.log.push(undo);
+            history.log.len() == history.interval
+        };
+        if checkpoint {
+            let next = self.checkpoint();
+            let history = self.history.as_mut().unwrap();
+            let current = history.checkpoints.back_mut().unwrap();
+            current.inputs = history
+                .log
+                .iter()
+                .flat_map(|undo| undo.input.iter().copied())
+                .collect();
+            current.steps = history.log.len();
+            history.log.clear();
+            history.checkpoints.push_back(next);
+            if history.checkpoints.len() > history.max_checkpoints {
+                history.checkpoints.pop_front();
+            }
+        }
+    }
+
+    /// Rebuild the undo records of the segment before the current one by
+    /// replaying it. Returns false if there is no such segment.
+    fn replay_segment(&mut self) -> bool {
+        let history = self.history.as_mut().unwrap();
+        if history.checkpoints.len() < 2 {
+            return false;
+        }
+        history.checkpoints.pop_back();
+        let checkpoint = history.checkpoints.back().unwrap().clone();
+
+        let mut pc = IntcodeComputer::with_opcodes(checkpoint.mem, self.opcodes.clone());
+        pc.ip = checkpoint.ip;
+        pc.rb = checkpoint.rb;
+        pc.halted = checkpoint.halted;
+        pc.fault = checkpoint.fault;
+        pc.input = checkpoint.inputs.into();
+        pc.limits = self.limits.clone();
+        pc.record_history(usize::MAX, 1);
+        let recorded = |pc: &Self| pc.history.as_ref().unwrap().log.len();
+        while recorded(&pc) < checkpoint.steps {
+            let steps = recorded(&pc);
+            pc.step();
+            assert!(
+                recorded(&pc) > steps,
+                "Replay should repeat the recorded steps"
+            );
+            // A recorded step that faulted was followed by more steps only if
+            // the fault was cleared.
+            pc.fault = None;
+        }
+
+        let log = core::mem::take(&mut pc.history.as_mut().unwrap().log);
+        let history = self.history.as_mut().unwrap();
+        history.log = log;
+        let current = history.checkpoints.back_mut().unwrap();
+        current.inputs.clear();
+        current.steps = 0;
+        true
+    }
+
+    /// Undo the last recorded step. Consumed input is put back at the front
+    /// of `input`, and produced output is removed from `output` if it is
+    /// still at the back. Returns false if there is no recorded step to undo.
+    pub fn step_back(&mut self) -> bool {
+        let Some(history) = self.history.as_mut() else {
+            return false;
+        };
+        let undo = match history.log.pop() {
+            Some(undo) => undo,
+            None => {
+                if !self.replay_segment() {
+                    return false;
+                }
+                self.history.as_mut().unwrap().log.pop().unwrap()
+            }
+        };
+
+        for &(addr, value) in undo.writes.iter().rev() {
+            self.mem[addr] = value;
+        }
+        self.mem.truncate(undo.len);
+        self.ip = undo.ip;
+        self.rb = undo.rb;
+        self.halted = undo.halted;
+        self.fault = undo.fault;
+        for &value in undo.input.iter().rev() {
+            self.input.push_front(value);
+        }
+        for value in undo.output.iter().rev() {
+            if self.output.back() != Some(value) {
+                break;
+            }
+            self.output.pop_back();
+        }
+        true
+    }
+
+    /// Step back until the instruction pointer is `ip`, taking at least one
+    /// step. Returns false, with every recorded step undone, if the computer
+    /// wasn't at `ip` in the recorded history.
+    pub fn run_back_to(&mut self, ip: usize) -> bool {
+        while self.step_back() {
+            if self.ip == ip {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{opcodes::Flow, IntcodeComputer};
+
+    // Output the sum of each input and the previous one:
+    // IN [20]; ADD [20],[21],[22]; OUT [22]; ADD [20],0,[21]; JZ 0,0
+    const PROGRAM: [i64; 18] = [
+        3, 20, 1, 20, 21, 22, 4, 22, 1001, 20, 0, 21, 1106, 0, 0, 0, 0, 0,
+    ];
+
+    #[test]
+    fn step_back() {
+        let mut pc = IntcodeComputer::new(PROGRAM.to_vec());
+        pc.record_history(1000, 1);
+        pc.input.extend([1, 2, 3]);
+        pc.run();
+        assert_eq!(pc.output, [1, 3, 5]);
+        let end = pc.clone();
+
+        // Undo the last OUT and the ADD and IN before it.
+        assert!(pc.run_back_to(0));
+        assert_eq!(pc.output, [1, 3]);
+        assert_eq!(pc.input, [3]);
+        assert_eq!(pc.mem[20], 2);
+        assert_eq!(pc.mem.len(), 23);
+
+        pc.input.push_back(10);
+        pc.run();
+        assert_eq!(pc.output, [1, 3, 5, 13]);
+
+        // Undo everything.
+        assert!(!pc.run_back_to(100));
+        assert_eq!(pc.mem, PROGRAM);
+        assert_eq!(pc.ip, 0);
+        assert_eq!(pc.input, [1, 2, 3, 10]);
+        assert!(pc.output.is_empty());
+
+        pc.input.pop_back();
+        pc.run();
+        assert_eq!(pc.mem, end.mem);
+        assert_eq!(pc.output, end.output);
+    }
+
+    #[test]
+    fn checkpoints() {
+        let mut pc = IntcodeComputer::new(PROGRAM.to_vec());
+        pc.record_history(3, 4);
+        pc.input.extend(1..=10);
+        pc.run();
+        let end = pc.clone();
+
+        // The last 3 full segments and the current one can be undone.
+        let mut steps = 0;
+        while pc.step_back() {
+            steps += 1;
+        }
+        let total = 10 * 5;
+        assert_eq!(steps, 9 + total % 3);
+        assert!(pc.ip < PROGRAM.len());
+
+        while pc.ip != end.ip || pc.input != end.input {
+            pc.step();
+        }
+        assert_eq!(pc.mem, end.mem);
+    }
+
+    #[test]
+    fn step_back_over_fault() {
+        // OUT 1; then an invalid opcode.
+        let mut pc = IntcodeComputer::new(vec![104, 1, 77]);
+        pc.record_history(1, 10);
+        pc.run();
+        assert!(pc.fault.is_some());
+
+        assert!(pc.step_back());
+        assert_eq!(pc.fault, None);
+        assert_eq!(pc.ip, 2);
+        assert!(pc.step_back());
+        assert_eq!(pc.ip, 0);
+        assert!(pc.output.is_empty());
+    }
+
+    #[test]
+    fn drained_output() {
+        let mut pc = IntcodeComputer::new(PROGRAM.to_vec());
+        pc.record_history(1000, 1);
+        pc.input.extend([1, 2]);
+        pc.run();
+        assert_eq!(pc.output, [1, 3]);
+
+        // Output the caller already took isn't removed again.
+        pc.output.pop_back();
+        assert!(pc.run_back_to(6));
+        assert_eq!(pc.output, [1]);
+    }
+
+    #[test]
+    fn custom_opcode_inputs() {
+        // Opcode 50 consumes two inputs and outputs their sum.
+        let mut pc = IntcodeComputer::new(vec![50, 99]);
+        pc.register(50, 0, &[], |pc, _| {
+            match (pc.input.pop_front(), pc.input.pop_front()) {
+                (Some(a), Some(b)) => {
+                    pc.output.push_back(a + b);
+                    Flow::Next
+                }
+                _ => Flow::Blocked,
+            }
+        });
+        pc.record_history(1000, 1);
+        pc.input.extend([2, 3, 4]);
+        pc.run();
+        assert_eq!(pc.output, [5]);
+
+        assert!(pc.step_back());
+        assert!(pc.step_back());
+        assert_eq!(pc.input, [2, 3, 4]);
+        assert!(pc.output.is_empty());
+    }
+}
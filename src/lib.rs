@@ -1,17 +1,151 @@
-use std::collections::VecDeque;
+//! An Intcode computer and tools built around it.
+//!
+//! Without the default `std` feature, only the computer itself is built,
+//! needing just `alloc`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec::Vec};
+use core::{error, fmt, ops::Range};
+use cycle::CycleDetector;
+use history::History;
+use opcodes::{Flow, OpcodeTable, MAX_ARITY};
+use word::Word;
+
+#[cfg(feature = "std")]
+pub mod asm;
+#[cfg(feature = "std")]
+pub mod cheat;
+#[cfg(feature = "std")]
+pub mod compiler;
+#[cfg(feature = "std")]
+pub mod conformance;
+#[cfg(feature = "std")]
+pub mod console;
+mod cycle;
+#[cfg(feature = "std")]
+pub mod dap;
+#[cfg(feature = "std")]
+pub mod device;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod fuzz;
+#[cfg(feature = "std")]
+pub mod gdb;
+mod history;
+#[cfg(feature = "std")]
+pub mod lint;
+pub mod load;
+pub mod opcodes;
+#[cfg(feature = "std")]
+pub mod optimize;
+#[cfg(feature = "std")]
+pub mod outputs;
+#[cfg(feature = "std")]
+pub mod script;
+#[cfg(feature = "std")]
+pub mod specialize;
+#[cfg(feature = "std")]
+pub mod symbolic;
+#[cfg(feature = "std")]
+pub mod transcript;
+#[cfg(feature = "std")]
+pub mod translate;
+pub mod word;
 
 #[derive(Debug, Clone)]
-pub struct IntcodeComputer {
-    pub mem: Vec<i64>,
+pub struct IntcodeComputer<W = i64> {
+    pub mem: Vec<W>,
     pub ip: usize, // instruction pointer
     pub rb: i64,   // relative base
     pub halted: bool,
-    pub input: VecDeque<i64>,
-    pub output: VecDeque<i64>,
+    /// Why the computer stopped at `ip`, if an instruction failed.
+    pub fault: Option<Fault>,
+    pub input: VecDeque<W>,
+    pub output: VecDeque<W>,
+    pub limits: Limits,
+    opcodes: Arc<OpcodeTable<W>>,
+    /// Tracks the states visited by `run` when `Limits::detect_loops` is set.
+    cycle: Option<CycleDetector>,
+    /// Recorded steps, see `record_history`.
+    history: Option<Box<History<W>>>,
 }
 
-#[derive(Debug, Clone, Copy)]
-enum ParameterMode {
+/// Highest address a program may use when `Limits::max_addr` isn't set, so
+/// a stray address faults instead of exhausting memory.
+pub const MAX_ADDR: usize = (1 << 28) - 1;
+
+/// Restrictions on what a program may do, for running untrusted code.
+/// Violating them faults the computer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Highest memory address the program may read or write. Defaults to
+    /// `MAX_ADDR`.
+    pub max_addr: Option<usize>,
+    /// Maximum number of values waiting in `output`.
+    pub max_output: Option<usize>,
+    /// Address ranges the program may not write.
+    pub read_only: Vec<Range<usize>>,
+    /// Make `run` stop when the computer returns to an earlier state without
+    /// input or output in between. Ignored when custom opcodes are
+    /// registered, since their handlers may write memory without
+    /// `IntcodeComputer::store`.
+    pub detect_loops: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// An arithmetic result didn't fit in a word.
+    Overflow,
+    /// An access past `Limits::max_addr`.
+    MemoryLimit { addr: usize },
+    /// An output while `Limits::max_output` values were waiting.
+    OutputLimit,
+    /// A write to a range in `Limits::read_only`.
+    ReadOnly { addr: usize },
+    /// The computer would repeat the last `cycle` steps forever.
+    LoopDetected { cycle: usize },
+    /// The word at `ip` has no opcode in the opcode table.
+    InvalidOpcode { instruction: i64 },
+    /// Parameter `param`, counting from 1, has an unknown mode.
+    InvalidMode { param: usize },
+    /// Parameter `param`, counting from 1, is a write address in immediate
+    /// mode.
+    ImmediateWrite { param: usize },
+    /// A read, write or jump at a negative address.
+    NegativeAddress { addr: i64 },
+    /// An address or relative base offset that doesn't fit in an i64.
+    InvalidAddress,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overflow => write!(f, "Arithmetic overflow"),
+            Self::MemoryLimit { addr } => write!(f, "Address {addr} exceeds the memory limit"),
+            Self::OutputLimit => write!(f, "Output exceeds the output limit"),
+            Self::ReadOnly { addr } => write!(f, "Write to read-only address {addr}"),
+            Self::LoopDetected { cycle } => write!(f, "Infinite loop of {cycle} steps"),
+            Self::InvalidOpcode { instruction } => {
+                write!(f, "Unknown opcode in instruction {instruction}")
+            }
+            Self::InvalidMode { param } => write!(f, "Unknown mode for parameter {param}"),
+            Self::ImmediateWrite { param } => {
+                write!(f, "Parameter {param} is a write address in immediate mode")
+            }
+            Self::NegativeAddress { addr } => write!(f, "Negative address {addr}"),
+            Self::InvalidAddress => write!(f, "Address out of range"),
+        }
+    }
+}
+
+impl error::Error for Fault {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterMode {
     Position,
     Immediate,
     Relative,
@@ -19,190 +153,321 @@ enum ParameterMode {
 
 impl ParameterMode {
     /// Get the parameter mode for the n-th parameter.
-    fn new(mut modes: i64, n: usize) -> Self {
+    pub fn new(modes: i64, n: usize) -> Self {
+        match Self::try_new(modes, n) {
+            Some(mode) => mode,
+            None => panic!("Unknown parameter mode {modes} with n = {n}"),
+        }
+    }
+
+    /// Get the parameter mode for the n-th parameter, or None if the mode
+    /// digit is unknown.
+    pub fn try_new(mut modes: i64, n: usize) -> Option<Self> {
         for _ in 0..n - 1 {
             modes /= 10;
         }
         if modes % 10 == 0 {
-            Self::Position
+            Some(Self::Position)
         } else if modes % 10 == 1 {
-            Self::Immediate
+            Some(Self::Immediate)
         } else if modes % 10 == 2 {
-            Self::Relative
+            Some(Self::Relative)
         } else {
-            panic!("Unknown parameter mode {modes} with n = {n}");
+            None
+        }
+    }
+
+    /// The mode digit used to encode this mode.
+    pub fn digit(self) -> i64 {
+        match self {
+            Self::Position => 0,
+            Self::Immediate => 1,
+            Self::Relative => 2,
         }
     }
 }
 
-impl IntcodeComputer {
+impl<W: Word> IntcodeComputer<W> {
     /// Ensure the memory address `addr` is readable/writable, extending the
     /// memory with zeros when necessary.
-    fn ensure_addr(&mut self, addr: i64) {
-        if self.mem.len() <= addr as usize {
-            self.mem.resize(addr as usize + 1, 0);
+    fn ensure_addr(&mut self, addr: i64) -> Result<(), Fault> {
+        let addr = addr as usize;
+        if addr > self.limits.max_addr.unwrap_or(MAX_ADDR) {
+            return Err(Fault::MemoryLimit { addr });
+        }
+        if self.mem.len() <= addr {
+            if self.mem.try_reserve(addr + 1 - self.mem.len()).is_err() {
+                return Err(Fault::MemoryLimit { addr });
+            }
+            self.mem.resize(addr + 1, W::from(0));
         }
+        Ok(())
     }
 
     /// Read a parameter according to its parameter mode.
-    fn read(&mut self, value: i64, mode: ParameterMode) -> i64 {
-        match mode {
-            ParameterMode::Position => {
-                let addr = value;
-                assert!(addr >= 0, "Position read address should be non-negative");
-                self.ensure_addr(addr);
-                self.mem[addr as usize]
-            }
-            ParameterMode::Immediate => value,
-            ParameterMode::Relative => {
-                let addr = self.rb + value;
-                assert!(addr >= 0, "Relative read address should be non-negative");
-                self.ensure_addr(addr);
-                self.mem[addr as usize]
-            }
+    fn read(&mut self, value: W, mode: ParameterMode) -> Result<W, Fault> {
+        if mode == ParameterMode::Immediate {
+            return Ok(value);
         }
+        let addr = self.absolute(value, mode)?;
+        self.ensure_addr(addr)?;
+        Ok(self.mem[addr as usize])
     }
 
-    /// Write `value` to memory. The address is looked up according to the
-    /// parameter mode, which cannot be immediate. `value` is always immediate.
-    fn write(&mut self, addr: i64, mode: ParameterMode, value: i64) {
-        match mode {
-            ParameterMode::Position => {
-                assert!(addr >= 0, "Position write address should be non-negative");
-                self.ensure_addr(addr);
-                self.mem[addr as usize] = value;
-            }
-            ParameterMode::Immediate => {
-                panic!("Write address parameter mode cannot be immediate");
-            }
-            ParameterMode::Relative => {
-                let addr = self.rb + addr;
-                assert!(addr >= 0, "Relative write address should be non-negative");
-                self.ensure_addr(addr);
-                self.mem[addr as usize] = value;
+    /// The absolute address a position or relative mode parameter refers to.
+    fn absolute(&self, value: W, mode: ParameterMode) -> Result<i64, Fault> {
+        let addr = Self::to_addr(value)?;
+        let addr = match mode {
+            ParameterMode::Relative => self.rb.checked_add(addr).ok_or(Fault::InvalidAddress)?,
+            _ => addr,
+        };
+        if addr < 0 {
+            return Err(Fault::NegativeAddress { addr });
+        }
+        Ok(addr)
+    }
+
+    /// Convert a word used as an address or offset.
+    fn to_addr(value: W) -> Result<i64, Fault> {
+        value.to_i64().ok_or(Fault::InvalidAddress)
+    }
+
+    /// The word at `addr`, without growing memory. Words past the end are
+    /// zero.
+    fn fetch(&self, addr: usize) -> W {
+        self.mem.get(addr).copied().unwrap_or(W::from(0))
+    }
+
+    /// Write `value` to memory at the absolute address `addr`.
+    pub fn store(&mut self, addr: W, value: W) -> Result<(), Fault> {
+        let addr = Self::to_addr(addr)?;
+        if addr < 0 {
+            return Err(Fault::NegativeAddress { addr });
+        }
+        let index = addr as usize;
+        if self
+            .limits
+            .read_only
+            .iter()
+            .any(|range| range.contains(&index))
+        {
+            return Err(Fault::ReadOnly { addr: index });
+        }
+        self.ensure_addr(addr)?;
+        if let Some(cycle) = &mut self.cycle {
+            cycle.store(index, self.mem[index], value);
+        }
+        if let Some(history) = &mut self.history {
+            history.writes.push((index, self.mem[index]));
+        }
+        self.mem[index] = value;
+        Ok(())
+    }
+
+    /// Append `value` to `output`.
+    pub fn emit(&mut self, value: W) -> Result<(), Fault> {
+        if let Some(max_output) = self.limits.max_output {
+            if self.output.len() >= max_output {
+                return Err(Fault::OutputLimit);
             }
         }
+        self.output.push_back(value);
+        Ok(())
+    }
+
+    /// Make the currently loaded memory image read-only.
+    pub fn protect_image(&mut self) {
+        self.limits.read_only.push(0..self.mem.len());
     }
 
     /// Create a new IntcodeComputer intialized with `memory`.
-    pub fn new(memory: Vec<i64>) -> Self {
+    pub fn with_memory(memory: Vec<W>) -> Self {
+        Self::with_opcodes(memory, W::builtin_opcodes())
+    }
+
+    /// Create a new IntcodeComputer intialized with `memory` that executes
+    /// the opcodes in `opcodes`.
+    pub fn with_opcodes(memory: Vec<W>, opcodes: Arc<OpcodeTable<W>>) -> Self {
         Self {
             mem: memory,
             ip: 0,
             rb: 0,
             halted: false,
+            fault: None,
             input: VecDeque::new(),
             output: VecDeque::new(),
+            limits: Limits::default(),
+            opcodes,
+            cycle: None,
+            history: None,
         }
     }
 
-    /// Create a new IntcodeComputer whose memory is intialized from the contents of `file`.
-    pub fn from_file(file: &str) -> Self {
-        let bytes = std::fs::read(file).unwrap();
-        let text = String::from_utf8(bytes).unwrap();
-        let memory: Vec<i64> = text
-            .split_terminator(',')
-            .map(|s| s.trim().parse().unwrap())
-            .collect();
-        Self::new(memory)
+    /// Add or replace an opcode of this computer. See
+    /// [`OpcodeTable::register`].
+    pub fn register(
+        &mut self,
+        opcode: i64,
+        arity: usize,
+        writes: &[usize],
+        handler: impl Fn(&mut IntcodeComputer<W>, &[W]) -> Flow + Send + Sync + 'static,
+    ) {
+        Arc::make_mut(&mut self.opcodes).register(opcode, arity, writes, handler);
+    }
+
+    /// The opcodes this computer executes.
+    pub fn opcodes(&self) -> &Arc<OpcodeTable<W>> {
+        &self.opcodes
+    }
+
+    /// Write the contents of memory to `file` in the format `from_file` reads.
+    #[cfg(feature = "std")]
+    pub fn save(&self, file: &str) {
+        let text = self
+            .mem
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>();
+        std::fs::write(file, text.join(",") + "\n").unwrap();
     }
 
     /// Execute a single instruction. If the instruction couldn't be executed
-    /// because the computer is waiting on input, returns false; otherwise
-    /// returns true.
+    /// because the computer is waiting on input or has faulted, returns false;
+    /// otherwise returns true.
     pub fn step(&mut self) -> bool {
+        if self.fault.is_some() {
+            return false;
+        }
+        let Some(undo) = self.begin_undo() else {
+            return self.execute();
+        };
+        let (input_len, output_len) = (self.input.len(), self.output.len());
+        let executed = self.execute();
+        if executed || self.fault.is_some() {
+            self.end_undo(undo, input_len, output_len);
+        }
+        executed
+    }
+
+    fn execute(&mut self) -> bool {
         let ip = self.ip;
-        let opcode = self.mem[ip] % 100;
-        let modes = self.mem[ip] / 100;
-
-        if opcode == 99 {
-            self.halted = true;
-        } else if opcode == 1 {
-            // ADD lhs,rhs,addr
-            let lhs = self.read(self.mem[ip + 1], ParameterMode::new(modes, 1));
-            let rhs = self.read(self.mem[ip + 2], ParameterMode::new(modes, 2));
-            self.write(self.mem[ip + 3], ParameterMode::new(modes, 3), lhs + rhs);
-            self.ip += 4;
-        } else if opcode == 2 {
-            // MUL lhs,rhs,addr
-            let lhs = self.read(self.mem[ip + 1], ParameterMode::new(modes, 1));
-            let rhs = self.read(self.mem[ip + 2], ParameterMode::new(modes, 2));
-            self.write(self.mem[ip + 3], ParameterMode::new(modes, 3), lhs * rhs);
-            self.ip += 4;
-        } else if opcode == 3 {
-            // INPUT addr
-            if let Some(value) = self.input.pop_front() {
-                self.write(self.mem[ip + 1], ParameterMode::new(modes, 1), value);
-                self.ip += 2;
-            } else {
-                return false;
-            }
-        } else if opcode == 4 {
-            // OUTPUT value
-            let value = self.read(self.mem[ip + 1], ParameterMode::new(modes, 1));
-            self.output.push_back(value);
-            self.ip += 2;
-        } else if opcode == 5 {
-            // JNZ cond,addr
-            let cond = self.read(self.mem[ip + 1], ParameterMode::new(modes, 1));
-            if cond != 0 {
-                let addr = self.read(self.mem[ip + 2], ParameterMode::new(modes, 2));
-                assert!(addr >= 0, "JNZ address should be non-negative");
-                self.ip = addr as usize;
-            } else {
-                self.ip += 3;
+        let instruction = self.fetch(ip).to_i64().unwrap_or(-1);
+        let opcode = instruction % 100;
+        let modes = instruction / 100;
+
+        let opcodes = Arc::clone(&self.opcodes);
+        let Some(spec) = opcodes.get(opcode).filter(|_| instruction >= 0) else {
+            self.fault = Some(Fault::InvalidOpcode { instruction });
+            return false;
+        };
+        let mut params = [W::from(0); MAX_ARITY];
+        for n in 1..=spec.arity {
+            let value = self.fetch(ip + n);
+            let param = match ParameterMode::try_new(modes, n) {
+                None => Err(Fault::InvalidMode { param: n }),
+                Some(ParameterMode::Immediate) if spec.writes.contains(&(n - 1)) => {
+                    Err(Fault::ImmediateWrite { param: n })
+                }
+                Some(mode) if spec.writes.contains(&(n - 1)) => {
+                    self.absolute(value, mode).map(W::from)
+                }
+                Some(mode) => self.read(value, mode),
+            };
+            match param {
+                Ok(param) => params[n - 1] = param,
+                Err(fault) => {
+                    self.fault = Some(fault);
+                    return false;
+                }
             }
-        } else if opcode == 6 {
-            // JZ cond,addr
-            let cond = self.read(self.mem[ip + 1], ParameterMode::new(modes, 1));
-            if cond == 0 {
-                let addr = self.read(self.mem[ip + 2], ParameterMode::new(modes, 2));
-                assert!(addr >= 0, "JZ address should be non-negative");
-                self.ip = addr as usize;
-            } else {
-                self.ip += 3;
+        }
+
+        match (spec.handler)(self, &params[..spec.arity]) {
+            Flow::Next => self.ip += 1 + spec.arity,
+            Flow::Jump(addr) => self.ip = addr,
+            Flow::Blocked => return false,
+            Flow::Halt => self.halted = true,
+            Flow::Fault(fault) => {
+                self.fault = Some(fault);
+                return false;
             }
-        } else if opcode == 7 {
-            // LT lhs,rhs,addr
-            let lhs = self.read(self.mem[ip + 1], ParameterMode::new(modes, 1));
-            let rhs = self.read(self.mem[ip + 2], ParameterMode::new(modes, 2));
-            if lhs < rhs {
-                self.write(self.mem[ip + 3], ParameterMode::new(modes, 3), 1);
-            } else {
-                self.write(self.mem[ip + 3], ParameterMode::new(modes, 3), 0);
+        }
+        true
+    }
+
+    /// Execute until the computer blocks on input, halts or faults.
+    pub fn run(&mut self) {
+        self.run_observed(|_, _| {});
+    }
+
+    /// Like `run`, calling `observe` after every executed step with the
+    /// lengths of the input and output queues before it.
+    pub(crate) fn run_observed(&mut self, mut observe: impl FnMut(&Self, (usize, usize))) {
+        if !self.limits.detect_loops || self.opcodes.custom {
+            loop {
+                let io = (self.input.len(), self.output.len());
+                if self.halted || !self.step() {
+                    return;
+                }
+                observe(self, io);
             }
-            self.ip += 4;
-        } else if opcode == 8 {
-            // EQ lhs,rhs,addr
-            let lhs = self.read(self.mem[ip + 1], ParameterMode::new(modes, 1));
-            let rhs = self.read(self.mem[ip + 2], ParameterMode::new(modes, 2));
-            if lhs == rhs {
-                self.write(self.mem[ip + 3], ParameterMode::new(modes, 3), 1);
-            } else {
-                self.write(self.mem[ip + 3], ParameterMode::new(modes, 3), 0);
+        }
+
+        self.cycle = Some(CycleDetector::new(&self.mem, self.ip, self.rb));
+        let mut io = (self.input.len(), self.output.len());
+        while !self.halted && self.step() {
+            observe(self, io);
+            let cycle = self.cycle.as_mut().unwrap();
+            if io != (self.input.len(), self.output.len()) {
+                io = (self.input.len(), self.output.len());
+                cycle.reset(self.ip, self.rb);
+            } else if let Some(cycle) = cycle.check(self.ip, self.rb) {
+                self.fault = Some(Fault::LoopDetected { cycle });
             }
-            self.ip += 4;
-        } else if opcode == 9 {
-            // RB delta
-            let delta = self.read(self.mem[ip + 1], ParameterMode::new(modes, 1));
-            self.rb += delta;
-            self.ip += 2;
-        } else {
-            panic!("Unknown opcode {opcode} at rip = {ip}");
         }
+        self.cycle = None;
+    }
+}
 
-        true
+impl IntcodeComputer {
+    /// Create a new IntcodeComputer intialized with `memory`.
+    pub fn new(memory: Vec<i64>) -> Self {
+        Self::with_memory(memory)
     }
 
-    /// Execute until the computer blocks on input or halts.
-    pub fn run(&mut self) {
-        while !self.halted && self.step() {}
+    /// Create a new IntcodeComputer whose memory is intialized from the contents of `file`,
+    /// program text or a binary image. Panics if the file can't be loaded; use
+    /// `load::read_file` to handle errors.
+    #[cfg(feature = "std")]
+    pub fn from_file(file: &str) -> Self {
+        match load::read_file(file) {
+            Ok(memory) => Self::new(memory),
+            Err(error) => panic!("{file}: {error}"),
+        }
+    }
+
+    /// Copy this computer to one computing with words of type `W`. Opcodes
+    /// registered on this computer are replaced by the builtin ones.
+    pub fn convert<W: Word>(&self) -> IntcodeComputer<W> {
+        IntcodeComputer {
+            mem: self.mem.iter().map(|&value| value.into()).collect(),
+            ip: self.ip,
+            rb: self.rb,
+            halted: self.halted,
+            fault: self.fault,
+            input: self.input.iter().map(|&value| value.into()).collect(),
+            output: self.output.iter().map(|&value| value.into()).collect(),
+            limits: self.limits.clone(),
+            cycle: None,
+            history: None,
+            opcodes: W::builtin_opcodes(),
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::IntcodeComputer;
+    use super::{Fault, Flow, IntcodeComputer};
 
     #[test]
     fn instruction_halt() {
@@ -415,4 +680,146 @@ mod test {
         pc.step();
         assert_eq!(pc.output, &[30]);
     }
+
+    #[test]
+    fn memory_limit() {
+        let mut pc = IntcodeComputer::new(vec![1101, 1, 2, 1000000000000, 99]);
+        pc.limits.max_addr = Some(1 << 20);
+        pc.run();
+        assert_eq!(
+            pc.fault,
+            Some(Fault::MemoryLimit {
+                addr: 1000000000000
+            })
+        );
+        assert_eq!(pc.ip, 0);
+        assert!(!pc.step());
+
+        let mut pc = IntcodeComputer::new(vec![4, 100, 99]);
+        pc.limits.max_addr = Some(99);
+        pc.run();
+        assert_eq!(pc.fault, Some(Fault::MemoryLimit { addr: 100 }));
+        assert_eq!(pc.mem.len(), 3);
+    }
+
+    #[test]
+    fn output_limit() {
+        // OUT 1; JZ 0,0
+        let mut pc = IntcodeComputer::new(vec![104, 1, 1106, 0, 0]);
+        pc.limits.max_output = Some(3);
+        pc.run();
+        assert_eq!(pc.fault, Some(Fault::OutputLimit));
+        assert_eq!(pc.output, [1, 1, 1]);
+    }
+
+    #[test]
+    fn read_only() {
+        // ADD 1,1,[0]
+        let mut pc = IntcodeComputer::new(vec![1101, 1, 1, 0, 99]);
+        pc.protect_image();
+        pc.run();
+        assert_eq!(pc.fault, Some(Fault::ReadOnly { addr: 0 }));
+        assert_eq!(pc.mem[0], 1101);
+
+        // Memory past the image stays writable.
+        let mut pc = IntcodeComputer::new(vec![1101, 1, 1, 5, 99]);
+        pc.protect_image();
+        pc.run();
+        assert!(pc.halted);
+        assert_eq!(pc.mem[5], 2);
+    }
+
+    #[test]
+    fn invalid_programs() {
+        let fault = |program: Vec<i64>| {
+            let mut pc = IntcodeComputer::new(program);
+            pc.run();
+            assert!(!pc.halted);
+            pc.fault
+        };
+        assert_eq!(
+            fault(vec![77]),
+            Some(Fault::InvalidOpcode { instruction: 77 })
+        );
+        assert_eq!(
+            fault(vec![-1]),
+            Some(Fault::InvalidOpcode { instruction: -1 })
+        );
+        assert_eq!(fault(vec![304, 0]), Some(Fault::InvalidMode { param: 1 }));
+        assert_eq!(
+            fault(vec![11101, 1, 1, 0]),
+            Some(Fault::ImmediateWrite { param: 3 })
+        );
+        assert_eq!(
+            fault(vec![4, -1]),
+            Some(Fault::NegativeAddress { addr: -1 })
+        );
+        assert_eq!(
+            fault(vec![109, -5, 203, 1]),
+            Some(Fault::NegativeAddress { addr: -4 })
+        );
+        assert_eq!(
+            fault(vec![1101, 1, 1, -2]),
+            Some(Fault::NegativeAddress { addr: -2 })
+        );
+        assert_eq!(
+            fault(vec![1105, 1, -3]),
+            Some(Fault::NegativeAddress { addr: -3 })
+        );
+        assert_eq!(
+            fault(vec![109, i64::MAX, 204, 1]),
+            Some(Fault::InvalidAddress)
+        );
+        assert_eq!(
+            fault(vec![109, i64::MAX, 109, 1, 99]),
+            Some(Fault::Overflow)
+        );
+        assert_eq!(
+            fault(vec![1101, 1, 0, 1 << 40, 99]),
+            Some(Fault::MemoryLimit { addr: 1 << 40 })
+        );
+        // Running off the end, or jumping past it, reads opcode 0.
+        assert_eq!(
+            fault(vec![104, 1]),
+            Some(Fault::InvalidOpcode { instruction: 0 })
+        );
+        let mut pc = IntcodeComputer::new(vec![1105, 1, 1000000000000]);
+        pc.run();
+        assert_eq!(pc.fault, Some(Fault::InvalidOpcode { instruction: 0 }));
+        assert_eq!(pc.mem.len(), 3);
+    }
+
+    #[test]
+    fn detect_loops() {
+        // Count [20] from 0 to 4, then start over.
+        // ADD [20],1,[20]; EQ [20],4,[21]; JZ [21],0; ADD 0,0,[20]; JZ 0,0
+        let mut pc = IntcodeComputer::new(vec![
+            1001, 20, 1, 20, 1008, 20, 4, 21, 1006, 21, 0, 1101, 0, 0, 20, 1106, 0, 0,
+        ]);
+        pc.limits.detect_loops = true;
+        pc.run();
+        assert_eq!(pc.fault, Some(Fault::LoopDetected { cycle: 14 }));
+
+        // Loops that do I/O aren't reported.
+        let mut pc = IntcodeComputer::new(vec![3, 20, 104, 0, 1106, 0, 0]);
+        pc.limits.detect_loops = true;
+        pc.input.extend([1; 100]);
+        pc.run();
+        assert_eq!(pc.fault, None);
+        assert_eq!(pc.output.len(), 100);
+
+        // A custom opcode writing memory directly isn't mistaken for a loop.
+        // INC [20]; EQ [20],5,[21]; JZ [21],0; HALT
+        let mut program = vec![10, 20, 1008, 20, 5, 21, 1006, 21, 0, 99];
+        program.resize(22, 0);
+        let mut pc = IntcodeComputer::new(program);
+        pc.register(10, 1, &[0], |pc, p| {
+            pc.mem[p[0] as usize] += 1;
+            Flow::Next
+        });
+        pc.limits.detect_loops = true;
+        pc.run();
+        assert!(pc.halted);
+        assert_eq!(pc.fault, None);
+    }
 }
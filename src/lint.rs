@@ -0,0 +1,517 @@
+//! Static checks of Intcode programs.
+//!
+//! The linter follows the code reachable from address 0 through fall-through
+//! and literal jump targets. Jumps through computed addresses can't be
+//! followed, so when the program has any, it also starts from return
+//! addresses: constants stored by `ADD value, 0, dst` or `MUL value, 1, dst`
+//! just before a jump, pointing just past the jump.
+//! Like the optimiser, it assumes that computed addresses never touch
+//! reachable code, so code that isn't written to is constant.
+//!
+//! The relative base is tracked as a constant along each path. Where paths
+//! with different values meet only a lower bound is kept, and if the bound
+//! keeps falling the base becomes unknown. Code after a call continues with
+//! the base from before the call, assuming that the callee restores it.
+
+use crate::{
+    asm::{Instruction, Opcode, Param},
+    ParameterMode,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The program may misbehave.
+    Warning,
+    /// Executing the instruction fails.
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// A reachable word isn't a known opcode.
+    InvalidOpcode { word: i64 },
+    /// Parameter `param` (from 0) has an unknown mode digit.
+    InvalidMode { param: usize },
+    /// The write address parameter is in immediate mode.
+    ImmediateWrite { param: usize },
+    /// The instruction writes to `target`, in reachable code.
+    SelfModifying { target: usize },
+    /// A jump to a literal target outside the program.
+    JumpOutside { target: i64 },
+    /// The relative base may become negative, to `rb` if it is known.
+    NegativeRelativeBase { rb: Option<i64> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Finding {
+    pub addr: usize,
+    pub severity: Severity,
+    pub lint: Lint,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidOpcode { word } => write!(f, "invalid opcode {word}"),
+            Self::InvalidMode { param } => write!(f, "invalid mode for parameter {param}"),
+            Self::ImmediateWrite { param } => {
+                write!(f, "parameter {param} is written in immediate mode")
+            }
+            Self::SelfModifying { target } => write!(f, "writes to code at {target}"),
+            Self::JumpOutside { target } => write!(f, "jumps to {target}, outside the program"),
+            Self::NegativeRelativeBase { rb: Some(rb) } => {
+                write!(f, "relative base becomes {rb}")
+            }
+            Self::NegativeRelativeBase { rb: None } => {
+                write!(f, "relative base may become negative")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.addr, self.severity, self.lint)
+    }
+}
+
+impl Lint {
+    pub fn severity(self) -> Severity {
+        match self {
+            Self::InvalidOpcode { .. }
+            | Self::InvalidMode { .. }
+            | Self::ImmediateWrite { .. }
+            | Self::JumpOutside { .. }
+            | Self::NegativeRelativeBase { rb: Some(_) } => Severity::Error,
+            Self::SelfModifying { .. } | Self::NegativeRelativeBase { rb: None } => {
+                Severity::Warning
+            }
+        }
+    }
+}
+
+/// Decode the instruction at `addr` like `Instruction::decode`, but with an
+/// immediate-mode write address allowed, or return what's wrong with it.
+fn decode(program: &[i64], addr: usize) -> Result<Instruction, Lint> {
+    let word = program.get(addr).copied().unwrap_or(0);
+    let Some(opcode) = Opcode::from_code(word % 100).filter(|_| word >= 0) else {
+        return Err(Lint::InvalidOpcode { word });
+    };
+    let mut instruction = Instruction::new(opcode, vec![Param::position(0); opcode.arity()]);
+    for (n, param) in instruction.params.iter_mut().enumerate() {
+        param.mode =
+            ParameterMode::try_new(word / 100, n + 1).ok_or(Lint::InvalidMode { param: n })?;
+        param.value = program.get(addr + n + 1).copied().unwrap_or(0);
+    }
+    Ok(instruction)
+}
+
+/// The constant `instruction` stores, if it is `ADD value, 0, dst` or
+/// `MUL value, 1, dst` (or with the operands swapped).
+fn stored_constant(instruction: &Instruction) -> Option<i64> {
+    let identity = match instruction.opcode {
+        Opcode::Add => 0,
+        Opcode::Mul => 1,
+        _ => return None,
+    };
+    let immediate = |n: usize| {
+        let param = instruction.params[n];
+        (param.mode == ParameterMode::Immediate).then_some(param.value)
+    };
+    match (immediate(0), immediate(1)) {
+        (Some(value), Some(other)) | (Some(other), Some(value)) if other == identity => Some(value),
+        _ => None,
+    }
+}
+
+/// What is known about the relative base.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rb {
+    Known(i64),
+    AtLeast(i64),
+    Unknown,
+}
+
+impl Rb {
+    /// Combine this state with `other`, from another path. A lower bound
+    /// that falls further is dropped, so that loops reach a fixed point.
+    fn join(self, other: Rb) -> Rb {
+        match (self, other) {
+            _ if self == other => self,
+            (Rb::Known(a), Rb::Known(b) | Rb::AtLeast(b)) => Rb::AtLeast(a.min(b)),
+            (Rb::AtLeast(a), Rb::Known(b) | Rb::AtLeast(b)) if b >= a => self,
+            _ => Rb::Unknown,
+        }
+    }
+
+    /// The relative base after adding `delta`.
+    fn adjust(self, delta: i64) -> Rb {
+        match self {
+            Rb::Known(rb) => Rb::Known(rb.saturating_add(delta)),
+            Rb::AtLeast(rb) => Rb::AtLeast(rb.saturating_add(delta)),
+            Rb::Unknown => Rb::Unknown,
+        }
+    }
+}
+
+struct Linter<'a> {
+    program: &'a [i64],
+    /// Words written by the code, whose values aren't known statically.
+    written: BTreeSet<usize>,
+    /// Decoded reachable instructions, and the relative base on entry.
+    code: BTreeMap<usize, (Instruction, Rb)>,
+    findings: BTreeMap<(usize, usize), Finding>,
+    computed_jumps: bool,
+    /// Return addresses stored by reachable calls, with the relative base at
+    /// the call.
+    returns: BTreeMap<usize, Rb>,
+}
+
+impl<'a> Linter<'a> {
+    fn new(program: &'a [i64], written: BTreeSet<usize>) -> Self {
+        Self {
+            program,
+            written,
+            code: BTreeMap::new(),
+            findings: BTreeMap::new(),
+            computed_jumps: false,
+            returns: BTreeMap::new(),
+        }
+    }
+
+    fn report(&mut self, addr: usize, lint: Lint) {
+        // Keep one finding of each kind per address.
+        let kind = match lint {
+            Lint::InvalidOpcode { .. } | Lint::InvalidMode { .. } => 0,
+            Lint::ImmediateWrite { .. } => 1,
+            Lint::SelfModifying { .. } => 2,
+            Lint::JumpOutside { .. } => 3,
+            Lint::NegativeRelativeBase { .. } => 4,
+        };
+        let severity = lint.severity();
+        self.findings.insert(
+            (addr, kind),
+            Finding {
+                addr,
+                severity,
+                lint,
+            },
+        );
+    }
+
+    /// The value of parameter `n` (from 0) of the instruction at `addr`, if
+    /// it is an immediate or reads a word of reachable code, and the code
+    /// doesn't write to the parameter or the word.
+    fn constant(&self, addr: usize, instruction: &Instruction, n: usize) -> Option<i64> {
+        let param = instruction.params[n];
+        if self.written.contains(&(addr + 1 + n)) {
+            return None;
+        }
+        let word = match param.mode {
+            ParameterMode::Immediate => addr + 1 + n,
+            ParameterMode::Position => {
+                let word = usize::try_from(param.value).ok()?;
+                let (start, (code, _)) = self.code.range(..=word).next_back()?;
+                (word < start + code.size()).then_some(word)?
+            }
+            ParameterMode::Relative => return None,
+        };
+        (!self.written.contains(&word)).then(|| self.program.get(word).copied().unwrap_or(0))
+    }
+
+    /// Follow the code from the `pending` addresses until the relative base
+    /// is stable at every instruction.
+    fn explore(&mut self, mut pending: Vec<(usize, Rb)>) {
+        while let Some((addr, rb)) = pending.pop() {
+            let rb = match self.code.get(&addr) {
+                Some(&(_, old)) if old.join(rb) == old => continue,
+                Some(&(_, old)) => old.join(rb),
+                None => rb,
+            };
+            let instruction = match decode(self.program, addr) {
+                Ok(instruction) => instruction,
+                // The code writes the instruction before it runs.
+                Err(_) if self.written.contains(&addr) => continue,
+                Err(lint) => {
+                    self.report(addr, lint);
+                    continue;
+                }
+            };
+
+            let params = &instruction.params;
+            if let Some(i) = instruction.opcode.write_param() {
+                if params[i].mode == ParameterMode::Immediate {
+                    self.report(addr, Lint::ImmediateWrite { param: i });
+                }
+            }
+            if let Some(value) = stored_constant(&instruction) {
+                let jump = addr + instruction.size();
+                if let Ok(Instruction {
+                    opcode: Opcode::Jnz | Opcode::Jz,
+                    ..
+                }) = decode(self.program, jump)
+                {
+                    if value == jump as i64 + 3 {
+                        let rb = self
+                            .returns
+                            .get(&(value as usize))
+                            .map_or(rb, |&old| old.join(rb));
+                        self.returns.insert(value as usize, rb);
+                    }
+                }
+            }
+
+            let next = addr + instruction.size();
+            match instruction.opcode {
+                Opcode::Halt => {}
+                Opcode::Rb => {
+                    let rb = match self.constant(addr, &instruction, 0) {
+                        Some(delta) => rb.adjust(delta),
+                        None => Rb::Unknown,
+                    };
+                    pending.push((next, rb));
+                }
+                Opcode::Jnz | Opcode::Jz => {
+                    let taken = self
+                        .constant(addr, &instruction, 0)
+                        .map(|cond| (cond != 0) == (instruction.opcode == Opcode::Jnz));
+                    if taken != Some(false) {
+                        match self.constant(addr, &instruction, 1) {
+                            None => self.computed_jumps = true,
+                            Some(target) if (0..self.program.len() as i64).contains(&target) => {
+                                pending.push((target as usize, rb));
+                            }
+                            Some(target) => self.report(addr, Lint::JumpOutside { target }),
+                        }
+                    }
+                    if taken != Some(true) {
+                        pending.push((next, rb));
+                    }
+                }
+                _ => pending.push((next, rb)),
+            }
+            self.code.insert(addr, (instruction, rb));
+        }
+    }
+
+    /// Explore from address 0 and, if the code jumps to computed addresses,
+    /// from the return addresses of calls.
+    fn explore_all(&mut self) {
+        self.explore(vec![(0, Rb::Known(0))]);
+        let mut entered = BTreeMap::new();
+        while self.computed_jumps {
+            let entries: Vec<_> = self
+                .returns
+                .iter()
+                .map(|(&addr, &rb)| (addr, rb))
+                .filter(|&(addr, rb)| entered.insert(addr, rb) != Some(rb))
+                .collect();
+            if entries.is_empty() {
+                break;
+            }
+            self.explore(entries);
+        }
+    }
+
+    /// The address the instruction at `addr` writes to, if it is known.
+    fn write_target(&self, addr: usize) -> Option<usize> {
+        let (instruction, rb) = &self.code[&addr];
+        let param = instruction.params[instruction.opcode.write_param()?];
+        let target = match (param.mode, rb) {
+            (ParameterMode::Position, _) => param.value,
+            (ParameterMode::Relative, Rb::Known(rb)) => rb.saturating_add(param.value),
+            _ => return None,
+        };
+        usize::try_from(target).ok()
+    }
+}
+
+/// Explore `program` until the words written by its code are known, since
+/// writes to code change what it does.
+fn analyze(program: &[i64]) -> Linter<'_> {
+    let mut linter = Linter::new(program, BTreeSet::new());
+    loop {
+        linter.explore_all();
+        let mut written = linter.written.clone();
+        written.extend(
+            linter
+                .code
+                .keys()
+                .filter_map(|&addr| linter.write_target(addr)),
+        );
+        if written == linter.written {
+            return linter;
+        }
+        linter = Linter::new(program, written);
+    }
+}
+
+/// The reachable instructions of `program`, as found by `lint`.
+pub(crate) fn reachable(program: &[i64]) -> BTreeMap<usize, Instruction> {
+    analyze(program)
+        .code
+        .into_iter()
+        .map(|(addr, (instruction, _))| (addr, instruction))
+        .collect()
+}
+
+/// Check the reachable code of `program`. Findings are ordered by address.
+pub fn lint(program: &[i64]) -> Vec<Finding> {
+    let mut linter = analyze(program);
+
+    let mut code_words = BTreeSet::new();
+    for (&addr, (instruction, _)) in &linter.code {
+        code_words.extend(addr..addr + instruction.size());
+    }
+    let mut lints = vec![];
+    for (&addr, &(ref instruction, rb)) in &linter.code {
+        if instruction.opcode == Opcode::Rb {
+            match linter.constant(addr, instruction, 0) {
+                Some(delta) => match rb.adjust(delta) {
+                    Rb::Known(rb) if rb < 0 => {
+                        lints.push((addr, Lint::NegativeRelativeBase { rb: Some(rb) }))
+                    }
+                    Rb::AtLeast(rb) if rb < 0 => {
+                        lints.push((addr, Lint::NegativeRelativeBase { rb: None }))
+                    }
+                    Rb::Unknown if delta < 0 => {
+                        lints.push((addr, Lint::NegativeRelativeBase { rb: None }))
+                    }
+                    _ => {}
+                },
+                None => lints.push((addr, Lint::NegativeRelativeBase { rb: None })),
+            }
+        }
+        if let Some(target) = linter.write_target(addr) {
+            if code_words.contains(&target) {
+                lints.push((addr, Lint::SelfModifying { target }));
+            }
+        }
+    }
+    for (addr, lint) in lints {
+        linter.report(addr, lint);
+    }
+    linter.findings.into_values().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asm::assemble;
+
+    fn findings(source: &str) -> Vec<(usize, Lint)> {
+        let program = assemble(source).unwrap().program;
+        lint(&program)
+            .into_iter()
+            .map(|finding| (finding.addr, finding.lint))
+            .collect()
+    }
+
+    #[test]
+    fn clean_program() {
+        assert_eq!(
+            findings(
+                "
+                       RB 100
+                loop:  IN [rb-1]
+                       OUT [rb-1]
+                       JNZ [rb-1], loop
+                       HALT
+                "
+            ),
+            []
+        );
+    }
+
+    #[test]
+    fn lints() {
+        let program = [
+            11101, 1, 2, 3, // ADD 1, 2, 3: immediate write
+            1, 0, 0, 9, // ADD [0], [0], [9]: writes the next instruction
+            3, 20, // IN [20]
+            109, -1, // RB -1
+            1105, 1, 40, // JNZ 1, 40
+        ];
+        let result = lint(&program);
+        let lints: Vec<_> = result.iter().map(|f| (f.addr, f.lint)).collect();
+        assert_eq!(
+            lints,
+            [
+                (0, Lint::ImmediateWrite { param: 2 }),
+                (4, Lint::SelfModifying { target: 9 }),
+                (10, Lint::NegativeRelativeBase { rb: Some(-1) }),
+                (12, Lint::JumpOutside { target: 40 }),
+            ]
+        );
+        assert_eq!(
+            result[0].to_string(),
+            "0: error: parameter 2 is written in immediate mode"
+        );
+        assert_eq!(result[1].severity, Severity::Warning);
+
+        // Only reachable words are decoded.
+        assert_eq!(lint(&[1105, 1, 4, 77, 99]), []);
+        assert_eq!(
+            lint(&[1106, 0, 3, 77, 40399])
+                .iter()
+                .map(|f| f.lint)
+                .collect::<Vec<_>>(),
+            [Lint::InvalidOpcode { word: 77 }]
+        );
+        assert_eq!(
+            lint(&[1106, 1, 3, 30001])
+                .iter()
+                .map(|f| f.lint)
+                .collect::<Vec<_>>(),
+            [Lint::InvalidMode { param: 2 }]
+        );
+    }
+
+    #[test]
+    fn relative_base() {
+        // Paths meeting with different bases make the base unknown, and
+        // lowering an unknown base may make it negative.
+        assert_eq!(
+            findings(
+                "
+                       IN [x]
+                       JZ [x], skip
+                       RB 5
+                skip:  RB -2
+                       HALT
+                x:     DATA 0
+                "
+            ),
+            [(7, Lint::NegativeRelativeBase { rb: None })]
+        );
+        // A return through a computed jump continues with the base from
+        // before the call.
+        assert_eq!(
+            findings(
+                "
+                       RB 20
+                       ADD ret, 0, [rb+0]
+                       JZ 0, func
+                ret:   RB -20
+                       HALT
+                func:  JZ 0, [rb+0]
+                       DATA 77
+                "
+            ),
+            []
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! Loading programs from text and from binary images.
+//!
+//! Program text is integers separated by commas. Newlines also separate
+//! values, a comma may end a line and `#` starts a comment running to the
+//! end of the line.
+//!
+//! A binary image is the magic bytes `INTCODE\0`, the number of values as a
+//! little-endian u64, the values as little-endian i64s and the CRC-32 of the
+//! values' bytes as a little-endian u32. Images are much faster to load than
+//! text for large programs.
+
+use alloc::{string::String, vec::Vec};
+use core::{error, fmt};
+
+/// The first bytes of a binary image.
+pub const MAGIC: &[u8; 8] = b"INTCODE\0";
+
+#[derive(Debug)]
+pub enum LoadError {
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    /// The text isn't valid UTF-8 from byte `offset` on.
+    Utf8 { offset: usize },
+    /// Token `index` of the program, on `line`, isn't an integer.
+    Parse {
+        line: usize,
+        index: usize,
+        token: String,
+    },
+    /// The image is shorter than its header says.
+    Truncated,
+    /// The image's contents don't match its checksum.
+    Checksum { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Utf8 { offset } => write!(f, "Invalid UTF-8 at byte {offset}"),
+            Self::Parse { line, index, token } => {
+                write!(f, "line {line}: value {index} {token:?} isn't an integer")
+            }
+            Self::Truncated => write!(f, "Truncated image"),
+            Self::Checksum { expected, actual } => {
+                write!(f, "Image checksum is {actual:08x}, expected {expected:08x}")
+            }
+        }
+    }
+}
+
+impl error::Error for LoadError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for LoadError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Parse program text.
+pub fn parse(text: &str) -> Result<Vec<i64>, LoadError> {
+    let mut memory = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let code = line.split('#').next().unwrap();
+        let mut tokens = code.split(',').map(str::trim).peekable();
+        while let Some(token) = tokens.next() {
+            // Allow a trailing comma, or nothing at all, on a line.
+            if token.is_empty() && tokens.peek().is_none() {
+                break;
+            }
+            let value = token.parse().map_err(|_| LoadError::Parse {
+                line: i + 1,
+                index: memory.len(),
+                token: token.into(),
+            })?;
+            memory.push(value);
+        }
+    }
+    Ok(memory)
+}
+
+/// Load a program from `bytes`, either text or a binary image.
+pub fn decode(bytes: &[u8]) -> Result<Vec<i64>, LoadError> {
+    if bytes.starts_with(MAGIC) {
+        return decode_image(bytes);
+    }
+    let text = core::str::from_utf8(bytes).map_err(|error| LoadError::Utf8 {
+        offset: error.valid_up_to(),
+    })?;
+    parse(text)
+}
+
+/// Encode `memory` as a binary image.
+pub fn encode_image(memory: &[i64]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(MAGIC.len() + 12 + 8 * memory.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&(memory.len() as u64).to_le_bytes());
+    for value in memory {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    let checksum = crc32(&bytes[MAGIC.len() + 8..]);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+/// Decode a binary image. `bytes` must start with `MAGIC`.
+pub fn decode_image(bytes: &[u8]) -> Result<Vec<i64>, LoadError> {
+    let header = bytes
+        .strip_prefix(MAGIC)
+        .and_then(|rest| rest.split_first_chunk::<8>());
+    let Some((len, rest)) = header else {
+        return Err(LoadError::Truncated);
+    };
+    let len = usize::try_from(u64::from_le_bytes(*len)).map_err(|_| LoadError::Truncated)?;
+    let values = len
+        .checked_mul(8)
+        .and_then(|size| rest.get(..size))
+        .ok_or(LoadError::Truncated)?;
+    let Some(checksum) = rest[values.len()..].first_chunk::<4>() else {
+        return Err(LoadError::Truncated);
+    };
+
+    let expected = u32::from_le_bytes(*checksum);
+    let actual = crc32(values);
+    if actual != expected {
+        return Err(LoadError::Checksum { expected, actual });
+    }
+    Ok(values
+        .chunks_exact(8)
+        .map(|chunk| i64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
+
+/// CRC-32 (IEEE) lookup table.
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Load a program from `reader`, either text or a binary image.
+#[cfg(feature = "std")]
+pub fn read(mut reader: impl std::io::Read) -> Result<Vec<i64>, LoadError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    decode(&bytes)
+}
+
+/// Load a program from stdin.
+#[cfg(feature = "std")]
+pub fn read_stdin() -> Result<Vec<i64>, LoadError> {
+    read(std::io::stdin().lock())
+}
+
+/// Load a program from `path`.
+#[cfg(feature = "std")]
+pub fn read_file(path: impl AsRef<std::path::Path>) -> Result<Vec<i64>, LoadError> {
+    decode(&std::fs::read(path)?)
+}
+
+/// Write `memory` to `path` as a binary image.
+#[cfg(feature = "std")]
+pub fn write_image(path: impl AsRef<std::path::Path>, memory: &[i64]) -> std::io::Result<()> {
+    std::fs::write(path, encode_image(memory))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_text() {
+        assert_eq!(parse("1,2,3\n").unwrap(), [1, 2, 3]);
+        assert_eq!(
+            parse("# header\n1, 2,\n  3,-4, # comment\n\n+5\r\n").unwrap(),
+            [1, 2, 3, -4, 5]
+        );
+        assert!(parse("").unwrap().is_empty());
+
+        let error = parse("1,2\n3,x4,5").unwrap_err();
+        assert!(matches!(
+            &error,
+            LoadError::Parse { line: 2, index: 3, token } if token == "x4"
+        ));
+        assert_eq!(error.to_string(), "line 2: value 3 \"x4\" isn't an integer");
+        assert!(matches!(
+            parse("1,,2").unwrap_err(),
+            LoadError::Parse { index: 1, token, .. } if token.is_empty()
+        ));
+        assert!(matches!(
+            decode(b"1,\xff"),
+            Err(LoadError::Utf8 { offset: 2 })
+        ));
+    }
+
+    #[test]
+    fn images() {
+        // The standard check value.
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        let memory = [109, 1, 204, -1, 1001, 100, 1, 100, i64::MIN, i64::MAX];
+        let image = encode_image(&memory);
+        assert_eq!(image.len(), 8 + 8 + 8 * memory.len() + 4);
+        assert_eq!(decode(&image).unwrap(), memory);
+        assert_eq!(read(&image[..]).unwrap(), memory);
+
+        let mut corrupt = image.clone();
+        corrupt[20] ^= 1;
+        assert!(matches!(decode(&corrupt), Err(LoadError::Checksum { .. })));
+        for len in [10, 20, image.len() - 1] {
+            assert!(matches!(decode(&image[..len]), Err(LoadError::Truncated)));
+        }
+        let mut huge = image.clone();
+        huge[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(decode(&huge), Err(LoadError::Truncated)));
+    }
+}
@@ -0,0 +1,248 @@
+//! The table of opcodes an IntcodeComputer understands.
+//!
+//! Each opcode has an arity, a set of parameters that are write addresses,
+//! and a handler. Before the handler runs, read parameters are resolved to
+//! their values and write parameters to absolute addresses according to
+//! their parameter modes, so a handler only deals with plain numbers.
+
+use crate::{word::Word, Fault, IntcodeComputer};
+use alloc::{sync::Arc, vec, vec::Vec};
+use core::fmt;
+
+/// Maximum number of parameters of an opcode.
+pub const MAX_ARITY: usize = 8;
+
+/// What the computer does after a handler returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Continue with the following instruction.
+    Next,
+    /// Continue at the given address.
+    Jump(usize),
+    /// The instruction couldn't be executed because the computer is waiting
+    /// on input. It will be retried on the next step.
+    Blocked,
+    Halt,
+    /// The instruction failed. The computer stops at the instruction.
+    Fault(Fault),
+}
+
+impl From<Result<(), Fault>> for Flow {
+    /// Continue with the following instruction unless there was a fault.
+    fn from(result: Result<(), Fault>) -> Self {
+        match result {
+            Ok(()) => Self::Next,
+            Err(fault) => Self::Fault(fault),
+        }
+    }
+}
+
+/// Executes an instruction given its resolved parameters.
+pub type Handler<W = i64> = Arc<dyn Fn(&mut IntcodeComputer<W>, &[W]) -> Flow + Send + Sync>;
+
+/// Jump to `addr`, if it's a valid address.
+fn jump<W: Word>(addr: W) -> Flow {
+    match addr.to_i64() {
+        Some(addr) if addr >= 0 => Flow::Jump(addr as usize),
+        Some(addr) => Flow::Fault(Fault::NegativeAddress { addr }),
+        None => Flow::Fault(Fault::InvalidAddress),
+    }
+}
+
+pub struct Opcode<W = i64> {
+    pub arity: usize,
+    /// Indices of the parameters that are write addresses.
+    pub writes: Vec<usize>,
+    pub handler: Handler<W>,
+}
+
+pub struct OpcodeTable<W = i64> {
+    /// Opcodes by number. Instructions only encode opcodes 0 to 99.
+    opcodes: Vec<Option<Opcode<W>>>,
+    /// Whether opcodes were registered after the builtin ones. Their handlers
+    /// may write `mem` directly, which loop detection can't see.
+    pub(crate) custom: bool,
+}
+
+// Handlers are shared, so cloning doesn't need `W: Clone`.
+impl<W> Clone for Opcode<W> {
+    fn clone(&self) -> Self {
+        Self {
+            arity: self.arity,
+            writes: self.writes.clone(),
+            handler: Arc::clone(&self.handler),
+        }
+    }
+}
+
+impl<W> Clone for OpcodeTable<W> {
+    fn clone(&self) -> Self {
+        Self {
+            opcodes: self.opcodes.clone(),
+            custom: self.custom,
+        }
+    }
+}
+
+impl<W: Word> OpcodeTable<W> {
+    /// A table without any opcodes.
+    pub fn empty() -> Self {
+        Self {
+            opcodes: vec![None; 100],
+            custom: false,
+        }
+    }
+
+    /// The opcodes of the Intcode specification.
+    pub fn builtin() -> Self {
+        let mut table = Self::empty();
+        // ADD lhs,rhs,addr
+        table.register(1, 3, &[2], |pc, p| match p[0].try_add(p[1]) {
+            Some(value) => pc.store(p[2], value).into(),
+            None => Flow::Fault(Fault::Overflow),
+        });
+        // MUL lhs,rhs,addr
+        table.register(2, 3, &[2], |pc, p| match p[0].try_mul(p[1]) {
+            Some(value) => pc.store(p[2], value).into(),
+            None => Flow::Fault(Fault::Overflow),
+        });
+        // INPUT addr
+        table.register(3, 1, &[0], |pc, p| match pc.input.pop_front() {
+            Some(value) => pc.store(p[0], value).into(),
+            None => Flow::Blocked,
+        });
+        // OUTPUT value
+        table.register(4, 1, &[], |pc, p| pc.emit(p[0]).into());
+        // JNZ cond,addr
+        table.register(5, 2, &[], |_, p| {
+            if p[0] != W::from(0) {
+                jump(p[1])
+            } else {
+                Flow::Next
+            }
+        });
+        // JZ cond,addr
+        table.register(6, 2, &[], |_, p| {
+            if p[0] == W::from(0) {
+                jump(p[1])
+            } else {
+                Flow::Next
+            }
+        });
+        // LT lhs,rhs,addr
+        table.register(7, 3, &[2], |pc, p| {
+            pc.store(p[2], W::from((p[0] < p[1]) as i64)).into()
+        });
+        // EQ lhs,rhs,addr
+        table.register(8, 3, &[2], |pc, p| {
+            pc.store(p[2], W::from((p[0] == p[1]) as i64)).into()
+        });
+        // RB delta
+        table.register(9, 1, &[], |pc, p| match p[0].to_i64() {
+            Some(delta) => match pc.rb.checked_add(delta) {
+                Some(rb) => {
+                    pc.rb = rb;
+                    Flow::Next
+                }
+                None => Flow::Fault(Fault::Overflow),
+            },
+            None => Flow::Fault(Fault::Overflow),
+        });
+        // HALT
+        table.register(99, 0, &[], |_, _| Flow::Halt);
+        table.custom = false;
+        table
+    }
+
+    /// Add or replace `opcode`, which takes `arity` parameters of which those
+    /// at indices `writes` are write addresses.
+    pub fn register(
+        &mut self,
+        opcode: i64,
+        arity: usize,
+        writes: &[usize],
+        handler: impl Fn(&mut IntcodeComputer<W>, &[W]) -> Flow + Send + Sync + 'static,
+    ) {
+        assert!(
+            (0..100).contains(&opcode),
+            "Opcode {opcode} should be within 0..100"
+        );
+        assert!(
+            arity <= MAX_ARITY,
+            "Opcode {opcode} has more than {MAX_ARITY} parameters"
+        );
+        assert!(
+            writes.iter().all(|&i| i < arity),
+            "Write parameter of opcode {opcode} out of range"
+        );
+        self.opcodes[opcode as usize] = Some(Opcode {
+            arity,
+            writes: writes.to_vec(),
+            handler: Arc::new(handler),
+        });
+        self.custom = true;
+    }
+
+    /// Remove `opcode` from the table.
+    pub fn unregister(&mut self, opcode: i64) {
+        if let Some(slot) = self.opcodes.get_mut(opcode as usize) {
+            *slot = None;
+        }
+    }
+
+    pub fn get(&self, opcode: i64) -> Option<&Opcode<W>> {
+        if opcode < 0 {
+            return None;
+        }
+        self.opcodes.get(opcode as usize)?.as_ref()
+    }
+}
+
+impl<W> fmt::Debug for OpcodeTable<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let opcodes = (0..100).filter(|&opcode| self.opcodes[opcode].is_some());
+        f.debug_set().entries(opcodes).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Flow;
+    use crate::{Fault, IntcodeComputer};
+
+    #[test]
+    fn register_opcode() {
+        // DIV 20,[8],[9]; OUT [9]; HALT
+        let mut pc = IntcodeComputer::new(vec![110, 20, 8, 9, 4, 9, 99, 0, 4, 0]);
+        pc.register(10, 3, &[2], |pc, p| pc.store(p[2], p[0] / p[1]).into());
+        pc.run();
+        assert!(pc.halted);
+        assert_eq!(pc.output, [5]);
+    }
+
+    #[test]
+    fn override_opcode() {
+        let mut pc = IntcodeComputer::new(vec![104, 7, 99]);
+        pc.register(4, 1, &[], |pc, p| {
+            pc.output.push_back(2 * p[0]);
+            Flow::Next
+        });
+        pc.run();
+        assert_eq!(pc.output, [14]);
+
+        // Other computers keep the builtin table.
+        let mut pc = IntcodeComputer::new(vec![104, 7, 99]);
+        pc.run();
+        assert_eq!(pc.output, [7]);
+    }
+
+    #[test]
+    fn unregister_opcode() {
+        let mut table = (**IntcodeComputer::new(vec![]).opcodes()).clone();
+        table.unregister(4);
+        let mut pc = IntcodeComputer::with_opcodes(vec![104, 7, 99], table.into());
+        pc.run();
+        assert_eq!(pc.fault, Some(Fault::InvalidOpcode { instruction: 104 }));
+        assert_eq!(pc.ip, 0);
+    }
+}
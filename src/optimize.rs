@@ -0,0 +1,587 @@
+//! Peephole optimisation of Intcode programs.
+//!
+//! Code addresses are baked into Intcode programs as literal operands, so the
+//! optimiser never moves an instruction. Removed instructions are skipped
+//! over with a jump, and every rewrite keeps its instruction's address.
+//!
+//! The analysis assumes that code is only entered through literal addresses
+//! and that computed addresses (relative mode, or position-mode operands
+//! written at run time) never touch the words of reachable code. Rewrites
+//! that rely on data flow (dead stores, compare-and-branch fusion and dead
+//! code removal) only apply to programs without computed addresses or jumps,
+//! where the facts they need can be proven. Instructions whose words are read
+//! or written as data are never changed, and programs that rewrite their own
+//! opcodes are left as they are.
+
+use crate::{
+    asm::{Instruction, Opcode, Param},
+    specialize::{check_equivalence, Mismatch},
+    ParameterMode,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+pub(crate) struct Analysis {
+    /// Reachable instructions by address.
+    pub(crate) code: BTreeMap<usize, Instruction>,
+    /// Addresses that may be entered other than by falling through.
+    targets: BTreeSet<usize>,
+    /// Words referenced by a position-mode parameter. They keep their values.
+    referenced: HashSet<usize>,
+    /// Words written through a position-mode parameter.
+    pub(crate) written: HashSet<usize>,
+    /// Whether some reachable jump has a non-literal target.
+    pub(crate) computed_jumps: bool,
+    /// Whether some reachable instruction has a relative-mode parameter, or
+    /// a position-mode parameter whose address is written at run time.
+    computed_addresses: bool,
+}
+
+impl Analysis {
+    /// Data flow can be tracked exactly through position-mode addresses.
+    fn closed(&self) -> bool {
+        !self.computed_jumps && !self.computed_addresses
+    }
+
+    /// Whether the instruction at `addr` must be kept as it is, because some
+    /// of its words are read or written as data.
+    fn fixed(&self, addr: usize) -> bool {
+        let len = self.code[&addr].size();
+        (addr..addr + len).any(|word| self.referenced.contains(&word))
+    }
+
+    /// The target of the jump at `addr`, if it is a literal.
+    fn literal_target(&self, addr: usize, instruction: &Instruction) -> Option<usize> {
+        let target = instruction.params[1];
+        (target.mode == ParameterMode::Immediate && !self.written.contains(&(addr + 2)))
+            .then_some(target.value as usize)
+    }
+}
+
+/// Find the code reachable from address 0, given the words that are written
+/// at run time. Returns None if some reachable code can't be decoded or
+/// overlaps other code.
+fn explore(
+    program: &[i64],
+    written: &HashSet<usize>,
+) -> Option<(BTreeMap<usize, Instruction>, BTreeSet<usize>, bool)> {
+    let mut code = BTreeMap::new();
+    let mut owner = HashMap::new();
+    let mut targets = BTreeSet::from([0]);
+    let mut literals = BTreeSet::new();
+    let mut computed_jumps = false;
+
+    let mut pending = vec![0];
+    loop {
+        while let Some(addr) = pending.pop() {
+            if code.contains_key(&addr) {
+                continue;
+            }
+            let instruction = Instruction::decode(program, addr)?;
+            for word in addr..addr + instruction.size() {
+                if *owner.entry(word).or_insert(addr) != addr {
+                    return None;
+                }
+            }
+            for param in &instruction.params {
+                if param.mode == ParameterMode::Immediate
+                    && (0..program.len() as i64).contains(&param.value)
+                {
+                    literals.insert(param.value as usize);
+                }
+            }
+
+            match instruction.opcode {
+                Opcode::Halt => {}
+                Opcode::Jnz | Opcode::Jz => {
+                    let (cond, target) = (instruction.params[0], instruction.params[1]);
+                    let (may_jump, may_fall) = if cond.mode == ParameterMode::Immediate
+                        && !written.contains(&(addr + 1))
+                    {
+                        let taken = (cond.value != 0) == (instruction.opcode == Opcode::Jnz);
+                        (taken, !taken)
+                    } else {
+                        (true, true)
+                    };
+                    if may_jump {
+                        if target.mode == ParameterMode::Immediate && !written.contains(&(addr + 2))
+                        {
+                            if target.value < 0 {
+                                return None;
+                            }
+                            pending.push(target.value as usize);
+                            targets.insert(target.value as usize);
+                        } else {
+                            computed_jumps = true;
+                        }
+                    }
+                    if may_fall {
+                        pending.push(addr + 3);
+                    }
+                }
+                _ => pending.push(addr + instruction.size()),
+            }
+            code.insert(addr, instruction);
+        }
+
+        // A computed jump may land on any literal that looks like code.
+        if computed_jumps {
+            for &literal in &literals {
+                if !owner.contains_key(&literal) && Instruction::decode(program, literal).is_some()
+                {
+                    pending.push(literal);
+                    targets.insert(literal);
+                }
+            }
+        }
+        if pending.is_empty() {
+            break;
+        }
+    }
+    Some((code, targets, computed_jumps))
+}
+
+/// Find the reachable code of `program` and the words it uses as data.
+/// Returns None if the code can't be decoded, overlaps itself, or rewrites
+/// one of its own opcodes.
+pub(crate) fn analyze(program: &[i64]) -> Option<Analysis> {
+    let mut written = HashSet::new();
+    loop {
+        let (code, targets, computed_jumps) = explore(program, &written)?;
+
+        let mut referenced = HashSet::new();
+        let mut new_written = written.clone();
+        let mut computed_addresses = false;
+        for (&addr, instruction) in &code {
+            for (i, param) in instruction.params.iter().enumerate() {
+                if param.mode != ParameterMode::Immediate && written.contains(&(addr + 1 + i)) {
+                    computed_addresses = true;
+                }
+                match param.mode {
+                    ParameterMode::Position if param.value >= 0 => {
+                        referenced.insert(param.value as usize);
+                        if instruction.opcode.write_param() == Some(i) {
+                            new_written.insert(param.value as usize);
+                        }
+                    }
+                    ParameterMode::Relative => computed_addresses = true,
+                    _ => {}
+                }
+            }
+        }
+
+        // Writes to operands change what the code does, so explore again.
+        if new_written != written {
+            written = new_written;
+            continue;
+        }
+        if written.iter().any(|addr| code.contains_key(addr)) {
+            return None;
+        }
+        return Some(Analysis {
+            code,
+            targets,
+            referenced,
+            written,
+            computed_jumps,
+            computed_addresses,
+        });
+    }
+}
+
+struct Optimizer<'a> {
+    analysis: &'a Analysis,
+    code: BTreeMap<usize, Instruction>,
+    /// Instructions that have been removed.
+    nops: BTreeSet<usize>,
+}
+
+impl Optimizer<'_> {
+    /// Addresses execution may continue at after the instruction at `addr`,
+    /// or None if a jump target isn't known.
+    fn successors(&self, addr: usize) -> Option<Vec<usize>> {
+        let instruction = &self.code[&addr];
+        if self.nops.contains(&addr) {
+            return Some(vec![addr + instruction.size()]);
+        }
+        match instruction.opcode {
+            Opcode::Halt => Some(vec![]),
+            Opcode::Jnz | Opcode::Jz => {
+                let mut result = vec![];
+                let cond = self.constant_cond(addr);
+                let taken = cond.map(|cond| (cond != 0) == (instruction.opcode == Opcode::Jnz));
+                if taken != Some(false) {
+                    result.push(self.analysis.literal_target(addr, instruction)?);
+                }
+                if taken != Some(true) {
+                    result.push(addr + 3);
+                }
+                Some(result)
+            }
+            _ => Some(vec![addr + instruction.size()]),
+        }
+    }
+
+    /// The condition of the (possibly rewritten) jump at `addr`, if known.
+    fn constant_cond(&self, addr: usize) -> Option<i64> {
+        let cond = self.code[&addr].params[0];
+        (cond.mode == ParameterMode::Immediate && !self.analysis.written.contains(&(addr + 1)))
+            .then_some(cond.value)
+    }
+
+    /// Whether `cell` may be read before it is written after the instruction
+    /// at `addr` executes. Only meaningful for closed programs.
+    fn live_after(&self, addr: usize, cell: i64) -> bool {
+        let Some(mut pending) = self.successors(addr) else {
+            return true;
+        };
+        let mut visited = HashSet::new();
+        while let Some(addr) = pending.pop() {
+            if !visited.insert(addr) {
+                continue;
+            }
+            let Some(instruction) = self.code.get(&addr) else {
+                return true;
+            };
+            if !self.nops.contains(&addr) {
+                // Executing an instruction reads its own words.
+                if (addr as i64..(addr + instruction.size()) as i64).contains(&cell) {
+                    return true;
+                }
+                let write = instruction.opcode.write_param();
+                let mut writes = false;
+                for (i, param) in instruction.params.iter().enumerate() {
+                    if param.mode == ParameterMode::Position && param.value == cell {
+                        if Some(i) == write {
+                            writes = true;
+                        } else {
+                            return true;
+                        }
+                    }
+                }
+                if writes {
+                    continue;
+                }
+            }
+            let Some(successors) = self.successors(addr) else {
+                return true;
+            };
+            pending.extend(successors);
+        }
+        false
+    }
+
+    /// The instruction falling through to `addr`, if it is the only way to
+    /// reach `addr`.
+    fn sole_predecessor(&self, addr: usize) -> Option<(usize, &Instruction)> {
+        if self.analysis.targets.contains(&addr) {
+            return None;
+        }
+        let (&prev, instruction) = self.code.range(..addr).next_back()?;
+        if prev + instruction.size() != addr || self.nops.contains(&prev) {
+            return None;
+        }
+        Some((prev, instruction))
+    }
+
+    /// Rewrite the instruction at `addr`. Returns whether anything changed.
+    fn rewrite(&mut self, addr: usize) -> bool {
+        if self.analysis.fixed(addr) {
+            return false;
+        }
+        let instruction = self.code[&addr].clone();
+        let params = &instruction.params;
+        let immediate =
+            |n: usize| (params[n].mode == ParameterMode::Immediate).then_some(params[n].value);
+
+        match instruction.opcode {
+            Opcode::Add | Opcode::Mul | Opcode::Lt | Opcode::Eq => {
+                // Fold constant arithmetic into ADD value,0,addr.
+                if let (Some(lhs), Some(rhs)) = (immediate(0), immediate(1)) {
+                    let value = match instruction.opcode {
+                        Opcode::Add => lhs.checked_add(rhs),
+                        Opcode::Mul => lhs.checked_mul(rhs),
+                        Opcode::Lt => Some((lhs < rhs) as i64),
+                        _ => Some((lhs == rhs) as i64),
+                    };
+                    if let Some(value) = value {
+                        let folded = Instruction::new(
+                            Opcode::Add,
+                            vec![Param::immediate(value), Param::immediate(0), params[2]],
+                        );
+                        if folded != instruction {
+                            self.code.insert(addr, folded);
+                            return true;
+                        }
+                    }
+                }
+
+                // Remove stores that are never read.
+                let dst = params[2];
+                if self.analysis.closed() && !self.live_after(addr, dst.value) {
+                    self.nops.insert(addr);
+                    return true;
+                }
+
+                // Fuse EQ x,0,t followed by JZ/JNZ t into JNZ/JZ x.
+                let next = addr + 4;
+                if instruction.opcode == Opcode::Eq
+                    && self.analysis.closed()
+                    && self.sole_predecessor(next).is_some()
+                {
+                    let x = match (immediate(0), immediate(1)) {
+                        (_, Some(0)) => params[0],
+                        (Some(0), _) => params[1],
+                        _ => return false,
+                    };
+                    let Some(jump) = self.code.get(&next) else {
+                        return false;
+                    };
+                    if self.analysis.fixed(next) {
+                        return false;
+                    }
+                    let cond = Param::position(dst.value);
+                    let fused = match jump.opcode {
+                        Opcode::Jz => Opcode::Jnz,
+                        Opcode::Jnz => Opcode::Jz,
+                        _ => return false,
+                    };
+                    if jump.params[0] == cond
+                        && jump.params[1] != cond
+                        && !self.live_after(next, dst.value)
+                    {
+                        let fused = Instruction::new(fused, vec![x, jump.params[1]]);
+                        self.code.insert(next, fused);
+                        self.nops.insert(addr);
+                        return true;
+                    }
+                }
+            }
+            Opcode::Jnz | Opcode::Jz => {
+                // Remove jumps that are never taken.
+                if let Some(cond) = self.constant_cond(addr) {
+                    if (cond != 0) != (instruction.opcode == Opcode::Jnz) {
+                        self.nops.insert(addr);
+                        return true;
+                    }
+                }
+
+                // Use the constant just stored to the condition cell.
+                let cond = params[0];
+                if cond.mode == ParameterMode::Position {
+                    if let Some((prev, _)) = self.sole_predecessor(addr) {
+                        if self.analysis.fixed(prev) {
+                            return false;
+                        }
+                        let prev = self.code[&prev].clone();
+                        let [value, zero, dst] = prev.params[..] else {
+                            return false;
+                        };
+                        if prev.opcode == Opcode::Add
+                            && value.mode == ParameterMode::Immediate
+                            && zero == Param::immediate(0)
+                            && dst == cond
+                        {
+                            let known =
+                                Instruction::new(instruction.opcode, vec![value, params[1]]);
+                            self.code.insert(addr, known);
+                            return true;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        false
+    }
+
+    /// Follow unconditional jumps and removed instructions from `target`.
+    fn thread(&self, mut target: usize) -> usize {
+        let mut visited = HashSet::new();
+        while visited.insert(target) {
+            let Some(instruction) = self.code.get(&target) else {
+                break;
+            };
+            if self.nops.contains(&target) {
+                target += instruction.size();
+            } else if matches!(instruction.opcode, Opcode::Jnz | Opcode::Jz)
+                && self.successors(target).is_some_and(|next| next.len() == 1)
+                && self.constant_cond(target).is_some()
+            {
+                target = self.successors(target).unwrap()[0];
+            } else {
+                break;
+            }
+        }
+        target
+    }
+}
+
+/// Optimise `program`, keeping every live instruction at its address.
+pub fn optimize(program: &[i64]) -> Vec<i64> {
+    let Some(analysis) = analyze(program) else {
+        return program.to_vec();
+    };
+    let mut optimizer = Optimizer {
+        analysis: &analysis,
+        code: analysis.code.clone(),
+        nops: BTreeSet::new(),
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        let addrs: Vec<usize> = optimizer.code.keys().copied().collect();
+        for addr in addrs {
+            if !optimizer.nops.contains(&addr) {
+                changed |= optimizer.rewrite(addr);
+            }
+        }
+    }
+
+    // Retarget literal jumps past removed instructions and jump chains.
+    let addrs: Vec<usize> = optimizer.code.keys().copied().collect();
+    for addr in addrs {
+        let instruction = &optimizer.code[&addr];
+        if matches!(instruction.opcode, Opcode::Jnz | Opcode::Jz)
+            && !optimizer.nops.contains(&addr)
+            && !analysis.fixed(addr)
+        {
+            let Some(target) = analysis.literal_target(addr, instruction) else {
+                continue;
+            };
+            let target = optimizer.thread(target);
+            optimizer.code.get_mut(&addr).unwrap().params[1].value = target as i64;
+        }
+    }
+
+    let mut result = program.to_vec();
+    for (&addr, instruction) in &optimizer.code {
+        if !optimizer.nops.contains(&addr) && !analysis.fixed(addr) {
+            result[addr..addr + instruction.size()].copy_from_slice(&instruction.encode());
+        }
+    }
+
+    // Skip each run of removed instructions with JZ 0,end.
+    let mut live: Vec<bool> = vec![false; program.len()];
+    let mut skipped = HashSet::new();
+    for &start in &optimizer.nops {
+        if skipped.contains(&start) {
+            continue;
+        }
+        let mut end = start;
+        while optimizer.nops.contains(&end) && (end == start || !analysis.targets.contains(&end)) {
+            skipped.insert(end);
+            end += optimizer.code[&end].size();
+        }
+        result[start..start + 3].copy_from_slice(&[1106, 0, end as i64]);
+        live[start..start + 3].fill(true);
+    }
+    for (&addr, instruction) in &optimizer.code {
+        if !optimizer.nops.contains(&addr) {
+            live[addr..addr + instruction.size()].fill(true);
+        }
+    }
+
+    let code_end = live
+        .iter()
+        .rposition(|&live| live)
+        .map_or(0, |last| last + 1);
+
+    // In a closed program, only reachable code and referenced cells matter.
+    if analysis.closed() {
+        for &addr in &analysis.referenced {
+            if addr < live.len() {
+                live[addr] = true;
+            }
+        }
+        for (word, live) in result.iter_mut().zip(&live) {
+            if !live {
+                *word = 0;
+            }
+        }
+    }
+
+    // Trailing zeros past the code read the same when they're missing.
+    let len = result
+        .iter()
+        .rposition(|&word| word != 0)
+        .map_or(0, |last| last + 1);
+    result.truncate(len.max(code_end));
+    result
+}
+
+/// Run `original` and `optimized` on each input sequence and report the first
+/// sequence on which they differ.
+pub fn check(
+    original: &[i64],
+    optimized: &[i64],
+    inputs: &[Vec<i64>],
+) -> Result<(), (usize, Mismatch)> {
+    for (i, input) in inputs.iter().enumerate() {
+        check_equivalence(original, optimized, &[], input).map_err(|mismatch| (i, mismatch))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check, optimize};
+
+    #[test]
+    fn fold_constant_arithmetic() {
+        // ADD 2,3,[7]; OUT [7]; HALT
+        let program = vec![1101, 2, 3, 7, 4, 7, 99, 0];
+        let optimized = optimize(&program);
+        assert_eq!(optimized, [1101, 5, 0, 7, 4, 7, 99]);
+        assert_eq!(check(&program, &optimized, &[vec![]]), Ok(()));
+    }
+
+    #[test]
+    fn fuse_compare_and_branch() {
+        // IN [20]; EQ [20],0,[21]; JZ [21],12; OUT 1; HALT; OUT 2; HALT
+        let program = vec![3, 20, 1008, 20, 0, 21, 1006, 21, 12, 104, 1, 99, 104, 2, 99];
+        let optimized = optimize(&program);
+        assert_eq!(
+            optimized,
+            [3, 20, 1106, 0, 6, 0, 1005, 20, 12, 104, 1, 99, 104, 2, 99]
+        );
+        assert_eq!(
+            check(&program, &optimized, &[vec![0], vec![5], vec![-1]]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn remove_dead_code() {
+        // JNZ 1,7; OUT 5; HALT; DATA 0; OUT 1; HALT
+        let program = vec![1105, 1, 7, 104, 5, 99, 0, 104, 1, 99];
+        let optimized = optimize(&program);
+        assert_eq!(optimized, [1105, 1, 7, 0, 0, 0, 0, 104, 1, 99]);
+        assert_eq!(check(&program, &optimized, &[vec![]]), Ok(()));
+    }
+
+    #[test]
+    fn remove_dead_branch() {
+        // LT 1,2,[13]; JZ [13],10; OUT 1; HALT; HALT
+        let program = vec![1107, 1, 2, 13, 1006, 13, 10, 104, 1, 99, 99, 0, 0, 0];
+        let optimized = optimize(&program);
+        assert_eq!(optimized, [1106, 0, 7, 0, 0, 0, 0, 104, 1, 99, 99]);
+        assert_eq!(check(&program, &optimized, &[vec![]]), Ok(()));
+    }
+
+    #[test]
+    fn keep_self_modifying_code() {
+        // ADD [0],[0],[0]... writes its own opcode
+        let program = vec![1, 0, 0, 0, 99];
+        assert_eq!(optimize(&program), program);
+    }
+
+    #[test]
+    fn keep_relative_data_flow() {
+        // RB 10; ADD 2,3,[rb+0]; OUT [rb+0]; HALT
+        let program = vec![109, 10, 21101, 2, 3, 0, 204, 0, 99];
+        let optimized = optimize(&program);
+        assert_eq!(optimized, [109, 10, 21101, 5, 0, 0, 204, 0, 99]);
+        assert_eq!(check(&program, &optimized, &[vec![]]), Ok(()));
+    }
+}
@@ -0,0 +1,106 @@
+//! Iterating over the outputs of an IntcodeComputer.
+
+use crate::IntcodeComputer;
+
+/// Iterator over the outputs of a computer, created by
+/// `IntcodeComputer::outputs`.
+pub struct Outputs<'a, F> {
+    pc: &'a mut IntcodeComputer,
+    input: F,
+}
+
+impl IntcodeComputer {
+    /// Iterate over the values the computer outputs. When it waits on input
+    /// and every output so far has been yielded, `input` is called for the
+    /// next input. Iteration ends when the computer halts or faults, or
+    /// `input` returns None.
+    ///
+    /// State shared between `input` and the loop over the outputs has to
+    /// live in a `Cell` or `RefCell`.
+    pub fn outputs<F: FnMut() -> Option<i64>>(&mut self, input: F) -> Outputs<'_, F> {
+        Outputs { pc: self, input }
+    }
+}
+
+impl<F: FnMut() -> Option<i64>> Iterator for Outputs<'_, F> {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        loop {
+            if let Some(value) = self.pc.output.pop_front() {
+                return Some(value);
+            }
+            if self.pc.halted || self.pc.fault.is_some() {
+                return None;
+            }
+            if self.pc.step() {
+                continue;
+            }
+            if self.pc.fault.is_some() {
+                return None;
+            }
+            let value = (self.input)()?;
+            self.pc.input.push_back(value);
+        }
+    }
+}
+
+impl<F: FnMut() -> Option<i64>> Outputs<'_, F> {
+    /// Group the outputs into arrays of `N` values. A trailing incomplete
+    /// group is dropped.
+    pub fn chunks<const N: usize>(self) -> Chunks<Self, N> {
+        Chunks { iter: self }
+    }
+}
+
+/// Iterator over groups of `N` outputs, created by `Outputs::chunks`.
+pub struct Chunks<I, const N: usize> {
+    iter: I,
+}
+
+impl<I: Iterator<Item = i64>, const N: usize> Iterator for Chunks<I, N> {
+    type Item = [i64; N];
+
+    fn next(&mut self) -> Option<[i64; N]> {
+        let mut chunk = [0; N];
+        for value in &mut chunk {
+            *value = self.iter.next()?;
+        }
+        Some(chunk)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntcodeComputer;
+    use std::cell::Cell;
+
+    #[test]
+    fn outputs() {
+        // Output the running total of the inputs until an input is zero.
+        // IN [20]; JZ [20],15; ADD [20],[21],[21]; OUT [21]; JZ 0,0; HALT
+        let mut pc = IntcodeComputer::new(vec![
+            3, 20, 1006, 20, 15, 1, 20, 21, 21, 4, 21, 1106, 0, 0, 0, 99,
+        ]);
+        let last = Cell::new(0);
+        let mut totals = vec![];
+        for total in pc.outputs(|| Some(if last.get() < 10 { 4 } else { 0 })) {
+            last.set(total);
+            totals.push(total);
+        }
+        assert_eq!(totals, [4, 8, 12]);
+        assert!(pc.halted);
+    }
+
+    #[test]
+    fn chunks() {
+        let mut pc = IntcodeComputer::new(vec![104, 1, 104, 2, 104, 3, 104, 4, 104, 5, 99]);
+        let pairs: Vec<[i64; 2]> = pc.outputs(|| None).chunks::<2>().collect();
+        assert_eq!(pairs, [[1, 2], [3, 4]]);
+
+        // Stops when the supplier has no input.
+        let mut pc = IntcodeComputer::new(vec![104, 1, 3, 0, 104, 2, 99]);
+        assert_eq!(pc.outputs(|| None).collect::<Vec<_>>(), [1]);
+        assert!(!pc.halted);
+    }
+}
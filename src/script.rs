@@ -0,0 +1,293 @@
+//! Scripted dialogue with interactive ASCII Intcode programs.
+//!
+//! A script has one command per line. Empty lines and lines starting with
+//! `#` are ignored.
+//!
+//! - `expect "text"` runs the program until its output contains `text`.
+//! - `send "text"` sends `text` and a newline as input.
+//! - `capture name` runs the program until it outputs a value outside the
+//!   ASCII range and stores the value as `name`.
+//! - `assert name value` checks a captured value.
+//! - `halt` runs the program and checks that it halts.
+//!
+//! Text in quotes may contain the escapes `\"`, `\\` and `\n`. Output is
+//! consumed as it is matched, so each `expect` only sees output after the
+//! previous match.
+
+use crate::IntcodeComputer;
+use std::{collections::HashMap, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Expect(String),
+    Send(String),
+    Capture(String),
+    Assert(String, i64),
+    Halt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    /// The script file couldn't be read.
+    Io { file: String, message: String },
+    /// Line `line` of the script is invalid.
+    Parse { line: usize, message: String },
+    /// The program stopped without printing `expected`.
+    Expect {
+        line: usize,
+        expected: String,
+        output: String,
+    },
+    /// The program stopped without outputting a non-ASCII value.
+    Capture { line: usize },
+    Assert {
+        line: usize,
+        name: String,
+        expected: i64,
+        actual: Option<i64>,
+    },
+    /// The program blocked on input instead of halting.
+    Halt { line: usize },
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { file, message } => write!(f, "{file}: {message}"),
+            Self::Parse { line, message } => write!(f, "line {line}: {message}"),
+            Self::Expect {
+                line,
+                expected,
+                output,
+            } => write!(f, "line {line}: expected {expected:?}, got {output:?}"),
+            Self::Capture { line } => write!(f, "line {line}: no value to capture"),
+            Self::Assert {
+                line,
+                name,
+                expected,
+                actual: Some(actual),
+            } => write!(f, "line {line}: expected {name} = {expected}, got {actual}"),
+            Self::Assert { line, name, .. } => write!(f, "line {line}: {name} was not captured"),
+            Self::Halt { line } => write!(f, "line {line}: program did not halt"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Parse a quoted string.
+fn unquote(text: &str) -> Option<String> {
+    let text = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => result.push('\n'),
+            c @ ('"' | '\\') => result.push(c),
+            _ => return None,
+        }
+    }
+    Some(result)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    /// Commands with their line numbers.
+    pub commands: Vec<(usize, Command)>,
+}
+
+/// Output of the program that hasn't been matched yet.
+#[derive(Default)]
+struct Pending {
+    text: String,
+    values: Vec<i64>,
+}
+
+impl Pending {
+    /// Move the output of `pc` into the pending output.
+    fn take(&mut self, pc: &mut IntcodeComputer) {
+        for value in pc.output.drain(..) {
+            match u8::try_from(value) {
+                Ok(byte) if byte.is_ascii() => self.text.push(byte as char),
+                _ => self.values.push(value),
+            }
+        }
+    }
+}
+
+impl Script {
+    pub fn parse(text: &str) -> Result<Self, ScriptError> {
+        let mut commands = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let error = |message: &str| ScriptError::Parse {
+                line: line_number,
+                message: message.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let rest = rest.trim();
+            let command = match keyword {
+                "expect" => Command::Expect(unquote(rest).ok_or_else(|| error("invalid string"))?),
+                "send" => Command::Send(unquote(rest).ok_or_else(|| error("invalid string"))?),
+                "capture" if !rest.is_empty() && !rest.contains(' ') => {
+                    Command::Capture(rest.to_string())
+                }
+                "assert" => {
+                    let (name, value) = rest
+                        .split_once(' ')
+                        .ok_or_else(|| error("expected a name and a value"))?;
+                    let value = value.trim().parse().map_err(|_| error("invalid value"))?;
+                    Command::Assert(name.to_string(), value)
+                }
+                "halt" if rest.is_empty() => Command::Halt,
+                _ => return Err(error("unknown command")),
+            };
+            commands.push((line_number, command));
+        }
+        Ok(Self { commands })
+    }
+
+    /// Read a script from `file`.
+    pub fn load(file: &str) -> Result<Self, ScriptError> {
+        let text = std::fs::read_to_string(file).map_err(|error| ScriptError::Io {
+            file: file.to_string(),
+            message: error.to_string(),
+        })?;
+        Self::parse(&text)
+    }
+
+    /// Run the script against `pc`. Returns the captured values.
+    pub fn run(&self, pc: &mut IntcodeComputer) -> Result<HashMap<String, i64>, ScriptError> {
+        let mut captures = HashMap::new();
+        let mut pending = Pending::default();
+        for (line, command) in &self.commands {
+            let line = *line;
+            match command {
+                Command::Expect(expected) => {
+                    pc.run();
+                    pending.take(pc);
+                    let Some(start) = pending.text.find(expected.as_str()) else {
+                        return Err(ScriptError::Expect {
+                            line,
+                            expected: expected.clone(),
+                            output: pending.text,
+                        });
+                    };
+                    pending.text.drain(..start + expected.len());
+                }
+                Command::Send(text) => {
+                    pc.input.extend(text.bytes().map(i64::from));
+                    pc.input.push_back(b'\n' as i64);
+                }
+                Command::Capture(name) => {
+                    pc.run();
+                    pending.take(pc);
+                    if pending.values.is_empty() {
+                        return Err(ScriptError::Capture { line });
+                    }
+                    captures.insert(name.clone(), pending.values.remove(0));
+                }
+                Command::Assert(name, expected) => {
+                    let actual = captures.get(name).copied();
+                    if actual != Some(*expected) {
+                        return Err(ScriptError::Assert {
+                            line,
+                            name: name.clone(),
+                            expected: *expected,
+                            actual,
+                        });
+                    }
+                }
+                Command::Halt => {
+                    pc.run();
+                    if !pc.halted {
+                        return Err(ScriptError::Halt { line });
+                    }
+                }
+            }
+        }
+        Ok(captures)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Command, Script, ScriptError};
+    use crate::IntcodeComputer;
+
+    /// A program that prints "?\n", reads a digit and a newline, then
+    /// outputs 1000 times the digit and halts.
+    fn program() -> IntcodeComputer {
+        // OUT 63; OUT 10; IN [30]; IN [31]; ADD [30],-48,[30];
+        // MUL [30],1000,[30]; OUT [30]; HALT
+        IntcodeComputer::new(vec![
+            104, 63, 104, 10, 3, 30, 3, 31, 1001, 30, -48, 30, 1002, 30, 1000, 30, 4, 30, 99,
+        ])
+    }
+
+    #[test]
+    fn parse() {
+        let script = Script::parse(
+            "# comment\n\nexpect \"a \\\"b\\\"\\n\"\nsend \"7\"\ncapture x\nassert x -3\nhalt\n",
+        )
+        .unwrap();
+        assert_eq!(
+            script.commands,
+            [
+                (3, Command::Expect("a \"b\"\n".to_string())),
+                (4, Command::Send("7".to_string())),
+                (5, Command::Capture("x".to_string())),
+                (6, Command::Assert("x".to_string(), -3)),
+                (7, Command::Halt),
+            ]
+        );
+        assert_eq!(
+            Script::parse("send 7"),
+            Err(ScriptError::Parse {
+                line: 1,
+                message: "invalid string".to_string()
+            })
+        );
+        assert!(matches!(
+            Script::load("data/no_such_script"),
+            Err(ScriptError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn run() {
+        let script =
+            Script::parse("expect \"?\"\nsend \"7\"\ncapture x\nassert x 7000\nhalt").unwrap();
+        let captures = script.run(&mut program()).unwrap();
+        assert_eq!(captures["x"], 7000);
+
+        let script = Script::parse("expect \"!\"").unwrap();
+        assert_eq!(
+            script.run(&mut program()),
+            Err(ScriptError::Expect {
+                line: 1,
+                expected: "!".to_string(),
+                output: "?\n".to_string()
+            })
+        );
+
+        let script = Script::parse("send \"7\"\ncapture x\nassert x 8000").unwrap();
+        assert_eq!(
+            script.run(&mut program()),
+            Err(ScriptError::Assert {
+                line: 3,
+                name: "x".to_string(),
+                expected: 8000,
+                actual: Some(7000)
+            })
+        );
+    }
+}
@@ -0,0 +1,409 @@
+//! Specialise an Intcode program for a known prefix of its input.
+//!
+//! Everything the program does before it asks for input beyond the prefix
+//! depends only on the prefix, so it is folded away by running it. The
+//! residual program starts from the resulting state: a prologue replays the
+//! outputs produced so far, restores the relative base and jumps to the
+//! point where the original program blocked.
+//!
+//! The prologue stays in memory, so it has to go where the resumed program
+//! can't see it. The resumed program's footprint, the addresses it may
+//! execute or read, is found by following every path from where it blocked.
+//! That only works when its jump targets, relative base changes and
+//! addresses don't depend on memory it writes. The footprint also shows
+//! which reads are of words that never change, and those are folded into
+//! immediate parameters, and which words the program never sees, which are
+//! cleared so trailing ones can be dropped.
+//!
+//! With an empty prefix nothing is folded, since running the original
+//! program gets it to the same state.
+
+use crate::{
+    asm::{Instruction, Opcode, Param},
+    Fault, IntcodeComputer, ParameterMode,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error, fmt,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpecializeError {
+    /// The program faulted on the known inputs.
+    Fault(Fault),
+    /// What the instruction at `ip` accesses or where it jumps depends on
+    /// memory the program changes, or on too many relative bases, so there's
+    /// no telling which addresses the resumed program reads.
+    Dynamic { ip: usize },
+}
+
+impl fmt::Display for SpecializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fault(fault) => write!(f, "{fault}"),
+            Self::Dynamic { ip } => write!(f, "Instruction at {ip} accesses memory dynamically"),
+        }
+    }
+}
+
+impl error::Error for SpecializeError {}
+
+/// Paths followed before giving up on finding a footprint.
+const MAX_STATES: usize = 100_000;
+
+/// The addresses a program may use from some point on.
+#[derive(Debug, Default)]
+struct Footprint {
+    /// Reachable instructions and the relative bases they run with.
+    instructions: BTreeMap<usize, (Instruction, BTreeSet<i64>)>,
+    /// Words of reachable instructions.
+    code: BTreeSet<usize>,
+    /// Addresses read as data.
+    reads: BTreeSet<usize>,
+    /// Addresses written, with an instruction writing each.
+    writes: BTreeMap<usize, usize>,
+}
+
+impl Footprint {
+    /// Whether the program may see the word at `addr`.
+    fn observes(&self, addr: usize) -> bool {
+        self.code.contains(&addr) || self.reads.contains(&addr)
+    }
+}
+
+/// Follow every path of the program in `mem` from `ip` with relative base
+/// `rb`. Jump targets and relative base changes read from memory are taken
+/// from `mem`, which is checked afterwards by making sure nothing writes
+/// those words.
+fn footprint(mem: &[i64], ip: usize, rb: i64) -> Result<Footprint, SpecializeError> {
+    let word = |addr: usize| mem.get(addr).copied().unwrap_or(0);
+    let mut footprint = Footprint::default();
+    // Words assumed constant, with an instruction reading each.
+    let mut assumed = BTreeMap::new();
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![(ip, rb)];
+    while let Some((addr, rb)) = pending.pop() {
+        if !seen.insert((addr, rb)) {
+            continue;
+        }
+        if seen.len() > MAX_STATES {
+            return Err(SpecializeError::Dynamic { ip: addr });
+        }
+        // An invalid instruction faults, which ends the path.
+        let Some(instruction) = Instruction::decode(mem, addr) else {
+            footprint.code.insert(addr);
+            continue;
+        };
+        footprint.code.extend(addr..addr + instruction.size());
+        let (_, rbs) = footprint
+            .instructions
+            .entry(addr)
+            .or_insert_with(|| (instruction.clone(), BTreeSet::new()));
+        rbs.insert(rb);
+
+        // The address of each parameter that isn't immediate. A negative
+        // address faults.
+        let mut addrs = vec![];
+        for param in &instruction.params {
+            let addr = match param.mode {
+                ParameterMode::Position => param.value,
+                ParameterMode::Immediate => {
+                    addrs.push(None);
+                    continue;
+                }
+                ParameterMode::Relative => rb.saturating_add(param.value),
+            };
+            match usize::try_from(addr) {
+                Ok(addr) => addrs.push(Some(addr)),
+                Err(_) => break,
+            }
+        }
+        if addrs.len() < instruction.params.len() {
+            continue;
+        }
+        for (n, param_addr) in addrs.iter().enumerate() {
+            match (*param_addr, instruction.opcode.write_param() == Some(n)) {
+                (Some(param_addr), true) => {
+                    footprint.writes.insert(param_addr, addr);
+                }
+                (Some(param_addr), false) => {
+                    footprint.reads.insert(param_addr);
+                }
+                (None, _) => {}
+            }
+        }
+        let mut value = |n: usize| match addrs[n] {
+            Some(param_addr) => {
+                assumed.insert(param_addr, addr);
+                word(param_addr)
+            }
+            None => instruction.params[n].value,
+        };
+
+        let next = addr + instruction.size();
+        match instruction.opcode {
+            Opcode::Halt => {}
+            Opcode::Jnz | Opcode::Jz => {
+                let target = value(1);
+                if let Ok(target) = usize::try_from(target) {
+                    pending.push((target, rb));
+                }
+                pending.push((next, rb));
+            }
+            Opcode::Rb => pending.push((next, rb.saturating_add(value(0)))),
+            _ => pending.push((next, rb)),
+        }
+    }
+
+    for (addr, reader) in &assumed {
+        if footprint.writes.contains_key(addr) {
+            return Err(SpecializeError::Dynamic { ip: *reader });
+        }
+    }
+    for (addr, writer) in &footprint.writes {
+        if footprint.code.contains(addr) {
+            return Err(SpecializeError::Dynamic { ip: *writer });
+        }
+    }
+    Ok(footprint)
+}
+/// Replace parameters of reachable instructions that read words nothing
+/// writes by immediates, unless the instruction itself is read as data.
+fn fold(mem: &mut [i64], footprint: &Footprint) {
+    for (&addr, (instruction, rbs)) in &footprint.instructions {
+        if (addr..addr + instruction.size()).any(|addr| footprint.reads.contains(&addr)) {
+            continue;
+        }
+        let mut folded = instruction.clone();
+        for (n, param) in folded.params.iter_mut().enumerate() {
+            let param_addr = match param.mode {
+                _ if instruction.opcode.write_param() == Some(n) => continue,
+                ParameterMode::Position => param.value,
+                ParameterMode::Relative if rbs.len() == 1 => {
+                    rbs.first().unwrap().saturating_add(param.value)
+                }
+                _ => continue,
+            };
+            let Ok(param_addr) = usize::try_from(param_addr) else {
+                continue;
+            };
+            if !footprint.writes.contains_key(&param_addr) {
+                *param = Param::immediate(mem.get(param_addr).copied().unwrap_or(0));
+            }
+        }
+        if folded != *instruction && addr + folded.size() <= mem.len() {
+            mem[addr..addr + folded.size()].copy_from_slice(&folded.encode());
+        }
+    }
+}
+
+/// Specialise `program` for the input prefix `inputs`.
+///
+/// If the program halts on the prefix alone, the result only outputs the
+/// values it produced. If the prefix is empty, the program is returned as it
+/// is. Otherwise the result is the memory image at the point the program
+/// blocked, with constant reads folded, words it can't see cleared, and a
+/// prologue in words the rest of the program never reads.
+pub fn specialize(program: &[i64], inputs: &[i64]) -> Result<Vec<i64>, SpecializeError> {
+    let mut pc = IntcodeComputer::new(program.to_vec());
+    pc.input.extend(inputs);
+    pc.run();
+    if let Some(fault) = pc.fault {
+        return Err(SpecializeError::Fault(fault));
+    }
+
+    if pc.halted {
+        let mut result = vec![];
+        for &value in &pc.output {
+            result.extend([104, value]);
+        }
+        result.push(99);
+        return Ok(result);
+    }
+    if inputs.is_empty() {
+        return Ok(program.to_vec());
+    }
+
+    let mut mem = pc.mem;
+    let footprint = footprint(&mem, pc.ip, pc.rb)?;
+    fold(&mut mem, &footprint);
+    for (addr, word) in mem.iter_mut().enumerate() {
+        if !footprint.observes(addr) {
+            *word = 0;
+        }
+    }
+    // Memory past the end reads as zero anyway.
+    while mem.last() == Some(&0) {
+        mem.pop();
+    }
+    if mem.len() < 3 {
+        mem.resize(3, 0);
+    }
+
+    let mut prologue = vec![];
+    for &value in &pc.output {
+        // OUTPUT value
+        prologue.extend([104, value]);
+    }
+    if pc.rb != 0 {
+        // RB rb
+        prologue.extend([109, pc.rb]);
+    }
+    for (addr, &value) in mem[..3].iter().enumerate() {
+        if footprint.observes(addr) {
+            // ADD value,0,addr
+            prologue.extend([1101, value, 0, addr as i64]);
+        }
+    }
+    // JZ 0,ip
+    prologue.extend([1106, 0, pc.ip as i64]);
+
+    // The first gap after the jump to the prologue that it fits in.
+    let mut start = 3;
+    while let Some(used) = (start..start + prologue.len()).rfind(|&addr| footprint.observes(addr)) {
+        start = used + 1;
+    }
+    let end = start + prologue.len();
+    if mem.len() < end {
+        mem.resize(end, 0);
+    }
+    mem[start..end].copy_from_slice(&prologue);
+    // JZ 0,prologue
+    mem[..3].copy_from_slice(&[1106, 0, start as i64]);
+    Ok(mem)
+}
+
+/// The first difference between the original and the specialised program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// The n-th output differs, or one program produced fewer outputs.
+    Output {
+        index: usize,
+        expected: Option<i64>,
+        actual: Option<i64>,
+    },
+    /// One program halted and the other blocked on input.
+    Halted { expected: bool, actual: bool },
+}
+
+/// Check that `specialized` behaves like `original` run with `known` inputs
+/// first. Both programs then receive `rest` and run until they halt or block.
+pub fn check_equivalence(
+    original: &[i64],
+    specialized: &[i64],
+    known: &[i64],
+    rest: &[i64],
+) -> Result<(), Mismatch> {
+    let mut expected = IntcodeComputer::new(original.to_vec());
+    expected.input.extend(known);
+    expected.input.extend(rest);
+    expected.run();
+
+    let mut actual = IntcodeComputer::new(specialized.to_vec());
+    actual.input.extend(rest);
+    actual.run();
+
+    let len = expected.output.len().max(actual.output.len());
+    for index in 0..len {
+        let (expected, actual) = (expected.output.get(index), actual.output.get(index));
+        if expected != actual {
+            return Err(Mismatch::Output {
+                index,
+                expected: expected.copied(),
+                actual: actual.copied(),
+            });
+        }
+    }
+    if expected.halted != actual.halted {
+        return Err(Mismatch::Halted {
+            expected: expected.halted,
+            actual: actual.halted,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_equivalence, specialize, SpecializeError};
+
+    #[test]
+    fn fold_halting_program() {
+        // output input * 2, input + 1
+        let program = vec![3, 20, 1002, 20, 2, 21, 4, 21, 101, 1, 20, 22, 4, 22, 99];
+        let specialized = specialize(&program, &[21]).unwrap();
+        assert_eq!(specialized, vec![104, 42, 104, 22, 99]);
+        assert_eq!(
+            check_equivalence(&program, &specialized, &[21], &[]),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn resume_blocked_program() {
+        // rb = 5, output input_1, then output input_1 + input_2 until input is 0
+        let program = vec![
+            109, 5, 203, 20, 204, 20, 203, 21, 1206, 21, 20, 22201, 20, 21, 20, 204, 20, 1105, 1,
+            6, 99,
+        ];
+        let specialized = specialize(&program, &[3, 4]).unwrap();
+        assert_ne!(specialized[0], program[0]);
+        for rest in [vec![], vec![0], vec![1, 2, 0]] {
+            assert_eq!(
+                check_equivalence(&program, &specialized, &[3, 4], &rest),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn untouched_memory() {
+        // Store an input at 20, then output the next one and the word at 22.
+        let program = vec![3, 20, 3, 21, 4, 21, 4, 22, 99];
+        let specialized = specialize(&program, &[7]).unwrap();
+        // The read of 22 is folded, the prologue fits after the code, and
+        // the input stored at 20 is never read again, so it is dropped.
+        assert_eq!(specialized[6..9], [104, 0, 99]);
+        assert_eq!(specialized.len(), 16);
+        for rest in [vec![], vec![1]] {
+            assert_eq!(
+                check_equivalence(&program, &specialized, &[7], &rest),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn amplifier() {
+        let program = crate::load::read_file("data/day7").unwrap();
+        for phase in [0, 3, 7] {
+            let specialized = specialize(&program, &[phase]).unwrap();
+            assert!(specialized.len() < program.len());
+            for rest in [vec![0], vec![5], vec![5, 0, 1, 2, 3, 4]] {
+                assert_eq!(
+                    check_equivalence(&program, &specialized, &[phase], &rest),
+                    Ok(())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn dynamic_program() {
+        // The relative base comes from the second input.
+        let program = vec![3, 100, 3, 101, 9, 101, 204, 0, 99];
+        assert_eq!(
+            specialize(&program, &[1]),
+            Err(SpecializeError::Dynamic { ip: 4 })
+        );
+        // Before any input, the program is returned as it is.
+        assert_eq!(specialize(&program, &[]).unwrap(), program);
+    }
+
+    #[test]
+    fn detect_mismatch() {
+        let program = vec![3, 0, 4, 0, 99];
+        let specialized = specialize(&program, &[1]).unwrap();
+        assert!(check_equivalence(&program, &specialized, &[2], &[]).is_err());
+    }
+}
@@ -0,0 +1,624 @@
+//! Symbolic execution of Intcode programs.
+//!
+//! Memory cells and inputs may hold expressions over named variables instead
+//! of plain integers. Arithmetic builds expression trees, and a conditional
+//! jump on a symbolic value forks the machine into two paths, each carrying
+//! the constraint that selected it.
+
+use crate::{IntcodeComputer, ParameterMode};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt, ops,
+    rc::Rc,
+};
+
+/// A value computed by a symbolic run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Const(i64),
+    Var(Rc<str>),
+    Add(Rc<Expr>, Rc<Expr>),
+    Mul(Rc<Expr>, Rc<Expr>),
+    /// 1 if lhs < rhs, otherwise 0.
+    Lt(Rc<Expr>, Rc<Expr>),
+    /// 1 if lhs == rhs, otherwise 0.
+    Eq(Rc<Expr>, Rc<Expr>),
+    /// A read through a symbolic address, from memory as it was at the time
+    /// of the read.
+    Load(Rc<Expr>, Rc<Vec<Expr>>),
+}
+
+impl Expr {
+    pub fn var(name: &str) -> Self {
+        Self::Var(name.into())
+    }
+
+    pub fn as_const(&self) -> Option<i64> {
+        match self {
+            Self::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn less_than(lhs: Expr, rhs: Expr) -> Self {
+        match (&lhs, &rhs) {
+            (Self::Const(a), Self::Const(b)) => Self::Const((a < b) as i64),
+            _ if lhs == rhs => Self::Const(0),
+            _ => Self::Lt(Rc::new(lhs), Rc::new(rhs)),
+        }
+    }
+
+    pub fn equals(lhs: Expr, rhs: Expr) -> Self {
+        match (&lhs, &rhs) {
+            (Self::Const(a), Self::Const(b)) => Self::Const((a == b) as i64),
+            _ if lhs == rhs => Self::Const(1),
+            _ => Self::Eq(Rc::new(lhs), Rc::new(rhs)),
+        }
+    }
+
+    pub fn load(addr: Expr, mem: Rc<Vec<Expr>>) -> Self {
+        match addr {
+            Self::Const(addr) if addr >= 0 => {
+                mem.get(addr as usize).cloned().unwrap_or(Self::Const(0))
+            }
+            _ => Self::Load(Rc::new(addr), mem),
+        }
+    }
+
+    /// Evaluate the expression with every variable bound by `env`. Returns
+    /// None if a variable is unbound or a load address is negative.
+    pub fn eval(&self, env: &HashMap<&str, i64>) -> Option<i64> {
+        Some(match self {
+            Self::Const(value) => *value,
+            Self::Var(name) => *env.get(&**name)?,
+            Self::Add(lhs, rhs) => lhs.eval(env)?.wrapping_add(rhs.eval(env)?),
+            Self::Mul(lhs, rhs) => lhs.eval(env)?.wrapping_mul(rhs.eval(env)?),
+            Self::Lt(lhs, rhs) => (lhs.eval(env)? < rhs.eval(env)?) as i64,
+            Self::Eq(lhs, rhs) => (lhs.eval(env)? == rhs.eval(env)?) as i64,
+            Self::Load(addr, mem) => {
+                let addr = addr.eval(env)?;
+                if addr < 0 {
+                    return None;
+                }
+                match mem.get(addr as usize) {
+                    Some(value) => value.eval(env)?,
+                    None => 0,
+                }
+            }
+        })
+    }
+
+    /// Rewrite the expression as a linear combination of its variables, if
+    /// it is one.
+    pub fn linear(&self) -> Option<Linear> {
+        match self {
+            Self::Const(value) => Some(Linear {
+                coefficients: BTreeMap::new(),
+                constant: *value,
+            }),
+            Self::Var(name) => Some(Linear {
+                coefficients: BTreeMap::from([(name.clone(), 1)]),
+                constant: 0,
+            }),
+            Self::Add(lhs, rhs) => {
+                let mut result = lhs.linear()?;
+                let rhs = rhs.linear()?;
+                for (name, coefficient) in rhs.coefficients {
+                    let sum = result.coefficients.entry(name).or_default();
+                    *sum = sum.wrapping_add(coefficient);
+                }
+                result.constant = result.constant.wrapping_add(rhs.constant);
+                result.coefficients.retain(|_, c| *c != 0);
+                Some(result)
+            }
+            Self::Mul(lhs, rhs) => {
+                let (lhs, rhs) = (lhs.linear()?, rhs.linear()?);
+                let (factor, mut result) = if lhs.coefficients.is_empty() {
+                    (lhs.constant, rhs)
+                } else if rhs.coefficients.is_empty() {
+                    (rhs.constant, lhs)
+                } else {
+                    return None;
+                };
+                for coefficient in result.coefficients.values_mut() {
+                    *coefficient = coefficient.wrapping_mul(factor);
+                }
+                result.constant = result.constant.wrapping_mul(factor);
+                result.coefficients.retain(|_, c| *c != 0);
+                Some(result)
+            }
+            Self::Lt(..) | Self::Eq(..) | Self::Load(..) => None,
+        }
+    }
+}
+
+impl ops::Add for Expr {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        match (&self, &rhs) {
+            (Self::Const(a), Self::Const(b)) => Self::Const(a.wrapping_add(*b)),
+            (Self::Const(0), _) => rhs,
+            (_, Self::Const(0)) => self,
+            _ => Self::Add(Rc::new(self), Rc::new(rhs)),
+        }
+    }
+}
+
+impl ops::Mul for Expr {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        match (&self, &rhs) {
+            (Self::Const(a), Self::Const(b)) => Self::Const(a.wrapping_mul(*b)),
+            (Self::Const(0), _) | (_, Self::Const(0)) => Self::Const(0),
+            (Self::Const(1), _) => rhs,
+            (_, Self::Const(1)) => self,
+            _ => Self::Mul(Rc::new(self), Rc::new(rhs)),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Const(value) => write!(f, "{value}"),
+            Self::Var(name) => write!(f, "{name}"),
+            Self::Add(lhs, rhs) => write!(f, "({lhs} + {rhs})"),
+            Self::Mul(lhs, rhs) => write!(f, "({lhs} * {rhs})"),
+            Self::Lt(lhs, rhs) => write!(f, "({lhs} < {rhs})"),
+            Self::Eq(lhs, rhs) => write!(f, "({lhs} == {rhs})"),
+            Self::Load(addr, _) => write!(f, "mem[{addr}]"),
+        }
+    }
+}
+
+/// A linear expression `sum(coefficient * variable) + constant`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Linear {
+    pub coefficients: BTreeMap<Rc<str>, i64>,
+    pub constant: i64,
+}
+
+/// A condition a path took: `expr != 0` if `nonzero`, otherwise `expr == 0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub expr: Expr,
+    pub nonzero: bool,
+}
+
+impl Constraint {
+    /// Check whether the constraint holds under `env`.
+    pub fn holds(&self, env: &HashMap<&str, i64>) -> Option<bool> {
+        Some((self.expr.eval(env)? != 0) == self.nonzero)
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.nonzero {
+            write!(f, "{} != 0", self.expr)
+        } else {
+            write!(f, "{} == 0", self.expr)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SymbolicError {
+    /// The instruction at `ip` is not a constant.
+    SymbolicInstruction {
+        ip: usize,
+    },
+    /// A write address at `ip` depends on a variable.
+    SymbolicWriteAddress {
+        ip: usize,
+    },
+    /// A jump target at `ip` depends on a variable.
+    SymbolicJump {
+        ip: usize,
+    },
+    /// The relative base offset at `ip` depends on a variable.
+    SymbolicRelativeBase {
+        ip: usize,
+    },
+    /// The instruction at `ip` accessed a negative address.
+    NegativeAddress {
+        ip: usize,
+    },
+    /// The relative base, or an address relative to it, at `ip` overflowed.
+    Overflow {
+        ip: usize,
+    },
+    UnknownOpcode {
+        ip: usize,
+        opcode: i64,
+    },
+    /// The instruction at `ip` has an unknown parameter mode.
+    InvalidMode {
+        ip: usize,
+    },
+    /// The instruction at `ip` writes to an immediate parameter.
+    ImmediateWrite {
+        ip: usize,
+    },
+    /// Exploration produced more than this many paths.
+    PathLimit(usize),
+}
+
+impl fmt::Display for SymbolicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SymbolicInstruction { ip } => write!(f, "symbolic instruction at ip = {ip}"),
+            Self::SymbolicWriteAddress { ip } => write!(f, "symbolic write address at ip = {ip}"),
+            Self::SymbolicJump { ip } => write!(f, "symbolic jump target at ip = {ip}"),
+            Self::SymbolicRelativeBase { ip } => write!(f, "symbolic relative base at ip = {ip}"),
+            Self::NegativeAddress { ip } => write!(f, "negative address at ip = {ip}"),
+            Self::Overflow { ip } => write!(f, "relative base overflow at ip = {ip}"),
+            Self::UnknownOpcode { ip, opcode } => write!(f, "unknown opcode {opcode} at ip = {ip}"),
+            Self::InvalidMode { ip } => write!(f, "unknown parameter mode at ip = {ip}"),
+            Self::ImmediateWrite { ip } => write!(f, "immediate write address at ip = {ip}"),
+            Self::PathLimit(limit) => write!(f, "more than {limit} paths"),
+        }
+    }
+}
+
+impl std::error::Error for SymbolicError {}
+
+/// The outcome of a single symbolic step.
+#[derive(Debug)]
+pub enum Step {
+    Executed,
+    /// The computer is waiting on input.
+    Blocked,
+    /// A conditional jump on a symbolic value. `self` took the jump; the
+    /// returned computer fell through.
+    Forked(Box<SymbolicComputer>),
+}
+
+#[derive(Debug, Clone)]
+pub struct SymbolicComputer {
+    pub mem: Vec<Expr>,
+    pub ip: usize,
+    pub rb: i64,
+    pub halted: bool,
+    pub input: VecDeque<Expr>,
+    pub output: VecDeque<Expr>,
+    /// Conditions of every symbolic branch taken so far.
+    pub constraints: Vec<Constraint>,
+    /// Memory as of the last write, shared by loads through symbolic addresses.
+    snapshot: Option<Rc<Vec<Expr>>>,
+}
+
+impl SymbolicComputer {
+    /// Create a new SymbolicComputer intialized with `memory`.
+    pub fn new(memory: Vec<i64>) -> Self {
+        Self::from_computer(&IntcodeComputer::new(memory))
+    }
+
+    /// Create a new SymbolicComputer with the same state as `computer`.
+    pub fn from_computer(computer: &IntcodeComputer) -> Self {
+        Self {
+            mem: computer
+                .mem
+                .iter()
+                .map(|&value| Expr::Const(value))
+                .collect(),
+            ip: computer.ip,
+            rb: computer.rb,
+            halted: computer.halted,
+            input: computer
+                .input
+                .iter()
+                .map(|&value| Expr::Const(value))
+                .collect(),
+            output: computer
+                .output
+                .iter()
+                .map(|&value| Expr::Const(value))
+                .collect(),
+            constraints: vec![],
+            snapshot: None,
+        }
+    }
+
+    fn ensure_addr(&mut self, addr: usize) {
+        if self.mem.len() <= addr {
+            self.mem.resize(addr + 1, Expr::Const(0));
+        }
+    }
+
+    /// Fetch the word at `addr`, which must be a constant.
+    fn fetch(&mut self, addr: usize) -> Result<i64, SymbolicError> {
+        self.ensure_addr(addr);
+        self.mem[addr]
+            .as_const()
+            .ok_or(SymbolicError::SymbolicInstruction { ip: self.ip })
+    }
+
+    /// Read the n-th parameter of the current instruction.
+    fn read(&mut self, modes: i64, n: usize) -> Result<Expr, SymbolicError> {
+        let ip = self.ip;
+        self.ensure_addr(ip + n);
+        let param = self.mem[ip + n].clone();
+        let mode = ParameterMode::try_new(modes, n).ok_or(SymbolicError::InvalidMode { ip })?;
+        let addr = match (mode, param) {
+            (ParameterMode::Immediate, param) => return Ok(param),
+            (ParameterMode::Position, param) => param,
+            (ParameterMode::Relative, Expr::Const(offset)) => Expr::Const(
+                self.rb
+                    .checked_add(offset)
+                    .ok_or(SymbolicError::Overflow { ip })?,
+            ),
+            (ParameterMode::Relative, param) => Expr::Const(self.rb) + param,
+        };
+        match addr {
+            Expr::Const(addr) if addr < 0 => Err(SymbolicError::NegativeAddress { ip }),
+            Expr::Const(addr) => {
+                self.ensure_addr(addr as usize);
+                Ok(self.mem[addr as usize].clone())
+            }
+            addr => {
+                let mem = self
+                    .snapshot
+                    .get_or_insert_with(|| Rc::new(self.mem.clone()))
+                    .clone();
+                Ok(Expr::load(addr, mem))
+            }
+        }
+    }
+
+    /// Write `value` to the address given by the n-th parameter.
+    fn write(&mut self, modes: i64, n: usize, value: Expr) -> Result<(), SymbolicError> {
+        let ip = self.ip;
+        let param = self
+            .fetch(ip + n)
+            .map_err(|_| SymbolicError::SymbolicWriteAddress { ip })?;
+        let addr = match ParameterMode::try_new(modes, n) {
+            Some(ParameterMode::Position) => param,
+            Some(ParameterMode::Immediate) => return Err(SymbolicError::ImmediateWrite { ip }),
+            Some(ParameterMode::Relative) => self
+                .rb
+                .checked_add(param)
+                .ok_or(SymbolicError::Overflow { ip })?,
+            None => return Err(SymbolicError::InvalidMode { ip }),
+        };
+        if addr < 0 {
+            return Err(SymbolicError::NegativeAddress { ip });
+        }
+        self.ensure_addr(addr as usize);
+        self.mem[addr as usize] = value;
+        self.snapshot = None;
+        Ok(())
+    }
+
+    /// Read a jump target, which must be a non-negative constant.
+    fn target(&mut self, modes: i64) -> Result<usize, SymbolicError> {
+        let ip = self.ip;
+        match self.read(modes, 2)?.as_const() {
+            Some(addr) if addr >= 0 => Ok(addr as usize),
+            Some(_) => Err(SymbolicError::NegativeAddress { ip }),
+            None => Err(SymbolicError::SymbolicJump { ip }),
+        }
+    }
+
+    /// Jump to the second parameter if `cond` is nonzero (JNZ) or zero (JZ),
+    /// forking if `cond` is symbolic.
+    fn branch(
+        &mut self,
+        modes: i64,
+        cond: Expr,
+        jump_if_nonzero: bool,
+    ) -> Result<Step, SymbolicError> {
+        if let Some(cond) = cond.as_const() {
+            if (cond != 0) == jump_if_nonzero {
+                self.ip = self.target(modes)?;
+            } else {
+                self.ip += 3;
+            }
+            return Ok(Step::Executed);
+        }
+
+        let mut fallthrough = self.clone();
+        fallthrough.ip += 3;
+        fallthrough.constraints.push(Constraint {
+            expr: cond.clone(),
+            nonzero: !jump_if_nonzero,
+        });
+        self.ip = self.target(modes)?;
+        self.constraints.push(Constraint {
+            expr: cond,
+            nonzero: jump_if_nonzero,
+        });
+        Ok(Step::Forked(Box::new(fallthrough)))
+    }
+
+    /// Execute a single instruction.
+    pub fn step(&mut self) -> Result<Step, SymbolicError> {
+        let ip = self.ip;
+        let instruction = self.fetch(ip)?;
+        let opcode = instruction % 100;
+        let modes = instruction / 100;
+
+        if opcode == 99 {
+            self.halted = true;
+        } else if opcode == 1 {
+            // ADD lhs,rhs,addr
+            let lhs = self.read(modes, 1)?;
+            let rhs = self.read(modes, 2)?;
+            self.write(modes, 3, lhs + rhs)?;
+            self.ip += 4;
+        } else if opcode == 2 {
+            // MUL lhs,rhs,addr
+            let lhs = self.read(modes, 1)?;
+            let rhs = self.read(modes, 2)?;
+            self.write(modes, 3, lhs * rhs)?;
+            self.ip += 4;
+        } else if opcode == 3 {
+            // INPUT addr
+            if let Some(value) = self.input.pop_front() {
+                self.write(modes, 1, value)?;
+                self.ip += 2;
+            } else {
+                return Ok(Step::Blocked);
+            }
+        } else if opcode == 4 {
+            // OUTPUT value
+            let value = self.read(modes, 1)?;
+            self.output.push_back(value);
+            self.ip += 2;
+        } else if opcode == 5 {
+            // JNZ cond,addr
+            let cond = self.read(modes, 1)?;
+            return self.branch(modes, cond, true);
+        } else if opcode == 6 {
+            // JZ cond,addr
+            let cond = self.read(modes, 1)?;
+            return self.branch(modes, cond, false);
+        } else if opcode == 7 {
+            // LT lhs,rhs,addr
+            let lhs = self.read(modes, 1)?;
+            let rhs = self.read(modes, 2)?;
+            self.write(modes, 3, Expr::less_than(lhs, rhs))?;
+            self.ip += 4;
+        } else if opcode == 8 {
+            // EQ lhs,rhs,addr
+            let lhs = self.read(modes, 1)?;
+            let rhs = self.read(modes, 2)?;
+            self.write(modes, 3, Expr::equals(lhs, rhs))?;
+            self.ip += 4;
+        } else if opcode == 9 {
+            // RB delta
+            let delta = self.read(modes, 1)?;
+            let delta = delta
+                .as_const()
+                .ok_or(SymbolicError::SymbolicRelativeBase { ip })?;
+            self.rb = self
+                .rb
+                .checked_add(delta)
+                .ok_or(SymbolicError::Overflow { ip })?;
+            self.ip += 2;
+        } else {
+            return Err(SymbolicError::UnknownOpcode { ip, opcode });
+        }
+
+        Ok(Step::Executed)
+    }
+
+    /// Run every path until it halts or blocks on input, returning the final
+    /// state of each path. Fails if more than `max_paths` paths are created.
+    pub fn explore(self, max_paths: usize) -> Result<Vec<SymbolicComputer>, SymbolicError> {
+        let mut pending = vec![self];
+        let mut finished = vec![];
+        let mut paths = 1;
+        while let Some(mut pc) = pending.pop() {
+            loop {
+                if pc.halted {
+                    finished.push(pc);
+                    break;
+                }
+                match pc.step()? {
+                    Step::Executed => {}
+                    Step::Blocked => {
+                        finished.push(pc);
+                        break;
+                    }
+                    Step::Forked(other) => {
+                        paths += 1;
+                        if paths > max_paths {
+                            return Err(SymbolicError::PathLimit(max_paths));
+                        }
+                        pending.push(*other);
+                    }
+                }
+            }
+        }
+        Ok(finished)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Expr, SymbolicComputer, SymbolicError};
+    use std::collections::HashMap;
+
+    #[test]
+    fn linear_memory() {
+        // mem[0] = mem[9] * 3 + mem[10]
+        let mut pc = SymbolicComputer::new(vec![1002, 9, 3, 11, 1, 11, 10, 0, 99, 0, 0, 0]);
+        pc.mem[9] = Expr::var("a");
+        pc.mem[10] = Expr::var("b");
+        let paths = pc.explore(1).unwrap();
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].halted);
+
+        let linear = paths[0].mem[0].linear().unwrap();
+        assert_eq!(linear.coefficients["a"], 3);
+        assert_eq!(linear.coefficients["b"], 1);
+        assert_eq!(linear.constant, 0);
+    }
+
+    #[test]
+    fn fork_on_input() {
+        // output 1 if input < 10, otherwise output 2
+        let mut pc = SymbolicComputer::new(vec![
+            3, 20, 1007, 20, 10, 21, 1005, 21, 12, 104, 2, 99, 104, 1, 99,
+        ]);
+        pc.input.push_back(Expr::var("x"));
+        let paths = pc.explore(2).unwrap();
+        assert_eq!(paths.len(), 2);
+
+        for x in [5, 10, 15] {
+            let env = HashMap::from([("x", x)]);
+            let taken: Vec<_> = paths
+                .iter()
+                .filter(|pc| pc.constraints.iter().all(|c| c.holds(&env) == Some(true)))
+                .collect();
+            assert_eq!(taken.len(), 1);
+            let expected = if x < 10 { 1 } else { 2 };
+            assert_eq!(taken[0].output[0].eval(&env), Some(expected));
+        }
+    }
+
+    #[test]
+    fn symbolic_address() {
+        // output mem[input]
+        let mut pc = SymbolicComputer::new(vec![3, 3, 4, 0, 99, 42]);
+        pc.input.push_back(Expr::var("x"));
+        let paths = pc.explore(1).unwrap();
+        let output = &paths[0].output[0];
+        assert_eq!(output.eval(&HashMap::from([("x", 5)])), Some(42));
+        assert_eq!(output.eval(&HashMap::from([("x", 100)])), Some(0));
+    }
+
+    #[test]
+    fn invalid_programs() {
+        let error = |program: Vec<i64>| SymbolicComputer::new(program).explore(1).unwrap_err();
+        // ADD 1,1,1 with the write address in immediate mode.
+        assert_eq!(
+            error(vec![11101, 1, 1, 1, 99]),
+            SymbolicError::ImmediateWrite { ip: 0 }
+        );
+        assert_eq!(
+            error(vec![304, 0, 99]),
+            SymbolicError::InvalidMode { ip: 0 }
+        );
+        assert_eq!(
+            error(vec![109, i64::MAX, 109, 1, 99]),
+            SymbolicError::Overflow { ip: 2 }
+        );
+
+        // Arithmetic wraps like the computer's.
+        let pc = SymbolicComputer::new(vec![1102, i64::MAX, 2, 0, 1101, i64::MAX, 1, 1, 99]);
+        let paths = pc.explore(1).unwrap();
+        assert_eq!(paths[0].mem[0], Expr::Const(-2));
+        assert_eq!(paths[0].mem[1], Expr::Const(i64::MIN));
+    }
+
+    #[test]
+    fn path_limit() {
+        let mut pc = SymbolicComputer::new(vec![3, 20, 1005, 20, 0, 99]);
+        pc.input.push_back(Expr::var("x"));
+        assert!(pc.explore(1).is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Recording and replaying the I/O of an Intcode session.
+//!
+//! A transcript file has one event per line: `in <value>` for a consumed
+//! input and `out <value>` for a produced output, in the order they
+//! happened, followed by `halt` if the program halted.
+
+use crate::IntcodeComputer;
+use std::{error, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Input(i64),
+    Output(i64),
+}
+
+/// The first difference between a replay and the recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The n-th output differs, or the replay produced a different number
+    /// of outputs.
+    Output {
+        index: usize,
+        expected: Option<i64>,
+        actual: Option<i64>,
+    },
+    /// The recording halted and the replay blocked on input, or the reverse.
+    Halted { expected: bool, actual: bool },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |value: &Option<i64>| match value {
+            Some(value) => value.to_string(),
+            None => "nothing".to_string(),
+        };
+        match self {
+            Self::Output {
+                index,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Output {index} is {} instead of {}",
+                value(actual),
+                value(expected)
+            ),
+            Self::Halted { expected: true, .. } => f.write_str("Blocked instead of halting"),
+            Self::Halted { .. } => f.write_str("Halted instead of blocking"),
+        }
+    }
+}
+
+impl error::Error for ReplayError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TranscriptError {
+    /// The transcript file couldn't be read or written.
+    Io { file: String, message: String },
+    /// Line `line` of the transcript is invalid.
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for TranscriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { file, message } => write!(f, "{file}: {message}"),
+            Self::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl error::Error for TranscriptError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transcript {
+    pub events: Vec<Event>,
+    pub halted: bool,
+}
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `pc` like `IntcodeComputer::run`, recording its I/O.
+    pub fn run(&mut self, pc: &mut IntcodeComputer) {
+        // Built-in opcodes only consume input from the front of the queue.
+        let inputs: Vec<i64> = pc.input.iter().copied().collect();
+        let mut consumed = 0;
+        pc.run_observed(|pc, (input_len, output_len)| {
+            if pc.input.len() < input_len {
+                self.events.push(Event::Input(inputs[consumed]));
+                consumed += 1;
+            }
+            if pc.output.len() > output_len {
+                self.events.push(Event::Output(*pc.output.back().unwrap()));
+            }
+        });
+        self.halted = pc.halted;
+    }
+
+    /// The recorded inputs.
+    pub fn inputs(&self) -> impl Iterator<Item = i64> + '_ {
+        self.events.iter().filter_map(|&event| match event {
+            Event::Input(value) => Some(value),
+            Event::Output(_) => None,
+        })
+    }
+
+    /// The recorded outputs.
+    pub fn outputs(&self) -> impl Iterator<Item = i64> + '_ {
+        self.events.iter().filter_map(|&event| match event {
+            Event::Input(_) => None,
+            Event::Output(value) => Some(value),
+        })
+    }
+
+    /// Feed the recorded inputs to `pc`, run it, and check that it produces
+    /// the recorded outputs and halts or blocks like the recording did.
+    pub fn replay(&self, pc: &mut IntcodeComputer) -> Result<(), ReplayError> {
+        pc.input.extend(self.inputs());
+        let mut replay = Transcript::new();
+        replay.run(pc);
+
+        let expected: Vec<i64> = self.outputs().collect();
+        let actual: Vec<i64> = replay.outputs().collect();
+        for index in 0..expected.len().max(actual.len()) {
+            let (expected, actual) = (expected.get(index), actual.get(index));
+            if expected != actual {
+                return Err(ReplayError::Output {
+                    index,
+                    expected: expected.copied(),
+                    actual: actual.copied(),
+                });
+            }
+        }
+        if self.halted != replay.halted {
+            return Err(ReplayError::Halted {
+                expected: self.halted,
+                actual: replay.halted,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, TranscriptError> {
+        let mut result = Self::new();
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: &str| TranscriptError::Parse {
+                line: i + 1,
+                message: message.to_string(),
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let value = |value: &str| value.parse().map_err(|_| error("invalid value"));
+            match line.split_once(' ') {
+                Some(("in", rest)) => result.events.push(Event::Input(value(rest)?)),
+                Some(("out", rest)) => result.events.push(Event::Output(value(rest)?)),
+                None if line == "halt" => result.halted = true,
+                _ => return Err(error("unknown event")),
+            }
+        }
+        Ok(result)
+    }
+
+    /// Read a transcript from `file`.
+    pub fn load(file: &str) -> Result<Self, TranscriptError> {
+        let text = std::fs::read_to_string(file).map_err(|error| TranscriptError::Io {
+            file: file.to_string(),
+            message: error.to_string(),
+        })?;
+        Self::parse(&text)
+    }
+
+    /// Write the transcript to `file` in the format `load` reads.
+    pub fn save(&self, file: &str) -> Result<(), TranscriptError> {
+        let mut text = String::new();
+        for event in &self.events {
+            match event {
+                Event::Input(value) => text += &format!("in {value}\n"),
+                Event::Output(value) => text += &format!("out {value}\n"),
+            }
+        }
+        if self.halted {
+            text += "halt\n";
+        }
+        std::fs::write(file, text).map_err(|error| TranscriptError::Io {
+            file: file.to_string(),
+            message: error.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Event, ReplayError, Transcript, TranscriptError};
+    use crate::{Fault, IntcodeComputer};
+
+    // Output each input doubled until it is zero.
+    // IN [15]; JZ [15],14; MUL [15],2,[15]; OUT [15]; JZ 0,0; HALT
+    const PROGRAM: [i64; 16] = [
+        3, 15, 1006, 15, 14, 1002, 15, 2, 15, 4, 15, 1106, 0, 0, 99, 0,
+    ];
+
+    #[test]
+    fn record() {
+        let mut pc = IntcodeComputer::new(PROGRAM.to_vec());
+        let mut transcript = Transcript::new();
+        pc.input.extend([3, 5]);
+        transcript.run(&mut pc);
+        assert!(!transcript.halted);
+        pc.input.push_back(0);
+        transcript.run(&mut pc);
+        assert!(transcript.halted);
+        assert_eq!(
+            transcript.events,
+            [
+                Event::Input(3),
+                Event::Output(6),
+                Event::Input(5),
+                Event::Output(10),
+                Event::Input(0)
+            ]
+        );
+    }
+
+    #[test]
+    fn replay() {
+        let transcript = Transcript {
+            events: vec![Event::Input(3), Event::Output(6), Event::Input(0)],
+            halted: true,
+        };
+        assert_eq!(
+            transcript.replay(&mut IntcodeComputer::new(PROGRAM.to_vec())),
+            Ok(())
+        );
+
+        let mut pc = IntcodeComputer::new(PROGRAM.to_vec());
+        pc.mem[7] = 3;
+        assert_eq!(
+            transcript.replay(&mut pc),
+            Err(ReplayError::Output {
+                index: 0,
+                expected: Some(6),
+                actual: Some(9)
+            })
+        );
+
+        let transcript = Transcript {
+            halted: false,
+            ..transcript
+        };
+        assert_eq!(
+            transcript.replay(&mut IntcodeComputer::new(PROGRAM.to_vec())),
+            Err(ReplayError::Halted {
+                expected: false,
+                actual: true
+            })
+        );
+    }
+    #[test]
+    fn parse() {
+        let transcript = Transcript::parse("in 3\nout 6\n\nin 0\nhalt\n").unwrap();
+        assert_eq!(
+            transcript.events,
+            [Event::Input(3), Event::Output(6), Event::Input(0)]
+        );
+        assert!(transcript.halted);
+        assert_eq!(
+            Transcript::parse("in 3\nout six"),
+            Err(TranscriptError::Parse {
+                line: 2,
+                message: "invalid value".to_string()
+            })
+        );
+        assert_eq!(
+            Transcript::parse("jump 3"),
+            Err(TranscriptError::Parse {
+                line: 1,
+                message: "unknown event".to_string()
+            })
+        );
+        assert!(matches!(
+            Transcript::load("data/missing_transcript"),
+            Err(TranscriptError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn detect_loops() {
+        // OUT 1; JZ 0,3
+        let mut pc = IntcodeComputer::new(vec![104, 1, 1106, 0, 2]);
+        pc.limits.detect_loops = true;
+        let mut transcript = Transcript::new();
+        transcript.run(&mut pc);
+        assert!(matches!(pc.fault, Some(Fault::LoopDetected { .. })));
+        assert_eq!(transcript.events, [Event::Output(1)]);
+    }
+}
@@ -0,0 +1,292 @@
+//! Ahead-of-time translation of Intcode programs to Rust source.
+//!
+//! The generated module has a single entry point,
+//! `pub fn run(input: &mut impl Iterator<Item = i64>, out: &mut Vec<i64>) -> Result<(), Fault>`,
+//! which runs the program until it halts, faults or `input` runs dry. Every
+//! reachable instruction becomes an arm of a `match` on the instruction
+//! pointer. Operands that the program overwrites are read from memory when
+//! the instruction runs. Execution falls back to `IntcodeComputer` when it
+//! reaches an address that wasn't translated or writes to any other word of
+//! translated code, and programs that rewrite their own opcodes are not
+//! translated at all.
+
+use crate::{
+    asm::{Instruction, Opcode, Param},
+    optimize::analyze,
+    ParameterMode,
+};
+use std::{collections::HashSet, fmt::Write};
+
+/// Functions shared by every generated module.
+const RUNTIME: &str = r#"
+fn read(mem: &[i64], addr: i64) -> Result<i64, Fault> {
+    if addr < 0 {
+        return Err(Fault::NegativeAddress { addr });
+    }
+    Ok(mem.get(addr as usize).copied().unwrap_or(0))
+}
+
+/// Write `value` to `addr`. Returns true if the write changed translated code.
+fn write(mem: &mut Vec<i64>, addr: i64, value: i64) -> Result<bool, Fault> {
+    if addr < 0 {
+        return Err(Fault::NegativeAddress { addr });
+    }
+    let addr = addr as usize;
+    if addr > MAX_ADDR {
+        return Err(Fault::MemoryLimit { addr });
+    }
+    if mem.len() <= addr {
+        mem.resize(addr + 1, 0);
+    }
+    mem[addr] = value;
+    Ok(is_code(addr))
+}
+
+fn relative(rb: i64, offset: i64) -> Result<i64, Fault> {
+    rb.checked_add(offset).ok_or(Fault::InvalidAddress)
+}
+
+fn jump(addr: i64) -> Result<usize, Fault> {
+    if addr < 0 {
+        return Err(Fault::NegativeAddress { addr });
+    }
+    Ok(addr as usize)
+}
+
+/// Continue running on the interpreter from the given state.
+fn interpret(
+    mem: Vec<i64>,
+    ip: usize,
+    rb: i64,
+    input: &mut impl Iterator<Item = i64>,
+    out: &mut Vec<i64>,
+) -> Result<(), Fault> {
+    let mut pc = IntcodeComputer::new(mem);
+    pc.ip = ip;
+    pc.rb = rb;
+    loop {
+        pc.run();
+        out.extend(pc.output.drain(..));
+        if let Some(fault) = pc.fault {
+            return Err(fault);
+        }
+        if pc.halted {
+            return Ok(());
+        }
+        match input.next() {
+            Some(value) => pc.input.push_back(value),
+            None => return Ok(()),
+        }
+    }
+}
+"#;
+
+/// Rust expression for the value of the n-th parameter of the instruction at
+/// `addr`. Operands that the program overwrites are read from memory.
+fn operand(addr: usize, n: usize, param: Param, written: &HashSet<usize>) -> String {
+    if written.contains(&(addr + n)) {
+        format!("mem[{}]", addr + n)
+    } else if param.value < 0 {
+        format!("({})", param.value)
+    } else {
+        param.value.to_string()
+    }
+}
+
+/// Rust expression for reading a parameter with the given operand.
+fn read(mode: ParameterMode, operand: &str) -> String {
+    match mode {
+        ParameterMode::Position => format!("read(&mem, {operand})?"),
+        ParameterMode::Immediate => operand.to_string(),
+        ParameterMode::Relative => format!("read(&mem, relative(rb, {operand})?)?"),
+    }
+}
+
+/// Rust statements storing `value` to the address given by a parameter with
+/// the given operand, then moving on to `next`.
+fn write(mode: ParameterMode, operand: &str, next: usize) -> String {
+    let addr = match mode {
+        ParameterMode::Relative => format!("relative(rb, {operand})?"),
+        _ => operand.to_string(),
+    };
+    format!("ip = {next};\n{INDENT}if write(&mut mem, {addr}, value)? {{\n{INDENT}    break;\n{INDENT}}}")
+}
+
+/// Indentation of the statements in a generated match arm.
+const INDENT: &str = "                ";
+
+/// Rust statements executing `instruction` at `addr`.
+fn translate_instruction(
+    addr: usize,
+    instruction: &Instruction,
+    written: &HashSet<usize>,
+) -> String {
+    let params = &instruction.params;
+    let next = addr + instruction.size();
+    let operands: Vec<String> = params
+        .iter()
+        .enumerate()
+        .map(|(i, &param)| operand(addr, i + 1, param, written))
+        .collect();
+    let read = |n: usize| read(params[n].mode, &operands[n]);
+    let write = |n: usize| write(params[n].mode, &operands[n], next);
+    let binary = |op: &str| {
+        // Arithmetic wraps like the interpreter's.
+        let value = match op {
+            "<" | "==" => format!("({} {op} {}) as i64", read(0), read(1)),
+            _ => format!("i64::{op}({}, {})", read(0), read(1)),
+        };
+        format!("let value = {value};\n{INDENT}{}", write(2))
+    };
+
+    match instruction.opcode {
+        Opcode::Add => binary("wrapping_add"),
+        Opcode::Mul => binary("wrapping_mul"),
+        Opcode::Lt => binary("<"),
+        Opcode::Eq => binary("=="),
+        Opcode::Input => format!(
+            "let Some(value) = input.next() else {{\n{INDENT}    return Ok(());\n{INDENT}}};\n{INDENT}{}",
+            write(0)
+        ),
+        Opcode::Output => format!("out.push({});\n{INDENT}ip = {next};", read(0)),
+        Opcode::Jnz | Opcode::Jz => {
+            let op = if instruction.opcode == Opcode::Jnz {
+                "!="
+            } else {
+                "=="
+            };
+            let target = match params[1].mode {
+                ParameterMode::Immediate if operands[1].parse::<usize>().is_ok() => {
+                    operands[1].clone()
+                }
+                _ => format!("jump({})?", read(1)),
+            };
+            format!(
+                "ip = if {} {op} 0 {{ {target} }} else {{ {next} }};",
+                read(0)
+            )
+        }
+        Opcode::Rb => format!(
+            "rb = rb.checked_add({}).ok_or(Fault::Overflow)?;\n{INDENT}ip = {next};",
+            read(0)
+        ),
+        Opcode::Halt => "return Ok(());".to_string(),
+    }
+}
+
+/// Translate `program` to the source of a Rust module.
+pub fn translate(program: &[i64]) -> String {
+    let mut result = String::new();
+    writeln!(result, "//! Generated by aoc2019::translate. Do not edit.").unwrap();
+    writeln!(result).unwrap();
+    writeln!(result, "#![allow(clippy::all, unused)]").unwrap();
+    writeln!(result).unwrap();
+    writeln!(result, "use aoc2019::{{Fault, IntcodeComputer, MAX_ADDR}};").unwrap();
+    writeln!(result).unwrap();
+    writeln!(
+        result,
+        "const PROGRAM: [i64; {}] = {:?};",
+        program.len(),
+        program
+    )
+    .unwrap();
+
+    let Some(analysis) = analyze(program) else {
+        // Self-modifying code is left to the interpreter.
+        writeln!(result).unwrap();
+        writeln!(
+            result,
+            "pub fn run(input: &mut impl Iterator<Item = i64>, out: &mut Vec<i64>) -> Result<(), Fault> {{"
+        )
+        .unwrap();
+        writeln!(result, "    interpret(PROGRAM.to_vec(), 0, 0, input, out)").unwrap();
+        writeln!(result, "}}").unwrap();
+        writeln!(result).unwrap();
+        writeln!(result, "fn is_code(addr: usize) -> bool {{\n    false\n}}").unwrap();
+        result += RUNTIME;
+        return result;
+    };
+
+    writeln!(result).unwrap();
+    writeln!(
+        result,
+        "pub fn run(input: &mut impl Iterator<Item = i64>, out: &mut Vec<i64>) -> Result<(), Fault> {{"
+    )
+    .unwrap();
+    writeln!(result, "    let mut mem = PROGRAM.to_vec();").unwrap();
+    writeln!(result, "    let mut ip: usize = 0;").unwrap();
+    writeln!(result, "    let mut rb: i64 = 0;").unwrap();
+    writeln!(result, "    loop {{").unwrap();
+    writeln!(result, "        match ip {{").unwrap();
+    for (&addr, instruction) in &analysis.code {
+        writeln!(result, "            {addr} => {{").unwrap();
+        writeln!(result, "                // {instruction}").unwrap();
+        writeln!(
+            result,
+            "                {}",
+            translate_instruction(addr, instruction, &analysis.written)
+        )
+        .unwrap();
+        writeln!(result, "            }}").unwrap();
+    }
+    writeln!(result, "            _ => break,").unwrap();
+    writeln!(result, "        }}").unwrap();
+    writeln!(result, "    }}").unwrap();
+    writeln!(result, "    interpret(mem, ip, rb, input, out)").unwrap();
+    writeln!(result, "}}").unwrap();
+
+    // Merge the words baked into translated code into ranges.
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for (&addr, instruction) in &analysis.code {
+        for word in addr..addr + instruction.size() {
+            if analysis.written.contains(&word) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(last) if last.1 == word => last.1 = word + 1,
+                _ => ranges.push((word, word + 1)),
+            }
+        }
+    }
+    let ranges: Vec<String> = ranges
+        .iter()
+        .map(|(start, end)| format!("{start}..={}", end - 1))
+        .collect();
+    writeln!(result).unwrap();
+    writeln!(result, "fn is_code(addr: usize) -> bool {{").unwrap();
+    writeln!(result, "    matches!(addr, {})", ranges.join(" | ")).unwrap();
+    writeln!(result, "}}").unwrap();
+    result += RUNTIME;
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::translate;
+
+    #[test]
+    fn translate_instructions() {
+        // IN [rb+20]; MUL [20],-2,[21]; OUT [21]; JNZ [21],0; HALT
+        let source = translate(&[203, 20, 1002, 20, -2, 21, 4, 21, 1005, 21, 0, 99]);
+        assert!(source.contains("            2 => {\n                // MUL [20], -2, [21]\n"));
+        assert!(source.contains("let value = i64::wrapping_mul(read(&mem, 20)?, (-2));"));
+        assert!(source.contains("ip = if read(&mem, 21)? != 0 { 0 } else { 11 };"));
+        assert!(source.contains("if write(&mut mem, relative(rb, 20)?, value)? {"));
+        assert!(source.contains("matches!(addr, 0..=11)"));
+    }
+
+    #[test]
+    fn read_written_operands() {
+        // IN [3]; OUT 0; HALT
+        let source = translate(&[3, 3, 104, 0, 99]);
+        assert!(source.contains("out.push(mem[3]);"));
+        assert!(source.contains("matches!(addr, 0..=2 | 4..=4)"));
+    }
+
+    #[test]
+    fn fall_back_on_self_modification() {
+        let source = translate(&[1, 0, 0, 0, 99]);
+        assert!(!source.contains("match ip"));
+        assert!(source.contains("interpret(PROGRAM.to_vec(), 0, 0, input, out)"));
+    }
+}
@@ -0,0 +1,178 @@
+//! Word types an IntcodeComputer can compute with.
+//!
+//! The word type decides what happens when ADD or MUL overflows:
+//!
+//! | Word      | Bits | Overflow policy                          |
+//! |-----------|------|------------------------------------------|
+//! | `i64`     | 64   | [`Overflow::Wrap`]: two's complement     |
+//! | `Checked` | 64   | [`Overflow::Error`]: the computer faults |
+//! | `i128`    | 128  | [`Overflow::Widen`]: exact past 64 bits, faults past 128 |
+//!
+//! Addresses, the relative base and opcodes are still 64-bit, so a word used
+//! as one of those must fit in an i64.
+
+use crate::opcodes::OpcodeTable;
+use alloc::sync::Arc;
+use core::{fmt, hash::Hash};
+#[cfg(feature = "std")]
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Results that don't fit are an error.
+    Error,
+    /// Results wrap around.
+    Wrap,
+    /// Results are computed with a wider word.
+    Widen,
+}
+
+pub trait Word:
+    Copy + Ord + Hash + fmt::Debug + fmt::Display + From<i64> + Send + Sync + 'static
+{
+    const OVERFLOW: Overflow;
+
+    /// `self + rhs`, or None if the result overflows.
+    fn try_add(self, rhs: Self) -> Option<Self>;
+
+    /// `self * rhs`, or None if the result overflows.
+    fn try_mul(self, rhs: Self) -> Option<Self>;
+
+    /// The value as an i64, or None if it doesn't fit.
+    fn to_i64(self) -> Option<i64>;
+
+    /// A shared copy of the builtin opcode table.
+    fn builtin_opcodes() -> Arc<OpcodeTable<Self>>;
+}
+
+/// Define `builtin_opcodes` with a static table for a concrete word type.
+/// Without `std` there is no lazy static, so every call builds a table.
+macro_rules! builtin_opcodes {
+    ($word:ty) => {
+        #[cfg(feature = "std")]
+        fn builtin_opcodes() -> Arc<OpcodeTable<Self>> {
+            static TABLE: LazyLock<Arc<OpcodeTable<$word>>> =
+                LazyLock::new(|| Arc::new(OpcodeTable::builtin()));
+            Arc::clone(&TABLE)
+        }
+
+        #[cfg(not(feature = "std"))]
+        fn builtin_opcodes() -> Arc<OpcodeTable<Self>> {
+            Arc::new(OpcodeTable::builtin())
+        }
+    };
+}
+
+impl Word for i64 {
+    const OVERFLOW: Overflow = Overflow::Wrap;
+
+    fn try_add(self, rhs: Self) -> Option<Self> {
+        Some(self.wrapping_add(rhs))
+    }
+
+    fn try_mul(self, rhs: Self) -> Option<Self> {
+        Some(self.wrapping_mul(rhs))
+    }
+
+    fn to_i64(self) -> Option<i64> {
+        Some(self)
+    }
+
+    builtin_opcodes!(i64);
+}
+
+impl Word for i128 {
+    const OVERFLOW: Overflow = Overflow::Widen;
+
+    fn try_add(self, rhs: Self) -> Option<Self> {
+        self.checked_add(rhs)
+    }
+
+    fn try_mul(self, rhs: Self) -> Option<Self> {
+        self.checked_mul(rhs)
+    }
+
+    fn to_i64(self) -> Option<i64> {
+        self.try_into().ok()
+    }
+
+    builtin_opcodes!(i128);
+}
+
+/// A 64-bit word whose arithmetic reports overflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Checked(pub i64);
+
+impl From<i64> for Checked {
+    fn from(value: i64) -> Self {
+        Self(value)
+    }
+}
+
+impl fmt::Display for Checked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Word for Checked {
+    const OVERFLOW: Overflow = Overflow::Error;
+
+    fn try_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    fn try_mul(self, rhs: Self) -> Option<Self> {
+        self.0.checked_mul(rhs.0).map(Self)
+    }
+
+    fn to_i64(self) -> Option<i64> {
+        Some(self.0)
+    }
+
+    builtin_opcodes!(Checked);
+}
+
+#[cfg(test)]
+mod test {
+    use super::Checked;
+    use crate::{Fault, IntcodeComputer};
+
+    // MUL 4611686018427387904,4,[7]; OUT [7]; HALT
+    const OVERFLOW: [i64; 8] = [1102, 1 << 62, 4, 7, 4, 7, 99, 0];
+
+    #[test]
+    fn large_numbers() {
+        // From day 9: outputs a 16-digit number.
+        let program = vec![1102, 34915192, 34915192, 7, 4, 7, 99, 0];
+        let mut pc = IntcodeComputer::new(program).convert::<Checked>();
+        pc.run();
+        assert_eq!(pc.output, [Checked(1219070632396864)]);
+    }
+
+    #[test]
+    fn wrap() {
+        let mut pc = IntcodeComputer::new(OVERFLOW.to_vec());
+        pc.run();
+        assert!(pc.halted);
+        assert_eq!(pc.output, [0]);
+    }
+
+    #[test]
+    fn error() {
+        let mut pc = IntcodeComputer::new(OVERFLOW.to_vec()).convert::<Checked>();
+        pc.run();
+        assert!(!pc.halted);
+        assert_eq!(pc.fault, Some(Fault::Overflow));
+        assert_eq!(pc.ip, 0);
+        assert!(pc.output.is_empty());
+    }
+
+    #[test]
+    fn widen() {
+        let mut pc = IntcodeComputer::new(OVERFLOW.to_vec()).convert::<i128>();
+        pc.run();
+        assert!(pc.halted);
+        assert_eq!(pc.output, [1 << 64]);
+    }
+}